@@ -0,0 +1,333 @@
+use super::{items, shop};
+use crate::{
+    common::{self, Context, Error},
+    inventory::Inventory,
+};
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{self as serenity, ChannelId, UserId};
+use serde::Deserialize;
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection, PgPool, Row,
+};
+use std::{collections::HashMap, time::Duration};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Reward {
+    Tokens(i32),
+    Item { id: u64, count: u32 },
+}
+
+impl Reward {
+    fn describe(&self) -> String {
+        match self {
+            Self::Tokens(tokens) => format!("**{tokens}** tokens"),
+            Self::Item { id, count } => {
+                format!("{count}x {}", items::get_item_by_id(*id).unwrap().name)
+            }
+        }
+    }
+}
+
+/// An achievement unlocks once the progress a user made on its event reaches the goal
+#[derive(Deserialize)]
+struct Achievement {
+    id: String,
+    name: String,
+    desc: String,
+    event: String,
+    /// Left out for buying every item in the shop, which is however many items it sells
+    #[serde(default)]
+    goal: i64,
+    #[serde(default)]
+    rewards: Vec<Reward>,
+}
+
+impl Achievement {
+    fn describe_rewards(&self) -> Option<String> {
+        (!self.rewards.is_empty()).then(|| {
+            self.rewards
+                .iter()
+                .map(Reward::describe)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    }
+}
+
+static ACHIEVEMENTS: Lazy<Vec<Achievement>> = Lazy::new(|| {
+    let mut achievements: Vec<Achievement> =
+        serde_json::from_str(include_str!("achievements.json"))
+            .expect("achievements.json is malformed");
+
+    for achievement in &mut achievements {
+        if achievement.event == "shop_items" && achievement.goal == 0 {
+            achievement.goal = shop::ITEMS.len() as i64;
+        }
+    }
+
+    for (i, achievement) in achievements.iter().enumerate() {
+        assert!(
+            achievements[..i].iter().all(|a| a.id != achievement.id),
+            "achievements.json has more than one {}",
+            achievement.id
+        );
+        assert!(
+            achievement.goal > 0,
+            "achievement {} has no goal",
+            achievement.id
+        );
+
+        for reward in &achievement.rewards {
+            if let Reward::Item { id, .. } = reward {
+                assert!(
+                    items::get_item_by_id(*id).is_some(),
+                    "achievement {} rewards unknown item {id}",
+                    achievement.id
+                );
+            }
+        }
+    }
+
+    achievements
+});
+
+/// Unlock every achievement for an event that the user's progress has reached, granting
+/// their rewards. The unlocks are announced in `channel` once the transaction is committed.
+async fn unlock(
+    db: &mut PgConnection,
+    user: UserId,
+    channel: ChannelId,
+    event: &str,
+    progress: i64,
+) -> Result<(), Error> {
+    let reached = ACHIEVEMENTS
+        .iter()
+        .filter(|a| a.event == event && a.goal <= progress);
+
+    for achievement in reached {
+        let new = sqlx::query("INSERT INTO achievements (userid, achievement, channel) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING")
+            .bind(user.get() as i64)
+            .bind(&achievement.id)
+            .bind(channel.get() as i64)
+            .execute(&mut *db)
+            .await?
+            .rows_affected()
+            > 0;
+
+        if !new {
+            continue;
+        }
+
+        for reward in &achievement.rewards {
+            match reward {
+                Reward::Tokens(tokens) => {
+                    let balance = super::get_balance(user, &mut *db).await?;
+                    super::change_balance(user, balance + tokens, &mut *db).await?;
+                }
+                Reward::Item { id, count } => {
+                    let item = items::get_item_by_id(*id).unwrap();
+                    let inventory = Inventory::new(user, Some(super::ID));
+
+                    for _ in 0..*count {
+                        inventory
+                            .give_item(&mut *db, item.clone().inv_item())
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Add `n` to a user's progress on an event, like the tokens they gave away
+pub async fn add(
+    db: &mut PgConnection,
+    user: UserId,
+    channel: ChannelId,
+    event: &str,
+    n: i64,
+) -> Result<(), Error> {
+    if n == 0 {
+        return Ok(());
+    }
+
+    let progress: i64 = sqlx::query("INSERT INTO achievement_progress (userid, event, progress) VALUES ($1, $2, $3) ON CONFLICT (userid, event) DO UPDATE SET progress = achievement_progress.progress + EXCLUDED.progress RETURNING progress")
+        .bind(user.get() as i64)
+        .bind(event)
+        .bind(n)
+        .fetch_one(&mut *db)
+        .await?
+        .try_get(0)?;
+
+    unlock(db, user, channel, event, progress).await
+}
+
+/// Raise a user's progress on an event to `value` if it is higher, like their best streak
+pub async fn reach(
+    db: &mut PgConnection,
+    user: UserId,
+    channel: ChannelId,
+    event: &str,
+    value: i64,
+) -> Result<(), Error> {
+    let progress: i64 = sqlx::query("INSERT INTO achievement_progress (userid, event, progress) VALUES ($1, $2, $3) ON CONFLICT (userid, event) DO UPDATE SET progress = GREATEST(achievement_progress.progress, EXCLUDED.progress) RETURNING progress")
+        .bind(user.get() as i64)
+        .bind(event)
+        .bind(value)
+        .fetch_one(&mut *db)
+        .await?
+        .try_get(0)?;
+
+    unlock(db, user, channel, event, progress).await
+}
+
+/// Count `key` toward an event that tracks how many different things a user has done,
+/// like the different items they bought
+pub async fn distinct(
+    db: &mut PgConnection,
+    user: UserId,
+    channel: ChannelId,
+    event: &str,
+    key: &str,
+) -> Result<(), Error> {
+    sqlx::query("INSERT INTO achievement_progress (userid, event, progress) VALUES ($1, $2, 1) ON CONFLICT DO NOTHING")
+        .bind(user.get() as i64)
+        .bind(format!("{event}:{key}"))
+        .execute(&mut *db)
+        .await?;
+
+    let seen: i64 = sqlx::query(
+        "SELECT COUNT(*) FROM achievement_progress WHERE userid = $1 AND starts_with(event, $2)",
+    )
+    .bind(user.get() as i64)
+    .bind(format!("{event}:"))
+    .fetch_one(&mut *db)
+    .await?
+    .try_get(0)?;
+
+    reach(db, user, channel, event, seen).await
+}
+
+/// Announce every unlock that hasn't been yet in the channel it happened in
+async fn announce_unlocked(ctx: &serenity::Context, db: &PgPool) -> Result<(), Error> {
+    let rows = sqlx::query("UPDATE achievements SET announced = true WHERE NOT announced RETURNING userid, achievement, channel")
+        .fetch_all(db)
+        .await?;
+
+    for row in rows {
+        let user: i64 = row.try_get(0)?;
+        let id: String = row.try_get(1)?;
+        let channel = ChannelId::new(row.try_get::<i64, usize>(2)? as u64);
+
+        // definitions can be removed after they were unlocked
+        let Some(achievement) = ACHIEVEMENTS.iter().find(|a| a.id == id) else {
+            continue;
+        };
+
+        let mut announcement = format!(
+            "\u{1F3C6} <@{user}> unlocked **{}**: {}!",
+            achievement.name, achievement.desc
+        );
+
+        if let Some(rewards) = achievement.describe_rewards() {
+            announcement += &format!(" They were rewarded with {rewards}.");
+        }
+
+        // the channel might be gone, the achievement is unlocked either way
+        let _ = channel
+            .send_message(
+                ctx,
+                serenity::CreateMessage::new()
+                    .content(announcement)
+                    .allowed_mentions(serenity::CreateAllowedMentions::new().all_users(true)),
+            )
+            .await;
+    }
+
+    Ok(())
+}
+
+/// Check for new unlocks to announce every few seconds
+pub fn run_schedule(ctx: serenity::Context, db: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = announce_unlocked(&ctx, &db).await {
+                eprintln!("Failed to announce achievements: {e}");
+            }
+        }
+    });
+}
+
+/// See the achievements you or someone else have unlocked and how close the rest are
+#[poise::command(slash_command, prefix_command)]
+pub async fn achievements(ctx: Context<'_>, user: Option<serenity::User>) -> Result<(), Error> {
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let db = &ctx.data().database;
+
+    let unlocked: HashMap<String, DateTime<Utc>> =
+        sqlx::query("SELECT achievement, at FROM achievements WHERE userid = $1")
+            .bind(user.id.get() as i64)
+            .fetch_all(db)
+            .await?
+            .iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect::<Result<_, sqlx::Error>>()?;
+
+    let progress: HashMap<String, i64> =
+        sqlx::query("SELECT event, progress FROM achievement_progress WHERE userid = $1")
+            .bind(user.id.get() as i64)
+            .fetch_all(db)
+            .await?
+            .iter()
+            .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .collect::<Result<_, sqlx::Error>>()?;
+
+    let mut output = format!(
+        "{user} has unlocked **{}/{}** achievements\n\n",
+        ACHIEVEMENTS
+            .iter()
+            .filter(|a| unlocked.contains_key(&a.id))
+            .count(),
+        ACHIEVEMENTS.len()
+    );
+
+    for achievement in ACHIEVEMENTS.iter() {
+        output += &match unlocked.get(&achievement.id) {
+            Some(at) => format!(
+                "\u{2705} **{}**: {}, unlocked <t:{}:d>",
+                achievement.name,
+                achievement.desc,
+                at.timestamp()
+            ),
+            None => format!(
+                "\u{1F512} **{}**: {} ({}/{})",
+                achievement.name,
+                achievement.desc,
+                progress
+                    .get(&achievement.event)
+                    .copied()
+                    .unwrap_or(0)
+                    .min(achievement.goal),
+                achievement.goal
+            ),
+        };
+
+        if let Some(rewards) = achievement.describe_rewards() {
+            output += &format!(", rewards {rewards}");
+        }
+
+        output += "\n";
+    }
+
+    common::no_ping_reply(&ctx, output).await?;
+
+    Ok(())
+}
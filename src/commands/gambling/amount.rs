@@ -0,0 +1,355 @@
+use crate::common::{Context, Error};
+
+use std::{error, fmt, str::FromStr};
+
+/// Why an amount of tokens couldn't be worked out
+#[derive(Debug)]
+pub enum AmountError {
+    Invalid(String),
+    DivideByZero,
+    /// The amount depends on a balance but there isn't one, like a limit of `half`
+    NeedsBalance,
+    TooLarge,
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Invalid(reason) => write!(
+                f,
+                "{reason}. Amounts can be numbers like `250` or `2.5k`, `all`, `max`, `half`, a percent like `25%`, a fraction of it like `1/3` (up to tenths), or sums like `all - 100`."
+            ),
+            Self::DivideByZero => write!(f, "You can't divide an amount by zero."),
+            Self::NeedsBalance => write!(
+                f,
+                "This amount can't depend on your balance, use a number like `250` or `2.5k`."
+            ),
+            Self::TooLarge => write!(f, "That amount is way too large."),
+        }
+    }
+}
+
+impl error::Error for AmountError {}
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    Number(f64),
+    /// A share of the balance, `all` is 1 and `25%` is 0.25
+    Share(f64),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, balance: Option<f64>) -> Result<f64, AmountError> {
+        Ok(match self {
+            Self::Number(n) => *n,
+            Self::Share(share) => balance.ok_or(AmountError::NeedsBalance)? * share,
+            Self::Neg(e) => -e.eval(balance)?,
+            Self::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(balance)?, rhs.eval(balance)?);
+
+                match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div if rhs == 0.0 => return Err(AmountError::DivideByZero),
+                    Op::Div => lhs / rhs,
+                }
+            }
+        })
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.input = self.input.trim_start();
+        self.input.chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.input = &self.input[c.len_utf8()..];
+        Some(c)
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &str {
+        let end = self.input.find(|c| !f(c)).unwrap_or(self.input.len());
+        let (taken, rest) = self.input.split_at(end);
+        self.input = rest;
+        taken
+    }
+
+    /// expr = term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, AmountError> {
+        let mut lhs = self.term()?;
+
+        loop {
+            let op = match self.peek() {
+                Some('+') => Op::Add,
+                Some('-') => Op::Sub,
+                _ => return Ok(lhs),
+            };
+
+            self.bump();
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.term()?));
+        }
+    }
+
+    /// term = factor (('*' | '/') factor)*
+    fn term(&mut self) -> Result<Expr, AmountError> {
+        let mut lhs = self.factor()?;
+
+        loop {
+            let op = match self.peek() {
+                Some('*') => Op::Mul,
+                Some('/') => Op::Div,
+                _ => return Ok(lhs),
+            };
+
+            self.bump();
+            lhs = Expr::Binary(Box::new(lhs), op, Box::new(self.factor()?));
+        }
+    }
+
+    /// factor = '-' factor | '(' expr ')' | word | number ('k' | 'm')? '%'?
+    fn factor(&mut self) -> Result<Expr, AmountError> {
+        match self.peek() {
+            Some('-') => {
+                self.bump();
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.bump();
+                let inner = self.expr()?;
+
+                match self.bump() {
+                    Some(')') => Ok(inner),
+                    _ => Err(AmountError::Invalid(
+                        "A bracket was never closed".to_string(),
+                    )),
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                match self.take_while(|c| c.is_ascii_alphabetic()) {
+                    "all" | "max" => Ok(Expr::Share(1.0)),
+                    "half" => Ok(Expr::Share(0.5)),
+                    word => Err(AmountError::Invalid(format!("`{word}` is not an amount"))),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let digits = self.take_while(|c| c.is_ascii_digit() || c == '.' || c == ',');
+                let mut n: f64 = digits
+                    .replace(',', "")
+                    .parse()
+                    .map_err(|_| AmountError::Invalid(format!("`{digits}` is not a number")))?;
+
+                // suffixes have to be attached to the number, so `2 k` isn't read as 2000
+                match self.input.chars().next() {
+                    Some('k') => {
+                        self.bump();
+                        n *= 1e3;
+                    }
+                    Some('m') => {
+                        self.bump();
+                        n *= 1e6;
+                    }
+                    _ => (),
+                }
+
+                if self.input.starts_with('%') {
+                    self.bump();
+                    return Ok(Expr::Share(n / 100.0));
+                }
+
+                Ok(Expr::Number(n))
+            }
+            Some(c) => Err(AmountError::Invalid(format!("`{c}` was not expected"))),
+            None => Err(AmountError::Invalid("An amount was expected".to_string())),
+        }
+    }
+}
+
+/// The biggest denominator a bare fraction can have and still be read as a share of the balance
+const MAX_DENOMINATOR: f64 = 10.0;
+
+/// An amount of tokens as a player typed it, worked out against their balance once it is known.
+/// A bare fraction of whole numbers less than 1, like `1/3`, with a denominator of at most
+/// [`MAX_DENOMINATOR`] is a share of the balance. Any other division is just division, so
+/// `50/100` is half a token rather than half the balance.
+#[derive(Clone, Debug)]
+pub struct TokenAmount(Expr);
+
+impl FromStr for TokenAmount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        let mut parser = Parser { input: &lower };
+
+        let expr = parser.expr()?;
+
+        if let Some(c) = parser.peek() {
+            return Err(AmountError::Invalid(format!("`{c}` was not expected")));
+        }
+
+        let expr = match expr {
+            Expr::Binary(lhs, Op::Div, rhs) => match (*lhs, *rhs) {
+                (Expr::Number(a), Expr::Number(b)) if is_fraction(a, b) => Expr::Share(a / b),
+                (lhs, rhs) => Expr::Binary(Box::new(lhs), Op::Div, Box::new(rhs)),
+            },
+            expr => expr,
+        };
+
+        Ok(Self(expr))
+    }
+}
+
+fn is_fraction(numerator: f64, denominator: f64) -> bool {
+    numerator.fract() == 0.0
+        && denominator.fract() == 0.0
+        && 1.0 <= numerator
+        && numerator < denominator
+        && denominator <= MAX_DENOMINATOR
+}
+
+fn tokens(n: f64) -> Result<i32, AmountError> {
+    let n = n.floor();
+
+    if !n.is_finite() || n.abs() > i32::MAX as f64 {
+        return Err(AmountError::TooLarge);
+    }
+
+    Ok(n as i32)
+}
+
+impl TokenAmount {
+    /// The amount without a balance to go off of, for things like limits and prices
+    pub fn fixed(&self) -> Result<i32, AmountError> {
+        tokens(self.0.eval(None)?)
+    }
+
+    /// The amount out of a balance, which can't be more than it
+    pub fn of(&self, balance: i32) -> Result<i32, AmountError> {
+        tokens(self.0.eval(Some(balance as f64))?)
+    }
+
+    /// Work out an amount of tokens to spend out of a balance. Replies to the user and
+    /// returns `None` if it isn't valid, less than 1 or more than the balance.
+    pub async fn resolve(&self, ctx: Context<'_>, balance: i32) -> Result<Option<i32>, Error> {
+        match check(self.of(balance), balance) {
+            Ok(amount) => Ok(Some(amount)),
+            Err(reason) => {
+                ctx.reply(reason).await?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Make sure an amount can be spent out of a balance, or say why it can't
+pub fn check(amount: Result<i32, AmountError>, balance: i32) -> Result<i32, String> {
+    match amount {
+        Ok(amount) if amount < 1 => Err("The amount has to be at least 1 token.".to_string()),
+        Ok(amount) if amount > balance => Err(format!(
+            "You do not have enough tokens (**{balance}**) for this amount."
+        )),
+        Ok(amount) => Ok(amount),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AmountError, TokenAmount};
+
+    fn amount(s: &str) -> TokenAmount {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(amount("2.5k").fixed().unwrap(), 2500);
+        assert_eq!(amount("1.5m").fixed().unwrap(), 1_500_000);
+        assert_eq!(amount("1,000").fixed().unwrap(), 1000);
+    }
+
+    #[test]
+    fn shares_of_the_balance() {
+        assert_eq!(amount("all").of(1000).unwrap(), 1000);
+        assert_eq!(amount("half").of(999).unwrap(), 499);
+        assert_eq!(amount("25%").of(1000).unwrap(), 250);
+        assert_eq!(amount("1/3").of(900).unwrap(), 300);
+    }
+
+    #[test]
+    fn sums() {
+        assert_eq!(amount("all - 100").of(1000).unwrap(), 900);
+        assert_eq!(amount("half + 2.5k").of(1000).unwrap(), 3000);
+        assert_eq!(amount("(all - 100) / 2").of(1000).unwrap(), 450);
+    }
+
+    #[test]
+    fn division_that_isnt_a_share() {
+        assert_eq!(amount("10/4").of(1000).unwrap(), 2);
+        assert_eq!(amount("100/100").of(1000).unwrap(), 1);
+        assert_eq!(amount("50/100").of(1000).unwrap(), 0);
+        assert_eq!(amount("10/10").of(1000).unwrap(), 1);
+        assert_eq!(amount("1/20").of(1000).unwrap(), 0);
+        assert_eq!(amount("1.5/3").of(1000).unwrap(), 0);
+    }
+
+    #[test]
+    fn fractions_up_to_tenths() {
+        assert_eq!(amount("9/10").of(1000).unwrap(), 900);
+        assert_eq!(amount("2/3").of(900).unwrap(), 600);
+        assert!(matches!(
+            amount("1/2").fixed(),
+            Err(AmountError::NeedsBalance)
+        ));
+    }
+
+    #[test]
+    fn divide_by_zero() {
+        assert!(matches!(
+            amount("1/0").of(1000),
+            Err(AmountError::DivideByZero)
+        ));
+    }
+
+    #[test]
+    fn too_large() {
+        assert!(matches!(
+            amount("99999m").fixed(),
+            Err(AmountError::TooLarge)
+        ));
+    }
+
+    #[test]
+    fn needs_a_balance() {
+        assert!(matches!(
+            amount("half").fixed(),
+            Err(AmountError::NeedsBalance)
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!("lots".parse::<TokenAmount>().is_err());
+        assert!("(100".parse::<TokenAmount>().is_err());
+        assert!("100 100".parse::<TokenAmount>().is_err());
+        assert!("".parse::<TokenAmount>().is_err());
+    }
+}
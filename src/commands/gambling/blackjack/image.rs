@@ -0,0 +1,259 @@
+use super::Table;
+use crate::commands::gambling::cards::{Card, Rank, Suite};
+
+use flate2::{write::ZlibEncoder, Compression};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, io::Write};
+
+type Color = [u8; 3];
+
+const FELT: Color = [0x35, 0x65, 0x4d];
+const FACE: Color = [0xf5, 0xf5, 0xf0];
+const BORDER: Color = [0x28, 0x28, 0x28];
+const RED: Color = [0xc8, 0x1e, 0x1e];
+const BLACK: Color = [0x14, 0x14, 0x14];
+const BACK: Color = [0x28, 0x46, 0xa0];
+const STRIPE: Color = [0x46, 0x6e, 0xc8];
+
+const CARD_WIDTH: usize = 48;
+const CARD_HEIGHT: usize = 68;
+const GAP: usize = 6;
+const ROW_GAP: usize = 14;
+const MARGIN: usize = 12;
+
+/// A bitmap from the glyph sheet, `true` where there is ink
+struct Glyph(Vec<Vec<bool>>);
+
+impl Glyph {
+    fn width(&self) -> usize {
+        self.0[0].len()
+    }
+}
+
+static GLYPHS: Lazy<HashMap<String, Glyph>> = Lazy::new(|| {
+    let sheet = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/assets/cards/glyphs.txt"
+    ));
+
+    let glyphs: HashMap<String, Glyph> = sheet
+        .split("\n\n")
+        .filter_map(|block| {
+            let mut lines = block.lines().filter(|line| !line.is_empty());
+            let name = lines.next()?.to_string();
+            let rows: Vec<Vec<bool>> = lines
+                .map(|l| l.chars().map(|c| c == '#').collect())
+                .collect();
+
+            assert!(!rows.is_empty(), "glyph {name} has no rows");
+            assert!(
+                rows.iter().all(|row| row.len() == rows[0].len()),
+                "glyph {name} has rows of different widths"
+            );
+
+            Some((name, Glyph(rows)))
+        })
+        .collect();
+
+    for name in Rank::ranks()
+        .flat_map(|rank| glyph_names(&rank))
+        .chain(Suite::suites().map(|suite| suite_glyph(&suite).to_string()))
+    {
+        assert!(glyphs.contains_key(&name), "glyphs.txt is missing {name}");
+    }
+
+    glyphs
+});
+
+fn glyph_names(rank: &Rank) -> Vec<String> {
+    rank.to_string().chars().map(String::from).collect()
+}
+
+fn suite_glyph(suite: &Suite) -> &'static str {
+    match suite {
+        Suite::Hearts => "hearts",
+        Suite::Diamonds => "diamonds",
+        Suite::Clubs => "clubs",
+        Suite::Spades => "spades",
+    }
+}
+
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize, background: Color) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![background; width * height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, color: Color) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: Color) {
+        for dy in 0..height {
+            for dx in 0..width {
+                self.set(x + dx, y + dy, color);
+            }
+        }
+    }
+
+    /// Draw a glyph with every pixel scaled up to a `scale` sized square,
+    /// returns the width it took up
+    fn glyph(&mut self, name: &str, x: usize, y: usize, scale: usize, color: Color) -> usize {
+        let glyph = &GLYPHS[name];
+
+        for (row, line) in glyph.0.iter().enumerate() {
+            for (column, ink) in line.iter().enumerate() {
+                if *ink {
+                    self.fill(x + column * scale, y + row * scale, scale, scale, color);
+                }
+            }
+        }
+
+        glyph.width() * scale
+    }
+
+    /// A card's outline with its corners cut off
+    fn blank(&mut self, x: usize, y: usize, color: Color) {
+        self.fill(x, y, CARD_WIDTH, CARD_HEIGHT, BORDER);
+        self.fill(x + 1, y + 1, CARD_WIDTH - 2, CARD_HEIGHT - 2, color);
+
+        for (cx, cy) in [
+            (x, y),
+            (x + CARD_WIDTH - 1, y),
+            (x, y + CARD_HEIGHT - 1),
+            (x + CARD_WIDTH - 1, y + CARD_HEIGHT - 1),
+        ] {
+            self.set(cx, cy, FELT);
+        }
+    }
+
+    fn card(&mut self, card: &Card, x: usize, y: usize) {
+        let color = match card.suite {
+            Suite::Hearts | Suite::Diamonds => RED,
+            Suite::Clubs | Suite::Spades => BLACK,
+        };
+
+        self.blank(x, y, FACE);
+
+        let mut offset = x + 4;
+
+        for name in glyph_names(&card.rank) {
+            offset += self.glyph(&name, offset, y + 4, 2, color) + 2;
+        }
+
+        let suite = suite_glyph(&card.suite);
+        self.glyph(suite, x + 5, y + 21, 1, color);
+
+        let size = GLYPHS[suite].width() * 3;
+        self.glyph(
+            suite,
+            x + (CARD_WIDTH - size) / 2,
+            y + CARD_HEIGHT - size - 10,
+            3,
+            color,
+        );
+    }
+
+    fn back(&mut self, x: usize, y: usize) {
+        self.blank(x, y, BACK);
+
+        for dy in 3..CARD_HEIGHT - 3 {
+            for dx in 3..CARD_WIDTH - 3 {
+                if (dx + dy) % 6 < 2 {
+                    self.set(x + dx, y + dy, STRIPE);
+                }
+            }
+        }
+    }
+
+    fn png(&self) -> Vec<u8> {
+        fn chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+            out.extend((data.len() as u32).to_be_bytes());
+            out.extend(kind);
+            out.extend(data);
+
+            let mut crc = crc32fast::Hasher::new();
+            crc.update(kind);
+            crc.update(data);
+            out.extend(crc.finalize().to_be_bytes());
+        }
+
+        let mut header = vec![];
+        header.extend((self.width as u32).to_be_bytes());
+        header.extend((self.height as u32).to_be_bytes());
+        // 8 bit RGB, default compression and filtering, no interlacing
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+
+        for row in self.pixels.chunks(self.width) {
+            // every scanline starts with its filter type, which is none here
+            let mut line = vec![0];
+            line.extend(row.iter().flatten());
+            encoder
+                .write_all(&line)
+                .expect("writing to a Vec can't fail");
+        }
+
+        let data = encoder.finish().expect("writing to a Vec can't fail");
+
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        chunk(&mut out, b"IHDR", &header);
+        chunk(&mut out, b"IDAT", &data);
+        chunk(&mut out, b"IEND", &[]);
+        out
+    }
+}
+
+/// Draw the dealer's hand on the first row and then every hand at the table on its own row,
+/// in the same order as the text layout describes them
+pub fn draw(table: &Table, reveal: bool) -> Vec<u8> {
+    let show = reveal || (table.seats.len() == 1 && table.seats[0].peek);
+    let hands: Vec<&[Card]> = table
+        .seats
+        .iter()
+        .flat_map(|seat| seat.hands.iter().map(|hand| hand.cards.as_slice()))
+        .collect();
+
+    let widest = hands
+        .iter()
+        .map(|cards| cards.len())
+        .chain([table.dealer.len(), 5])
+        .max()
+        .unwrap_or(5);
+
+    let width = MARGIN * 2 + widest * (CARD_WIDTH + GAP) - GAP;
+    let height = MARGIN * 2 + (hands.len() + 1) * (CARD_HEIGHT + ROW_GAP) - ROW_GAP;
+    let mut canvas = Canvas::new(width, height, FELT);
+
+    for (i, card) in table.dealer.iter().enumerate() {
+        let x = MARGIN + i * (CARD_WIDTH + GAP);
+
+        if !show && i > 0 {
+            canvas.back(x, MARGIN);
+        } else {
+            canvas.card(card, x, MARGIN);
+        }
+    }
+
+    for (row, cards) in hands.iter().enumerate() {
+        let y = MARGIN + (row + 1) * (CARD_HEIGHT + ROW_GAP);
+
+        for (i, card) in cards.iter().enumerate() {
+            canvas.card(card, MARGIN + i * (CARD_WIDTH + GAP), y);
+        }
+    }
+
+    canvas.png()
+}
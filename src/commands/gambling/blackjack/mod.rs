@@ -0,0 +1,1184 @@
+use super::{
+    amount::TokenAmount,
+    cards::{show, Card, Rank, Shoe},
+    effects::{Effects, Odds, Outcome, Settlement},
+    fair::{self, Round},
+    session::{self, Session, Status},
+    Effect,
+};
+use crate::{
+    commands::settings,
+    common::{Context, Error},
+    games::Game,
+    interactions::Route,
+};
+use poise::serenity_prelude::{
+    self as serenity, CreateInteractionResponseMessage, GuildId, UserId,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{cmp::Ordering, time::Duration};
+use tokio::time::Instant;
+
+mod image;
+mod table;
+
+/// The registry entry that blackjack's sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 441,
+    name: "Blackjack",
+};
+
+impl Rank {
+    /// The hard value of a card, aces are counted as 1
+    fn value(&self) -> u8 {
+        match self {
+            Self::Pip(n) => *n,
+            Self::Jack | Self::King | Self::Queen => 10,
+            Self::Ace => 1,
+        }
+    }
+}
+
+impl Card {
+    fn value(&self) -> u8 {
+        self.rank.value()
+    }
+}
+
+/// The total of a set of cards and whether it is soft (an ace is being counted as 11)
+fn count(cards: &[Card]) -> (u8, bool) {
+    let hard: u8 = cards.iter().map(Card::value).sum();
+    let ace = cards.iter().any(|card| card.rank == Rank::Ace);
+
+    if ace && hard + 10 <= 21 {
+        (hard + 10, true)
+    } else {
+        (hard, false)
+    }
+}
+
+/// The house rules a game of blackjack is played with
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Rules {
+    /// How many decks are shuffled together into the shoe
+    pub decks: usize,
+    pub dealer_hits_soft_17: bool,
+    /// The most hands a player can split into
+    pub max_hands: usize,
+    pub double_after_split: bool,
+    pub late_surrender: bool,
+    /// What a natural pays, as a percent of the bet
+    pub natural_payout: i32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            decks: 6,
+            dealer_hits_soft_17: true,
+            max_hands: 4,
+            double_after_split: true,
+            late_surrender: true,
+            natural_payout: 150,
+        }
+    }
+}
+
+impl Rules {
+    /// An estimate of the share of every bet paid back to a player using basic strategy, as a percent.
+    /// Starts from the house edge of six decks where the dealer stands on soft 17 and adds
+    /// the usual effect of each rule on top of it.
+    pub fn expected_return(&self) -> f64 {
+        let mut edge = 0.40;
+
+        edge += match self.decks {
+            1 => -0.48,
+            2 => -0.19,
+            3 => -0.10,
+            4 => -0.06,
+            5 => -0.03,
+            6 => 0.0,
+            7 => 0.01,
+            _ => 0.02,
+        };
+
+        edge += match self.max_hands {
+            1 => 0.57,
+            2 => 0.10,
+            3 => 0.03,
+            _ => 0.0,
+        };
+
+        if self.dealer_hits_soft_17 {
+            edge += 0.22;
+        }
+
+        if !self.double_after_split {
+            edge += 0.14;
+        }
+
+        if self.late_surrender {
+            edge -= 0.08;
+        }
+
+        // a natural comes up in about 4.5% of hands that the dealer doesn't also have one
+        edge += (150 - self.natural_payout) as f64 / 100.0 * 4.53;
+
+        100.0 - edge
+    }
+
+    /// The most the expected return can be with items, as a percent. About 43% of hands are won
+    /// and 49% lost with basic strategy, and no win is boosted on more than the bet.
+    pub fn best_return(&self) -> f64 {
+        super::best_return(&GAME, |_| Odds {
+            expected: self.expected_return() / 100.0,
+            win: 0.43,
+            loss: 0.49,
+            winnings: 0.43,
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} deck(s), dealer {} soft 17, split up to {} hand(s), double after split {}, late surrender {}, naturals pay {}%",
+            self.decks,
+            if self.dealer_hits_soft_17 { "hits" } else { "stands on" },
+            self.max_hands,
+            if self.double_after_split { "allowed" } else { "not allowed" },
+            if self.late_surrender { "allowed" } else { "not allowed" },
+            self.natural_payout
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Hand {
+    cards: Vec<Card>,
+    bet: i32,
+    /// Whether this hand came from a split, which means it can't be a natural
+    split: bool,
+    done: bool,
+    surrendered: bool,
+}
+
+impl Hand {
+    fn new(cards: Vec<Card>, bet: i32, split: bool) -> Self {
+        Self {
+            cards,
+            bet,
+            split,
+            done: false,
+            surrendered: false,
+        }
+    }
+
+    fn total(&self) -> u8 {
+        count(&self.cards).0
+    }
+
+    fn bust(&self) -> bool {
+        self.total() > 21
+    }
+
+    fn natural(&self) -> bool {
+        !self.split && self.cards.len() == 2 && self.total() == 21
+    }
+
+    fn summary(&self) -> String {
+        match count(&self.cards) {
+            (total, true) if total < 21 => format!("soft {total}"),
+            (total, _) => total.to_string(),
+        }
+    }
+
+    /// The hand's cards and total, or just the total when the cards are drawn in an image
+    fn describe(&self, text: bool) -> String {
+        if text {
+            format!("{} ({})", show(&self.cards), self.summary())
+        } else {
+            self.summary()
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Hit,
+    Stand,
+    Double,
+    Split,
+    Surrender,
+    Insurance,
+    NoInsurance,
+    /// Shows a player with a peek the dealer's hidden card when others share the table
+    Peek,
+}
+
+impl Action {
+    const ALL: [Action; 8] = [
+        Self::Hit,
+        Self::Stand,
+        Self::Double,
+        Self::Split,
+        Self::Surrender,
+        Self::Insurance,
+        Self::NoInsurance,
+        Self::Peek,
+    ];
+
+    fn id(&self) -> &'static str {
+        match self {
+            Self::Hit => "hit",
+            Self::Stand => "stand",
+            Self::Double => "double",
+            Self::Split => "split",
+            Self::Surrender => "surrender",
+            Self::Insurance => "insurance",
+            Self::NoInsurance => "noinsurance",
+            Self::Peek => "peek",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Hit => "Hit",
+            Self::Stand => "Stand",
+            Self::Double => "Double Down",
+            Self::Split => "Split",
+            Self::Surrender => "Surrender",
+            Self::Insurance => "Insurance",
+            Self::NoInsurance => "No Insurance",
+            Self::Peek => "Peek",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    fn button(&self, route: &Route) -> serenity::CreateButton {
+        serenity::CreateButton::new(route.id(self.id()))
+            .label(self.label())
+            .style(match self {
+                Self::Surrender | Self::NoInsurance | Self::Peek => {
+                    serenity::ButtonStyle::Secondary
+                }
+                _ => serenity::ButtonStyle::Primary,
+            })
+    }
+}
+
+/// One player's place at the table
+#[derive(Serialize, Deserialize)]
+struct Seat {
+    user: UserId,
+    hands: Vec<Hand>,
+    /// The hand currently being played
+    current: usize,
+    /// The player's balance outside of the table, bets can't go over it
+    balance: i32,
+    insurance: i32,
+    /// The dealer shows an ace and the player has not decided on insurance yet
+    offering_insurance: bool,
+    /// The player ran out of time and loses all of their bets
+    forfeited: bool,
+    peek: bool,
+    forgiveness: bool,
+    /// A note about an item effect that was just used, shown once
+    note: Option<String>,
+    effects: Effects,
+    /// The player's own shoe, shuffled from their seeds so they can verify their cards
+    #[serde(default)]
+    shoe: Option<Shoe>,
+    /// The number of the bet the seat's shoe was shuffled for
+    #[serde(default)]
+    nonce: Option<i32>,
+}
+
+impl Seat {
+    fn new(user: UserId, bet: i32, balance: i32, effects: Effects) -> Self {
+        Self {
+            user,
+            hands: vec![Hand::new(vec![], bet, false)],
+            current: 0,
+            balance,
+            insurance: 0,
+            offering_insurance: false,
+            forfeited: false,
+            peek: effects.any(|e| matches!(e, Effect::Peek)),
+            forgiveness: effects.any(|e| matches!(e, Effect::BustForgiveness)),
+            note: None,
+            effects,
+            shoe: None,
+            nonce: None,
+        }
+    }
+
+    /// Give the seat its own shoe, shuffled from the player's seeds as their bet #`nonce`
+    fn shuffle(&mut self, shoe: Shoe, nonce: i32) {
+        self.shoe = Some(shoe);
+        self.nonce = Some(nonce);
+    }
+
+    /// Deal from the seat's own shoe, or the table's for seats that were seated without one
+    fn next_card(&mut self, table: &mut Shoe) -> Card {
+        self.shoe.as_mut().unwrap_or(table).draw()
+    }
+
+    /// The blackjack counters for this seat's stats
+    fn counters(&self) -> [(&'static str, i32); 4] {
+        let hands = |f: fn(&Hand) -> bool| self.hands.iter().filter(|hand| f(hand)).count() as i32;
+
+        [
+            ("naturals", hands(Hand::natural)),
+            ("busts", hands(Hand::bust)),
+            ("splits", self.hands.len() as i32 - 1),
+            ("surrenders", hands(|hand| hand.surrendered)),
+        ]
+    }
+
+    fn committed(&self) -> i32 {
+        self.hands.iter().map(|hand| hand.bet).sum::<i32>() + self.insurance
+    }
+
+    fn can_afford(&self, extra: i32) -> bool {
+        extra <= self.balance
+    }
+
+    fn finished(&self) -> bool {
+        self.current >= self.hands.len()
+    }
+
+    fn finish(&mut self) {
+        self.hands.iter_mut().for_each(|hand| hand.done = true);
+        self.current = self.hands.len();
+    }
+
+    /// Move on to the next hand that still needs to be played
+    fn advance(&mut self) {
+        self.hands[self.current].done = true;
+
+        while self.current < self.hands.len() && self.hands[self.current].done {
+            self.current += 1;
+        }
+
+        if let Some(hand) = self.hands.get(self.current)
+            && hand.total() == 21
+        {
+            self.advance();
+        }
+    }
+
+    /// Draw a card for the current hand, taking it back if it busts and forgiveness is available
+    fn draw(&mut self, shoe: &mut Shoe) {
+        let card = self.next_card(shoe);
+        let hand = &mut self.hands[self.current];
+        hand.cards.push(card);
+
+        if hand.bust() && self.forgiveness {
+            self.forgiveness = false;
+            let card = hand.cards.pop().unwrap();
+            self.note = Some(format!(
+                "Your luck took back the `{card}` that made you bust!"
+            ));
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Table {
+    rules: Rules,
+    /// The dealer's shoe, players are dealt from their own
+    shoe: Shoe,
+    dealer: Vec<Card>,
+    /// The dealer checked their hole card for a natural
+    checked: bool,
+    /// The dealer checked their hole card and has a natural
+    dealer_natural: bool,
+    seats: Vec<Seat>,
+    /// Show the cards as text instead of drawing them, for players who asked for it
+    #[serde(default)]
+    text_only: bool,
+    /// The seed the dealer's shoe was shuffled with at a table of several players
+    #[serde(default)]
+    round: Option<Round>,
+    /// The server the table is in, whose limits raising a bet is checked against
+    #[serde(default)]
+    guild: Option<GuildId>,
+}
+
+impl Table {
+    fn new(
+        rules: Rules,
+        mut seats: Vec<Seat>,
+        text_only: bool,
+        mut shoe: Shoe,
+        round: Option<Round>,
+        guild: Option<GuildId>,
+    ) -> Self {
+        for seat in &mut seats {
+            let cards = vec![seat.next_card(&mut shoe), seat.next_card(&mut shoe)];
+            seat.hands[0].cards = cards;
+        }
+
+        let dealer = vec![shoe.draw(), shoe.draw()];
+
+        let mut table = Self {
+            rules,
+            shoe,
+            dealer,
+            checked: false,
+            dealer_natural: false,
+            seats,
+            text_only,
+            round,
+            guild,
+        };
+
+        if table.dealer[0].rank == Rank::Ace {
+            table
+                .seats
+                .iter_mut()
+                .for_each(|seat| seat.offering_insurance = true);
+        } else {
+            table.check_dealer();
+        }
+
+        table
+    }
+
+    /// The dealer checks their hole card for a natural, ending the round for everyone if
+    /// they have one. Players with a natural of their own are done as well.
+    fn check_dealer(&mut self) {
+        self.checked = true;
+        self.dealer_natural = count(&self.dealer).0 == 21;
+
+        for seat in &mut self.seats {
+            if self.dealer_natural || seat.hands[0].natural() {
+                seat.finish();
+            }
+        }
+    }
+
+    /// The seat that has to make a decision next. Every player decides on insurance
+    /// before anybody plays their hands.
+    fn turn(&self) -> Option<usize> {
+        self.seats
+            .iter()
+            .position(|seat| seat.offering_insurance)
+            .or_else(|| self.seats.iter().position(|seat| !seat.finished()))
+    }
+
+    fn actions(&self, seat: usize) -> Vec<Action> {
+        let seat = &self.seats[seat];
+
+        // everyone sees the table's message, so a peek there is shown only to the one who has it
+        let peek = (self.seats.len() > 1 && seat.peek).then_some(Action::Peek);
+
+        if seat.offering_insurance {
+            let mut actions = vec![];
+
+            if seat.hands[0].bet / 2 > 0 && seat.can_afford(seat.hands[0].bet / 2) {
+                actions.push(Action::Insurance);
+            }
+
+            actions.push(Action::NoInsurance);
+            actions.extend(peek);
+            return actions;
+        }
+
+        if seat.finished() {
+            return vec![];
+        }
+
+        let hand = &seat.hands[seat.current];
+        let first = hand.cards.len() == 2;
+        let mut actions = vec![Action::Hit, Action::Stand];
+
+        if first && (!hand.split || self.rules.double_after_split) && seat.can_afford(hand.bet) {
+            actions.push(Action::Double);
+        }
+
+        if first
+            && hand.cards[0].rank == hand.cards[1].rank
+            && seat.hands.len() < self.rules.max_hands
+            && seat.can_afford(hand.bet)
+        {
+            actions.push(Action::Split);
+        }
+
+        if first && self.rules.late_surrender && seat.hands.len() == 1 {
+            actions.push(Action::Surrender);
+        }
+
+        actions.extend(peek);
+        actions
+    }
+
+    /// What an action adds to a seat's bets, if anything
+    fn extra(&self, seat: usize, action: Action) -> Option<i32> {
+        let s = &self.seats[seat];
+
+        match action {
+            Action::Double | Action::Split => Some(s.hands[s.current].bet),
+            Action::Insurance => Some(s.hands[0].bet / 2),
+            _ => None,
+        }
+    }
+
+    fn apply(&mut self, seat: usize, action: Action) {
+        let shoe = &mut self.shoe;
+        let s = &mut self.seats[seat];
+
+        match action {
+            Action::Insurance => {
+                s.insurance = s.hands[0].bet / 2;
+                s.offering_insurance = false;
+            }
+            Action::NoInsurance => {
+                s.offering_insurance = false;
+            }
+            Action::Hit => {
+                s.draw(shoe);
+
+                if s.hands[s.current].total() >= 21 {
+                    s.advance();
+                }
+            }
+            Action::Stand => s.advance(),
+            Action::Double => {
+                s.hands[s.current].bet *= 2;
+                s.draw(shoe);
+                s.advance();
+            }
+            Action::Split => {
+                let current = s.current;
+                let hand = &mut s.hands[current];
+                hand.split = true;
+                let aces = hand.cards[0].rank == Rank::Ace;
+                let card = hand.cards.pop().unwrap();
+                let bet = hand.bet;
+
+                s.hands
+                    .insert(current + 1, Hand::new(vec![card], bet, true));
+                let (first, second) = (s.next_card(shoe), s.next_card(shoe));
+                s.hands[current].cards.push(first);
+                s.hands[current + 1].cards.push(second);
+
+                // split aces only get one more card each
+                if aces {
+                    s.hands[current + 1].done = true;
+                    s.advance();
+                } else if s.hands[current].total() == 21 {
+                    s.advance();
+                }
+            }
+            Action::Surrender => {
+                s.hands[s.current].surrendered = true;
+                s.advance();
+            }
+            // the hidden card is shown to the player on their own, the table stays as it is
+            Action::Peek => return,
+        }
+
+        self.next();
+    }
+
+    /// A player ran out of time to make a decision
+    fn forfeit(&mut self, seat: usize) {
+        let s = &mut self.seats[seat];
+        s.forfeited = true;
+        s.offering_insurance = false;
+        s.finish();
+
+        self.next();
+    }
+
+    /// Check the dealer's hand once everyone has decided on insurance and play it once every
+    /// player is done
+    fn next(&mut self) {
+        if !self.checked && !self.seats.iter().any(|seat| seat.offering_insurance) {
+            self.check_dealer();
+        }
+
+        if self.turn().is_none() {
+            self.play_dealer();
+        }
+    }
+
+    fn play_dealer(&mut self) {
+        let live = self
+            .seats
+            .iter()
+            .filter(|seat| !seat.forfeited)
+            .any(|seat| {
+                seat.hands
+                    .iter()
+                    .any(|hand| !hand.bust() && !hand.surrendered && !hand.natural())
+            });
+
+        if self.dealer_natural || !live {
+            return;
+        }
+
+        loop {
+            match count(&self.dealer) {
+                (total, _) if total < 17 => (),
+                (17, true) if self.rules.dealer_hits_soft_17 => (),
+                _ => break,
+            }
+
+            let card = self.shoe.draw();
+            self.dealer.push(card);
+        }
+    }
+
+    fn settle(&self, seat: &Seat, hand: &Hand) -> Settlement {
+        let dealer = count(&self.dealer).0;
+
+        if seat.forfeited {
+            return Settlement::loss(hand.bet);
+        }
+
+        if hand.surrendered {
+            let mut settlement = Settlement::loss(hand.bet);
+            settlement.net = -(hand.bet / 2);
+            return settlement;
+        }
+
+        if hand.bust() {
+            return Settlement::loss(hand.bet);
+        }
+
+        if self.dealer_natural {
+            return if hand.natural() {
+                Settlement::push(hand.bet)
+            } else {
+                Settlement::loss(hand.bet)
+            };
+        }
+
+        if hand.natural() {
+            let won = hand.bet as i64 * self.rules.natural_payout as i64 / 100;
+            return Settlement::win(hand.bet, i32::try_from(won).unwrap_or(i32::MAX));
+        }
+
+        match dealer.cmp(&hand.total()) {
+            Ordering::Less => Settlement::win(hand.bet, hand.bet),
+            Ordering::Greater if dealer > 21 => Settlement::win(hand.bet, hand.bet),
+            Ordering::Equal => Settlement::push(hand.bet),
+            Ordering::Greater => Settlement::loss(hand.bet),
+        }
+    }
+
+    /// Pay out every hand of a seat, then run what the played hands came to through the
+    /// player's effects once. Surrendered hands are settled without them.
+    /// Returns what they won or lost and a line describing each result.
+    fn payout(&self, seat: usize) -> (i32, Vec<String>) {
+        let seat = &self.seats[seat];
+        let mut net = 0;
+        let mut bet = 0;
+        let mut played: i32 = 0;
+        let mut results = vec![];
+
+        for (i, hand) in seat.hands.iter().enumerate() {
+            let settlement = self.settle(seat, hand);
+
+            if hand.surrendered {
+                net += settlement.net;
+            } else {
+                bet += settlement.bet;
+                played = played.saturating_add(settlement.net);
+            }
+
+            if !seat.forfeited {
+                let s = describe_settlement(hand, &settlement);
+
+                results.push(if seat.hands.len() == 1 {
+                    s
+                } else {
+                    format!("**Hand {}**: {s}", i + 1)
+                });
+            }
+        }
+
+        if bet > 0 {
+            let mut settlement = Settlement::net(bet, played);
+            seat.effects.settle(&mut settlement);
+            net = net.saturating_add(settlement.net);
+
+            let bonus = settlement.net - played;
+
+            if bonus != 0 && !seat.forfeited {
+                results.push(format!(
+                    "Your items and buffs paid out another **{bonus}** token(s)."
+                ));
+            }
+        }
+
+        if seat.insurance > 0 {
+            let insurance = if self.dealer_natural && !seat.forfeited {
+                seat.insurance * 2
+            } else {
+                -seat.insurance
+            };
+
+            net += insurance;
+
+            if !seat.forfeited {
+                results.push(if insurance > 0 {
+                    format!("Your insurance paid out **{insurance}** token(s).")
+                } else {
+                    format!("You've lost your insurance of **{}** token(s).", -insurance)
+                });
+            }
+        }
+
+        if seat.forfeited {
+            results.push(format!(
+                "No bets go without a game! You've lost **{}** token(s).",
+                -net
+            ));
+        }
+
+        (net, results)
+    }
+
+    fn render_seat(&self, seat: usize, reveal: bool) -> String {
+        let s = &self.seats[seat];
+        let turn = !reveal && self.turn() == Some(seat);
+        let mut output = String::new();
+
+        if s.hands.len() == 1 {
+            output += &format!("**Your hand**: {}\n\n", s.hands[0].describe(self.text_only));
+            output += &format!("**Bet**: {}", s.hands[0].bet);
+        } else {
+            for (i, hand) in s.hands.iter().enumerate() {
+                output += &format!(
+                    "**Hand {}**: {} - **Bet**: {}{}\n",
+                    i + 1,
+                    hand.describe(self.text_only),
+                    hand.bet,
+                    if turn && i == s.current {
+                        " \u{25C0}"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+
+        if s.insurance > 0 {
+            output += &format!("\n**Insurance**: {}", s.insurance);
+        }
+
+        output
+    }
+
+    fn render(&self, reveal: bool) -> String {
+        let peek = self.seats.len() == 1 && self.seats[0].peek;
+
+        let mut output = match (reveal || peek, self.text_only) {
+            (true, true) => format!(
+                "**Dealer's hand**: {} ({})\n",
+                show(&self.dealer),
+                count(&self.dealer).0
+            ),
+            (true, false) => format!("**Dealer's hand**: {}\n", count(&self.dealer).0),
+            (false, true) => format!(
+                "**Dealer's hand**: `{}`, `XX` ({})\n",
+                self.dealer[0],
+                count(&self.dealer[..1]).0
+            ),
+            (false, false) => format!("**Dealer's hand**: {}\n", count(&self.dealer[..1]).0),
+        };
+
+        if self.seats.len() == 1 {
+            output += &self.render_seat(0, reveal);
+        } else {
+            for seat in 0..self.seats.len() {
+                output += &format!(
+                    "\n<@{}>{}\n{}\n",
+                    self.seats[seat].user,
+                    if !reveal && self.turn() == Some(seat) {
+                        " \u{25C0}"
+                    } else {
+                        ""
+                    },
+                    self.render_seat(seat, reveal)
+                        .replace("**Your hand**", "**Hand**")
+                        .replace("\n\n", "\n")
+                );
+            }
+        }
+
+        output
+    }
+
+    /// The game message, with the table drawn as an image unless it is text only
+    fn message(&self, reveal: bool, content: String) -> serenity::EditMessage {
+        let attachments = if self.text_only {
+            serenity::EditAttachments::new()
+        } else {
+            serenity::EditAttachments::new().add(
+                serenity::CreateAttachment::bytes(image::draw(self, reveal), "blackjack.png")
+                    .description("The cards on the blackjack table"),
+            )
+        };
+
+        serenity::EditMessage::new()
+            .content(content)
+            .attachments(attachments)
+    }
+}
+
+fn describe_settlement(hand: &Hand, settlement: &Settlement) -> String {
+    match settlement.outcome {
+        _ if hand.surrendered => format!(
+            "You surrendered. **{}** token(s) to the dealer.",
+            -settlement.net
+        ),
+        Outcome::Win if hand.natural() => format!(
+            "You've won with a Blackjack! You've gained **{}** token(s).",
+            settlement.net
+        ),
+        Outcome::Win => format!(
+            "You've won! **{}** token(s) have been added to your account.",
+            settlement.net
+        ),
+        Outcome::Push => "A draw!".to_string(),
+        Outcome::Loss if hand.bust() => {
+            format!("Bust! You've lost **{}** token(s).", -settlement.net)
+        }
+        Outcome::Loss => format!(
+            "You've lost. **{}** token(s) to the dealer.",
+            -settlement.net
+        ),
+    }
+}
+
+/// Play out every seat's turn on the session's message until the dealer's hand is done.
+/// A player who doesn't respond for two minutes forfeits their bets.
+async fn run(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: &Session,
+    table: &mut Table,
+) -> Result<(), Error> {
+    let mut route = Route::new(&GAME.key(), session.id);
+    route.restrict(table.seats.iter().map(|seat| seat.user).collect());
+
+    // whether the current player is going to time out in the next 60 seconds
+    let mut timeout = false;
+
+    while let Some(seat) = table.turn() {
+        let user = table.seats[seat].user;
+
+        let components = vec![serenity::CreateActionRow::Buttons(
+            table
+                .actions(seat)
+                .iter()
+                .map(|action| action.button(&route))
+                .collect(),
+        )];
+
+        let note = table.seats[seat]
+            .note
+            .take()
+            .map(|n| format!("\n{n}"))
+            .unwrap_or_default();
+
+        let warning = match (timeout, table.seats.len()) {
+            (false, _) => String::new(),
+            (true, 1) => "\n*You have 60 seconds to make a decision.*".to_string(),
+            (true, _) => format!("\n*<@{user}> has 60 seconds to make a decision.*"),
+        };
+
+        session
+            .channel
+            .edit_message(
+                ctx,
+                session.message,
+                table
+                    .message(false, format!("{}{note}{warning}", table.render(false)))
+                    .components(components),
+            )
+            .await?;
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        // other players at the table can click too, but only the current player's clicks count
+        let next = loop {
+            match route.next(deadline).await {
+                Some((mci, _)) if mci.user.id != user => {
+                    mci.create_response(
+                        ctx,
+                        serenity::CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .ephemeral(true)
+                                .content("It is not your turn yet."),
+                        ),
+                    )
+                    .await?;
+                }
+                next => break next,
+            }
+        };
+
+        let Some((mci, action)) = next else {
+            if timeout {
+                timeout = false;
+                table.forfeit(seat);
+
+                let mut tx = db.begin().await?;
+                session.save(&mut tx, table).await?;
+                tx.commit().await?;
+            } else {
+                timeout = true;
+            }
+
+            continue;
+        };
+
+        // Reset timeout after player interacts
+        timeout = false;
+
+        let Some(action) = Action::from_id(&action) else {
+            mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                .await?;
+            continue;
+        };
+
+        if action == Action::Peek && table.actions(seat).contains(&action) {
+            mci.create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(format!(
+                            "The dealer's hidden card is `{}`.",
+                            table.dealer[1]
+                        )),
+                ),
+            )
+            .await?;
+            continue;
+        }
+
+        let mut tx = db.begin().await?;
+
+        // raising a bet counts against the player's limits like any other bet
+        if let Some(extra) = table.extra(seat, action)
+            && let Some(reason) = super::limits::check(&mut tx, user, table.guild, extra).await?
+        {
+            mci.create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .ephemeral(true)
+                        .content(reason),
+                ),
+            )
+            .await?;
+            continue;
+        }
+
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        // the player's balance can change during the game, so bets are checked against the current one
+        table.seats[seat].balance = super::get_balance(user, &mut *tx).await?;
+
+        if !table.actions(seat).contains(&action) {
+            continue;
+        }
+
+        table.apply(seat, action);
+
+        let committed = table.seats[seat].committed();
+        table.seats[seat].balance = session.hold(&mut tx, user, committed).await?;
+        session.save(&mut tx, table).await?;
+
+        tx.commit().await?;
+    }
+
+    Ok(())
+}
+
+/// Pay every seat out of escrow, end the session and show the results
+async fn finish(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: &Session,
+    mut table: Table,
+) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+    let mut output = table.render(true);
+    let solo = table.seats.len() == 1;
+
+    for i in 0..table.seats.len() {
+        let (net, results) = table.payout(i);
+        let seat = &mut table.seats[i];
+
+        let held = session.release(&mut tx, seat.user).await?;
+        let balance = super::get_balance(seat.user, &mut *tx).await?;
+        super::change_balance(
+            seat.user,
+            balance.saturating_add(held).saturating_add(net),
+            &mut *tx,
+        )
+        .await?;
+        std::mem::take(&mut seat.effects).finish(&mut tx).await?;
+        super::history::record(&mut tx, seat.user, session.channel, &GAME, held, net).await?;
+        super::stats::count(&mut tx, seat.user, session.channel, &GAME, &seat.counters()).await?;
+
+        if let Some(note) = seat.note.take() {
+            output += &format!("\n{note}");
+        }
+
+        output += &if solo {
+            format!("\n{}", results.join("\n"))
+        } else {
+            format!("\n<@{}>: {}", seat.user, results.join(" "))
+        };
+    }
+
+    session.end(&mut tx, Status::Finished).await?;
+    tx.commit().await?;
+
+    match &table.round {
+        Some(round) => {
+            let bets: Vec<String> = table
+                .seats
+                .iter()
+                .filter_map(|seat| seat.nonce.map(|nonce| format!("<@{}> #{nonce}", seat.user)))
+                .collect();
+
+            if !bets.is_empty() {
+                output += &format!(
+                    "\n-# Hands dealt as bets {}, check yours with /verify bet once you rotate your seeds.",
+                    bets.join(", ")
+                );
+            }
+
+            output += &format!("\n{}", round.reveal());
+        }
+        None => {
+            if let Some(nonce) = table.seats.first().and_then(|seat| seat.nonce) {
+                output += &format!("\n{}", fair::footer(nonce));
+            }
+        }
+    }
+
+    session
+        .channel
+        .edit_message(
+            ctx,
+            session.message,
+            table.message(true, output).components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn play_out(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: Session,
+    mut table: Table,
+) -> Result<(), Error> {
+    session
+        .refund_on_error(db, async {
+            run(ctx, db, &session, &mut table).await?;
+            finish(ctx, db, &session, table).await
+        })
+        .await
+}
+
+/// Pick a saved game back up, handing the session back if its state can't be read
+pub fn resume(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: Session,
+    state: serde_json::Value,
+) -> Result<(), Session> {
+    let Some(table) = session::state::<Table>(state) else {
+        return Err(session);
+    };
+
+    let ctx = ctx.clone();
+    let db = db.clone();
+
+    tokio::spawn(async move {
+        if let Err(e) = play_out(&ctx, &db, session, table).await {
+            eprintln!("Failed to resume a game of blackjack: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Blackjack!
+#[poise::command(
+    slash_command,
+    prefix_command,
+    aliases("jackblack", "bj", "21"),
+    subcommands("play", "table::table")
+)]
+pub async fn blackjack(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[autocomplete = "super::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    solo(ctx, amount, item).await
+}
+
+/// Play a hand of blackjack against the dealer
+#[poise::command(slash_command, prefix_command)]
+pub async fn play(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[autocomplete = "super::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    solo(ctx, amount, item).await
+}
+
+async fn solo(ctx: Context<'_>, amount: TokenAmount, item: Option<String>) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let mut tx = db.begin().await?;
+    let Some(super::Stake {
+        amount,
+        balance,
+        effects,
+    }) = super::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
+        return Ok(());
+    };
+
+    let mut seat = Seat::new(ctx.author().id, amount, balance - amount, effects);
+    let text_only = settings::get_text_only(db, ctx.author().id).await?;
+    let rules = settings::get_blackjack_rules(&mut *tx, ctx.guild_id()).await?;
+
+    // playing alone, the dealer's shoe is shuffled from the same seeds right after the player's
+    let draw = fair::Draw::Blackjack { decks: rules.decks };
+    let (mut rng, nonce) = fair::next(&mut tx, ctx.author().id, &draw).await?;
+    seat.shuffle(Shoe::shuffled(rules.decks, &mut rng), nonce);
+    let dealer = Shoe::shuffled(rules.decks, &mut rng);
+    let table = Table::new(rules, vec![seat], text_only, dealer, None, ctx.guild_id());
+
+    let msg = ctx.reply("Just a second...").await?;
+    let message = msg.message().await?.id;
+
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), message, &table).await?;
+    session.hold(&mut tx, ctx.author().id, amount).await?;
+    tx.commit().await?;
+
+    play_out(ctx.serenity_context(), db, session, table).await
+}
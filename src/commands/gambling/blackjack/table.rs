@@ -0,0 +1,289 @@
+use super::{play_out, Seat, Table, GAME};
+use crate::{
+    commands::{
+        gambling::{
+            self,
+            amount::TokenAmount,
+            cards::Shoe,
+            effects::Effects,
+            fair::{Draw, Round},
+            session::Session,
+        },
+        settings,
+    },
+    common::{self, Context, Error},
+    interactions::Route,
+};
+use poise::serenity_prelude::{
+    self as serenity,
+    futures::{stream::FuturesUnordered, StreamExt},
+    CreateInteractionResponseMessage, UserId,
+};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The most players that can sit at one table
+const SEATS: usize = 7;
+
+#[derive(poise::Modal)]
+#[name = "Join the blackjack table"]
+struct Join {
+    #[name = "How many tokens do you want to bet?"]
+    #[placeholder = "100, 1k, half"]
+    bet: String,
+}
+
+fn lobby(
+    host: UserId,
+    seconds: u64,
+    players: &[(UserId, i32)],
+    round: &Round,
+    notice: Option<&str>,
+) -> String {
+    let mut output = format!(
+        "<@{host}> opened a blackjack table! Join with your bet within {seconds} seconds.\n\n**Players** ({}/{SEATS}):\n",
+        players.len()
+    );
+
+    if players.is_empty() {
+        output += "Nobody has joined yet.\n";
+    }
+
+    for (user, bet) in players {
+        output += &format!("- <@{user}>: {bet} token(s)\n");
+    }
+
+    if let Some(notice) = notice {
+        output += &format!("\n{notice}");
+    }
+
+    output += &format!("\n{}", round.commitment());
+
+    output
+}
+
+fn buttons(route: &Route) -> Vec<serenity::CreateActionRow> {
+    vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(route.id("join"))
+            .label("Join")
+            .style(serenity::ButtonStyle::Primary),
+        serenity::CreateButton::new(route.id("start"))
+            .label("Start")
+            .style(serenity::ButtonStyle::Success),
+    ])]
+}
+
+/// Open a blackjack table that other players can join with their own bets
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn table(
+    ctx: Context<'_>,
+    #[description = "How many seconds players have to join (10-120)"] seconds: Option<u64>,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let host = ctx.author().id;
+    let seconds = seconds.unwrap_or(30).clamp(10, 120);
+
+    // the dealer's shoe is settled before anyone joins
+    let mut tx = db.begin().await?;
+    let rules = settings::get_blackjack_rules(&mut *tx, ctx.guild_id()).await?;
+    let round = Round::start(&mut tx, &Draw::Dealer { decks: rules.decks }).await?;
+    tx.commit().await?;
+
+    let mut players: Vec<(UserId, i32)> = vec![];
+    let mut route = Route::unique("blackjack-table");
+
+    let msg = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(lobby(host, seconds, &players, &round, None))
+                .components(buttons(&route)),
+        )
+        .await?;
+
+    let id = msg.message().await?.id;
+
+    let mut modals = FuturesUnordered::new();
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+
+    loop {
+        tokio::select! {
+            Some((user, join)) = modals.next() => {
+                let Ok(Some(Join { bet })) = join else {
+                    continue;
+                };
+
+                let balance = gambling::get_balance(user, db).await?;
+
+                let bet = bet.parse::<TokenAmount>().and_then(|bet| bet.of(balance));
+
+                let refusal = match bet {
+                    Ok(bet) if bet >= 1 && bet <= balance => {
+                        gambling::limits::check(&mut *db.acquire().await?, user, ctx.guild_id(), bet)
+                            .await?
+                    }
+                    _ => None,
+                };
+
+                let notice = match bet {
+                    Ok(bet) if bet < 1 => Some(format!("<@{user}> cannot bet less than 1 token.")),
+                    Ok(bet) if bet > balance => Some(format!(
+                        "<@{user}> does not have enough tokens (**{balance}**) to bet this amount."
+                    )),
+                    Ok(_) if refusal.is_some() => {
+                        refusal.map(|reason| format!("<@{user}> can't join. {reason}"))
+                    }
+                    Ok(bet) => {
+                        match players.iter().position(|(u, _)| *u == user) {
+                            Some(i) => players[i].1 = bet,
+                            None if players.len() < SEATS => players.push((user, bet)),
+                            None => {}
+                        }
+
+                        None
+                    }
+                    Err(e) => Some(format!("<@{user}>: {e}")),
+                };
+
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(lobby(
+                            host,
+                            seconds,
+                            &players,
+                            &round,
+                            notice.as_deref(),
+                        ))
+                        .components(buttons(&route)),
+                )
+                .await?;
+            }
+            next = route.next(deadline) => {
+                let Some((mci, action)) = next else {
+                    break;
+                };
+
+                match action.as_str() {
+                    "start" if mci.user.id == host => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        break;
+                    }
+                    "start" => {
+                        mci.create_response(
+                            ctx,
+                            serenity::CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .ephemeral(true)
+                                    .content(format!("Only <@{host}> can start the game.")),
+                            ),
+                        )
+                        .await?;
+                    }
+                    _ if players.len() >= SEATS
+                        && !players.iter().any(|(u, _)| *u == mci.user.id) =>
+                    {
+                        mci.create_response(
+                            ctx,
+                            serenity::CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .ephemeral(true)
+                                    .content("This table is full."),
+                            ),
+                        )
+                        .await?;
+                    }
+                    _ => modals.push(async move {
+                        let user = mci.user.id;
+                        let join = poise::execute_modal_on_component_interaction::<Join>(
+                            ctx,
+                            mci,
+                            None,
+                            Some(Duration::from_secs(60)),
+                        )
+                        .await;
+
+                        (user, join)
+                    }),
+                }
+            }
+        }
+    }
+
+    if players.is_empty() {
+        msg.edit(
+            ctx,
+            poise::CreateReply::default()
+                .content("Nobody joined the blackjack table.")
+                .components(vec![]),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut tx = db.begin().await?;
+    let mut seats = vec![];
+    let mut left = vec![];
+
+    for (user, bet) in players {
+        // balances may have changed since joining
+        let balance = gambling::get_balance(user, &mut *tx).await?;
+
+        if balance < bet {
+            left.push(format!(
+                "<@{user}> can no longer afford their bet and left the table."
+            ));
+            continue;
+        }
+
+        // and so may their limits
+        if let Some(reason) = gambling::limits::check(&mut tx, user, ctx.guild_id(), bet).await? {
+            left.push(format!("<@{user}> left the table. {reason}"));
+            continue;
+        }
+
+        let effects = Effects::load(&mut *tx, user, None).await?;
+        seats.push(Seat::new(user, bet, balance - bet, effects));
+    }
+
+    if seats.is_empty() {
+        msg.edit(
+            ctx,
+            poise::CreateReply::default()
+                .content("Nobody at the blackjack table can place their bet anymore.")
+                .components(vec![]),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if !left.is_empty() {
+        common::no_ping_reply(&ctx, left.join("\n")).await?;
+    }
+
+    let mut text_only = false;
+
+    for seat in &seats {
+        text_only |= settings::get_text_only(db, seat.user).await?;
+    }
+
+    // every player's cards come from a shoe shuffled from their own seeds
+    let draw = Draw::Blackjack { decks: rules.decks };
+
+    for seat in &mut seats {
+        let (mut rng, nonce) = gambling::fair::next(&mut tx, seat.user, &draw).await?;
+        seat.shuffle(Shoe::shuffled(rules.decks, &mut rng), nonce);
+    }
+
+    let dealer = Shoe::shuffled(rules.decks, &mut round.rng());
+    let table = Table::new(rules, seats, text_only, dealer, Some(round), ctx.guild_id());
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), id, &table).await?;
+
+    for seat in &table.seats {
+        session.hold(&mut tx, seat.user, seat.committed()).await?;
+    }
+
+    tx.commit().await?;
+
+    play_out(ctx.serenity_context(), db, session, table).await
+}
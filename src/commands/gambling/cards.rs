@@ -0,0 +1,158 @@
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Suite {
+    Hearts,
+    Diamonds,
+    Clubs,
+    Spades,
+}
+
+impl Display for Suite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Hearts => "\u{2665}",
+                Self::Diamonds => "\u{2666}",
+                Self::Clubs => "\u{2663}",
+                Self::Spades => "\u{2660}",
+            }
+        )
+    }
+}
+
+impl Suite {
+    pub fn suites() -> impl Iterator<Item = Self> {
+        [Self::Hearts, Self::Diamonds, Self::Clubs, Self::Spades]
+            .iter()
+            .cloned()
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rank {
+    Pip(u8),
+    Jack,
+    King,
+    Queen,
+    Ace,
+}
+
+impl Display for Rank {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pip(n) => write!(f, "{}", n),
+            Self::Jack => write!(f, "J"),
+            Self::King => write!(f, "K"),
+            Self::Queen => write!(f, "Q"),
+            Self::Ace => write!(f, "A"),
+        }
+    }
+}
+
+impl Rank {
+    pub fn ranks() -> impl Iterator<Item = Self> {
+        (2..=10)
+            .map(Self::Pip)
+            .chain(vec![Self::Jack, Self::King, Self::Queen, Self::Ace])
+    }
+
+    /// Where the rank sits from 2 up to 14, aces are high
+    pub fn order(&self) -> u8 {
+        match self {
+            Self::Pip(n) => *n,
+            Self::Jack => 11,
+            Self::Queen => 12,
+            Self::King => 13,
+            Self::Ace => 14,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Card {
+    pub suite: Suite,
+    pub rank: Rank,
+}
+
+impl Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}", self.suite, self.rank)
+    }
+}
+
+impl Card {
+    pub fn new(suite: Suite, rank: Rank) -> Self {
+        Self { suite, rank }
+    }
+
+    pub fn deck() -> impl Iterator<Item = Card> {
+        let mut deck = vec![];
+
+        for rank in Rank::ranks() {
+            for suite in Suite::suites() {
+                deck.push(Card::new(suite, rank.clone()));
+            }
+        }
+
+        deck.into_iter()
+    }
+}
+
+pub fn show(cards: &[Card]) -> String {
+    cards
+        .iter()
+        .map(|card| format!("`{card}`"))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+/// One or more decks shuffled together, cards are dealt from the top
+#[derive(Serialize, Deserialize)]
+pub struct Shoe {
+    cards: Vec<Card>,
+    decks: usize,
+    /// Drawn from the generator the shoe was shuffled with, the next shoe is shuffled from it
+    /// so that every card dealt can be worked out again
+    #[serde(default)]
+    reshuffle: u64,
+}
+
+impl Shoe {
+    pub fn new(decks: usize) -> Self {
+        Self::shuffled(decks, &mut rand::thread_rng())
+    }
+
+    /// A shoe shuffled with a particular generator, so the order can be worked out again
+    pub fn shuffled(decks: usize, rng: &mut impl Rng) -> Self {
+        let decks = decks.max(1);
+        let mut cards: Vec<Card> = (0..decks).flat_map(|_| Card::deck()).collect();
+        cards.shuffle(rng);
+
+        Self {
+            cards,
+            decks,
+            reshuffle: rng.r#gen(),
+        }
+    }
+
+    /// The next `n` cards in the order they will be dealt
+    pub fn top(&self, n: usize) -> Vec<Card> {
+        self.cards.iter().rev().take(n).cloned().collect()
+    }
+
+    /// Deal the top card, a fresh shoe is shuffled once every card has been dealt
+    pub fn draw(&mut self) -> Card {
+        if self.cards.is_empty() {
+            let mut rng = ChaCha20Rng::seed_from_u64(self.reshuffle);
+            *self = Self::shuffled(self.decks, &mut rng);
+        }
+
+        self.cards.pop().unwrap()
+    }
+}
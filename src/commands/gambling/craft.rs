@@ -0,0 +1,217 @@
+use super::{items, Item};
+use crate::{
+    common::{Context, Error},
+    inventory::Inventory,
+};
+use once_cell::sync::Lazy;
+use poise::serenity_prelude as serenity;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// The most times a recipe can be crafted at once
+const MAX_CRAFTS: i64 = 1000;
+
+#[derive(Deserialize)]
+struct Stack {
+    id: u64,
+    count: i64,
+}
+
+impl Stack {
+    fn item(&self) -> &'static Item {
+        items::get_item_by_id(self.id).unwrap()
+    }
+}
+
+#[derive(Deserialize)]
+struct Recipe {
+    name: String,
+    inputs: Vec<Stack>,
+    outputs: Vec<Stack>,
+}
+
+impl Recipe {
+    /// How many times this recipe can be crafted with the given item counts
+    fn craftable(&self, owned: &HashMap<i64, i64>) -> i64 {
+        self.inputs
+            .iter()
+            .map(|stack| owned.get(&(stack.id as i64)).copied().unwrap_or(0) / stack.count)
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+fn describe(stacks: &[Stack], times: i64) -> String {
+    stacks
+        .iter()
+        .map(|stack| format!("{}x {}", stack.count * times, stack.item().name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+static RECIPES: Lazy<Vec<Recipe>> = Lazy::new(|| {
+    let recipes: Vec<Recipe> =
+        serde_json::from_str(include_str!("recipes.json")).expect("recipes.json is malformed");
+
+    for recipe in &recipes {
+        for stack in recipe.inputs.iter().chain(recipe.outputs.iter()) {
+            assert!(
+                items::get_item_by_id(stack.id).is_some(),
+                "recipe {} references unknown item {}",
+                recipe.name,
+                stack.id
+            );
+            assert!(stack.count > 0, "recipe {} has an empty stack", recipe.name);
+        }
+    }
+
+    recipes
+});
+
+async fn autocomplete_recipes<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = serenity::AutocompleteChoice> + use<'a> {
+    let db = &ctx.data().database;
+    let owned = Inventory::new(ctx.author().id, Some(super::ID))
+        .item_counts(db)
+        .await
+        .unwrap_or_default();
+
+    RECIPES
+        .iter()
+        .filter(move |recipe| recipe.name.contains(partial))
+        .map(move |recipe| {
+            let craftable = recipe.craftable(&owned);
+
+            serenity::AutocompleteChoice::new(
+                if craftable > 0 {
+                    format!("{} - {}", recipe.name, describe(&recipe.inputs, 1))
+                } else {
+                    format!(
+                        "{} - {} - Missing Items",
+                        recipe.name,
+                        describe(&recipe.inputs, 1)
+                    )
+                },
+                recipe.name.as_str(),
+            )
+        })
+}
+
+/// Combine items from your inventory into something better
+#[poise::command(slash_command, prefix_command)]
+pub async fn craft(
+    ctx: Context<'_>,
+    #[min = 1]
+    #[max = 1000]
+    count: Option<i64>,
+    #[autocomplete = "autocomplete_recipes"]
+    #[rest]
+    recipe: String,
+) -> Result<(), Error> {
+    let count = count.unwrap_or(1);
+
+    if count < 1 {
+        ctx.reply("You cannot craft less than one of something.")
+            .await?;
+        return Ok(());
+    }
+
+    if count > MAX_CRAFTS {
+        ctx.reply(format!(
+            "You cannot craft more than {MAX_CRAFTS} of something at once."
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let Some(recipe) = RECIPES.iter().find(|r| r.name == recipe) else {
+        ctx.reply(format!("There is no recipe for {recipe}."))
+            .await?;
+        return Ok(());
+    };
+
+    let mut tx = ctx.data().database.begin().await?;
+    let inventory = Inventory::new(ctx.author().id, Some(super::ID));
+
+    for stack in &recipe.inputs {
+        if !inventory
+            .take_items_of_type(&mut *tx, stack.id, stack.count * count)
+            .await?
+        {
+            ctx.reply(format!(
+                "You need {} to craft {count}x {}.",
+                describe(&recipe.inputs, count),
+                recipe.name
+            ))
+            .await?;
+            return Ok(());
+        }
+    }
+
+    for stack in &recipe.outputs {
+        for _ in 0..stack.count * count {
+            inventory
+                .give_item(&mut *tx, stack.item().clone().inv_item())
+                .await?;
+        }
+    }
+
+    tx.commit().await?;
+
+    ctx.reply(format!(
+        "You crafted {} from {}.",
+        describe(&recipe.outputs, count),
+        describe(&recipe.inputs, count)
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// List crafting recipes and what you can craft right now
+#[poise::command(slash_command, prefix_command)]
+pub async fn recipes(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let owned = Inventory::new(ctx.author().id, Some(super::ID))
+        .item_counts(db)
+        .await?;
+
+    let (craftable, missing): (Vec<_>, Vec<_>) = RECIPES
+        .iter()
+        .map(|recipe| (recipe, recipe.craftable(&owned)))
+        .partition(|(_, n)| *n > 0);
+
+    let mut output = String::new();
+
+    if !craftable.is_empty() {
+        output += "**You can craft**:\n";
+
+        for (recipe, n) in craftable {
+            output += &format!(
+                "- **{}** ({n}x): {} -> {}\n",
+                recipe.name,
+                describe(&recipe.inputs, 1),
+                describe(&recipe.outputs, 1)
+            );
+        }
+    }
+
+    if !missing.is_empty() {
+        output += "**Missing items for**:\n";
+
+        for (recipe, _) in missing {
+            output += &format!(
+                "- **{}**: {} -> {}\n",
+                recipe.name,
+                describe(&recipe.inputs, 1),
+                describe(&recipe.outputs, 1)
+            );
+        }
+    }
+
+    ctx.reply(output).await?;
+
+    Ok(())
+}
@@ -0,0 +1,405 @@
+use super::{
+    amount::TokenAmount,
+    effects::{Effects, Odds, Settlement},
+    fair,
+    session::{Session, Status},
+};
+use crate::{
+    commands::settings,
+    common::{Context, Error},
+    games::Game,
+    interactions::Route,
+};
+
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, CreateInteractionResponseMessage, UserId,
+};
+use rand::Rng;
+use serde::Serialize;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use tokio::{sync::mpsc, time::Instant};
+
+/// The registry entry that crash's sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 446,
+    name: "Crash",
+};
+
+/// How long players have to join a round before the multiplier starts climbing
+const BETTING: Duration = Duration::from_secs(15);
+
+/// How often the multiplier is updated
+const TICK: Duration = Duration::from_millis(1500);
+
+/// How fast the multiplier grows, it doubles about every ten seconds
+const GROWTH: f64 = 0.07;
+
+/// The highest a round can go before it crashes
+const MAX_MULTIPLIER: f64 = 1000.0;
+
+struct Entry {
+    user: UserId,
+    amount: i32,
+    effects: Effects,
+    cashed_out: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct State {
+    point: f64,
+}
+
+/// A round that is still taking bets, other players in the channel join it instead of starting their own
+struct Open {
+    session: Session,
+    users: Vec<UserId>,
+    sender: mpsc::UnboundedSender<Entry>,
+}
+
+static OPEN: Lazy<Mutex<HashMap<ChannelId, Open>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Pick where a round crashes. The chance of a round reaching a multiplier of `m` is
+/// `(1 - edge) / m`, so cashing out at any multiplier returns `1 - edge` on average.
+pub fn crash_point(edge: i32, rng: &mut impl Rng) -> f64 {
+    let u: f64 = rng.gen_range(0.0..1.0);
+    let point = (1.0 - edge as f64 / 100.0) / (1.0 - u);
+
+    // multipliers only ever show two decimals, rounding down keeps the edge in the house's favor
+    ((point * 100.0).floor() / 100.0).clamp(1.0, MAX_MULTIPLIER)
+}
+
+/// The most a round with a house edge of `edge` percent pays back on average with items, as a
+/// percent, whatever the player cashes out at
+pub fn best_return(edge: i32) -> f64 {
+    let expected = 1.0 - edge as f64 / 100.0;
+
+    // cashing out early wins more often and cashing out late wins more, so items are worth the
+    // most at different multipliers
+    [
+        1.01,
+        1.1,
+        1.25,
+        1.5,
+        2.0,
+        3.0,
+        5.0,
+        10.0,
+        100.0,
+        MAX_MULTIPLIER,
+    ]
+    .iter()
+    .map(|target| {
+        let win = (expected / target).min(1.0);
+
+        super::best_return(&GAME, |_| Odds {
+            expected,
+            win,
+            loss: 1.0 - win,
+            winnings: win * (target - 1.0).min(1.0),
+        })
+    })
+    .fold(0.0, f64::max)
+}
+
+fn multiplier(elapsed: Duration) -> f64 {
+    ((GROWTH * elapsed.as_secs_f64()).exp() * 100.0).floor() / 100.0
+}
+
+fn payout(amount: i32, multiplier: f64) -> i32 {
+    (amount as f64 * multiplier).floor() as i32
+}
+
+fn render(header: &str, entries: &[Entry]) -> String {
+    let mut output = format!("{header}\n\n**Players**:\n");
+
+    for entry in entries {
+        output += &match entry.cashed_out {
+            Some(m) => format!(
+                "- <@{}>: {} cashed out at {m:.2}x for **{}**\n",
+                entry.user,
+                entry.amount,
+                payout(entry.amount, m)
+            ),
+            None => format!("- <@{}>: {}\n", entry.user, entry.amount),
+        };
+    }
+
+    output
+}
+
+/// Bet on a multiplier that climbs until it crashes, cash out before it does
+#[poise::command(slash_command, prefix_command)]
+pub async fn crash(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[autocomplete = "super::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let user = ctx.author().id;
+    let channel = ctx.channel_id();
+
+    // join the round that is taking bets in this channel if there is one
+    // a round that stopped early never took its entry out, a new one replaces it
+    let open = match OPEN.lock().unwrap().get_mut(&channel) {
+        Some(open) if open.sender.is_closed() => Ok(None),
+        Some(open) if open.users.contains(&user) => Err(()),
+        Some(open) => {
+            open.users.push(user);
+            Ok(Some((open.session.clone(), open.sender.clone())))
+        }
+        None => Ok(None),
+    };
+
+    let open = match open {
+        Ok(open) => open,
+        Err(()) => {
+            ctx.reply("You are already in this round.").await?;
+            return Ok(());
+        }
+    };
+
+    let mut tx = db.begin().await?;
+
+    let Some(super::Stake {
+        amount, effects, ..
+    }) = super::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
+        if let Some(open) = OPEN.lock().unwrap().get_mut(&channel) {
+            open.users.retain(|u| *u != user);
+        }
+
+        return Ok(());
+    };
+
+    if let Some((session, sender)) = open {
+        session.hold(&mut tx, user, amount).await?;
+        tx.commit().await?;
+
+        let entry = Entry {
+            user,
+            amount,
+            effects,
+            cashed_out: None,
+        };
+
+        if sender.send(entry).is_err() {
+            let mut tx = db.begin().await?;
+            let held = session.release(&mut tx, user).await?;
+            let balance = super::get_balance(user, &mut *tx).await?;
+            super::change_balance(user, balance + held, &mut *tx).await?;
+            tx.commit().await?;
+
+            ctx.reply("That round has already taken off, your bet has been refunded.")
+                .await?;
+        } else {
+            ctx.reply(format!(
+                "You've joined the round with **{amount}** token(s)."
+            ))
+            .await?;
+        }
+
+        return Ok(());
+    }
+
+    let edge = match ctx.guild_id() {
+        Some(guild) => settings::get_crash_edge(&mut *tx, guild).await?,
+        None => 1,
+    };
+
+    let round = fair::Round::start(&mut tx, &fair::Draw::Crash { edge }).await?;
+    let point = crash_point(edge, &mut round.rng());
+
+    let msg = ctx.reply("Just a second...").await?;
+    let message = msg.message().await?.id;
+
+    let session = Session::start(&mut tx, &GAME, channel, message, &State { point }).await?;
+    session.hold(&mut tx, user, amount).await?;
+    tx.commit().await?;
+
+    let result = session
+        .refund_on_error(db, async {
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+
+            OPEN.lock().unwrap().insert(
+                channel,
+                Open {
+                    session: session.clone(),
+                    users: vec![user],
+                    sender,
+                },
+            );
+
+            let mut entries = vec![Entry {
+                user,
+                amount,
+                effects,
+                cashed_out: None,
+            }];
+
+            let header = format!(
+                "A crash round is taking off in {} seconds! Use `/crash` in this channel to join it.\n{}",
+                BETTING.as_secs(),
+                round.commitment()
+            );
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default().content(render(&header, &entries)),
+            )
+            .await?;
+
+            let takeoff = Instant::now() + BETTING;
+
+            while let Ok(Some(entry)) = tokio::time::timeout_at(takeoff, receiver.recv()).await {
+                entries.push(entry);
+
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default().content(render(&header, &entries)),
+                )
+                .await?;
+            }
+
+            // anyone who joined just before the round closed still gets in
+            OPEN.lock().unwrap().remove(&channel);
+            receiver.close();
+
+            while let Ok(entry) = receiver.try_recv() {
+                entries.push(entry);
+            }
+
+            let mut route = Route::new(&GAME.key(), session.id);
+            route.restrict(entries.iter().map(|entry| entry.user).collect());
+
+            let button = vec![serenity::CreateActionRow::Buttons(vec![
+                serenity::CreateButton::new(route.id("cashout"))
+                    .label("Cash Out")
+                    .style(serenity::ButtonStyle::Success),
+            ])];
+
+            let start = Instant::now();
+            let crash = start + Duration::from_secs_f64(point.ln() / GROWTH);
+            let mut tick = start;
+
+            loop {
+                let now = Instant::now();
+
+                if now >= crash {
+                    break;
+                }
+
+                if now >= tick {
+                    msg.edit(
+                        ctx,
+                        poise::CreateReply::default()
+                            .content(render(
+                                &format!("\u{1F4C8} **{:.2}x**", multiplier(now - start)),
+                                &entries,
+                            ))
+                            .components(button.clone()),
+                    )
+                    .await?;
+
+                    tick = now + TICK;
+                }
+
+                let Some((mci, _)) = route.next(tick.min(crash)).await else {
+                    continue;
+                };
+
+                // the multiplier is taken when the click arrives, not when the message was last edited
+                let m = multiplier(start.elapsed());
+
+                let text = match entries.iter_mut().find(|entry| entry.user == mci.user.id) {
+                    Some(entry) if entry.cashed_out.is_some() => "You have already cashed out.".to_string(),
+                    Some(_) if Instant::now() >= crash => "Too late, it already crashed!".to_string(),
+                    Some(entry) => {
+                        entry.cashed_out = Some(m);
+                        format!(
+                            "You cashed out at **{m:.2}x** for **{}** token(s)!",
+                            payout(entry.amount, m)
+                        )
+                    }
+                    None => "You are not in this round.".to_string(),
+                };
+
+                mci.create_response(
+                    ctx,
+                    serenity::CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .ephemeral(true)
+                            .content(text),
+                    ),
+                )
+                .await?;
+            }
+
+            drop(route);
+
+            let mut tx = db.begin().await?;
+
+            for entry in &mut entries {
+                let mut settlement = match entry.cashed_out {
+                    Some(m) => match payout(entry.amount, m) - entry.amount {
+                        net if net > 0 => Settlement::win(entry.amount, net),
+                        _ => Settlement::push(entry.amount),
+                    },
+                    None => Settlement::loss(entry.amount),
+                };
+
+                let effects = std::mem::take(&mut entry.effects);
+                effects.settle(&mut settlement);
+
+                let held = session.release(&mut tx, entry.user).await?;
+                let balance = super::get_balance(entry.user, &mut *tx)
+                    .await?
+                    .saturating_add(held)
+                    .saturating_add(settlement.net);
+                super::change_balance(entry.user, balance, &mut *tx).await?;
+                effects.finish(&mut tx).await?;
+                super::history::record(
+                    &mut tx,
+                    entry.user,
+                    session.channel,
+                    &GAME,
+                    entry.amount,
+                    settlement.net,
+                )
+                .await?;
+            }
+
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(render(
+                        &format!("\u{1F4A5} Crashed at **{point:.2}x**!\n{}", round.reveal()),
+                        &entries,
+                    ))
+                    .components(vec![]),
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await;
+
+    if result.is_err() {
+        // nobody can join a round that is no longer being played
+        let mut open = OPEN.lock().unwrap();
+
+        if open
+            .get(&channel)
+            .is_some_and(|open| open.session.id == session.id)
+        {
+            open.remove(&channel);
+        }
+    }
+
+    result
+}
@@ -125,6 +125,11 @@ async fn do_claim(ctx: Context<'_>) -> Result<(), Error> {
         set_streak(&mut *tx, user, streak + 1).await?;
         set_last(&mut *tx, user, now).await?;
 
+        let channel = ctx.channel_id();
+        super::achievements::add(&mut tx, user, channel, "dailies", 1).await?;
+        super::achievements::reach(&mut tx, user, channel, "daily_streak", streak as i64 + 1)
+            .await?;
+
         tx.commit().await?;
 
         ctx.reply(format!(
@@ -0,0 +1,401 @@
+use super::show;
+use crate::{
+    commands::gambling::{
+        self,
+        amount::TokenAmount,
+        effects::Settlement,
+        fair,
+        session::{Session, Status},
+    },
+    common::{Context, Error},
+    games::Game,
+    interactions::Route,
+};
+use poise::serenity_prelude::{self as serenity, CreateInteractionResponseMessage};
+use serde::Serialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The registry entry that craps' sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 449,
+    name: "Craps",
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, poise::ChoiceParameter)]
+pub enum Line {
+    #[name = "pass"]
+    Pass,
+    #[name = "don't pass"]
+    DontPass,
+}
+
+#[derive(Serialize)]
+struct Round {
+    line: Line,
+    /// The size of every bet in the round
+    stake: i32,
+    point: Option<u8>,
+    /// Come bets, along with the number each one moved to once it has one
+    come: Vec<Option<u8>>,
+    /// Whether there is a field bet on the next roll
+    field: bool,
+    /// Everything bet during the round, in i64 like what is paid back
+    staked: i64,
+    /// Everything paid back during the round, bets included, in i64 since a big stake
+    /// winning at high odds doesn't always fit in an i32
+    returned: i64,
+    rolls: Vec<(u8, u8)>,
+}
+
+impl Round {
+    fn new(line: Line, stake: i32) -> Self {
+        Self {
+            line,
+            stake,
+            point: None,
+            come: vec![],
+            field: false,
+            staked: stake as i64,
+            returned: 0,
+            rolls: vec![],
+        }
+    }
+
+    fn win(&mut self, lines: &mut Vec<String>, bet: &str, times: i32) {
+        let won = self.stake as i64 * times as i64;
+        self.returned += self.stake as i64 + won;
+        lines.push(format!("{bet} wins **{won}**!"));
+    }
+
+    fn lose(&self, lines: &mut Vec<String>, bet: &str) {
+        lines.push(format!("{bet} loses."));
+    }
+
+    /// Settle every bet the roll decides, returns what happened and whether the round is over
+    fn resolve(&mut self, dice: (u8, u8)) -> (Vec<String>, bool) {
+        let total = dice.0 + dice.1;
+        let mut lines = vec![];
+
+        self.rolls.push(dice);
+
+        if std::mem::take(&mut self.field) {
+            match total {
+                2 => self.win(&mut lines, "The field", 2),
+                12 => self.win(&mut lines, "The field", 3),
+                3 | 4 | 9 | 10 | 11 => self.win(&mut lines, "The field", 1),
+                _ => self.lose(&mut lines, "The field"),
+            }
+        }
+
+        for come in std::mem::take(&mut self.come) {
+            match (come, total) {
+                (None, 7 | 11) => self.win(&mut lines, "A come bet", 1),
+                (None, 2 | 3 | 12) => self.lose(&mut lines, "A come bet"),
+                (None, n) => {
+                    lines.push(format!("A come bet moves to {n}."));
+                    self.come.push(Some(n));
+                }
+                (Some(point), n) if n == point => {
+                    self.win(&mut lines, &format!("The come bet on {point}"), 1)
+                }
+                (Some(point), 7) => self.lose(&mut lines, &format!("The come bet on {point}")),
+                (Some(point), _) => self.come.push(Some(point)),
+            }
+        }
+
+        let line = match self.line {
+            Line::Pass => "The pass line",
+            Line::DontPass => "Don't pass",
+        };
+
+        let done = match (self.point, self.line, total) {
+            (None, Line::Pass, 7 | 11) => {
+                self.win(&mut lines, line, 1);
+                true
+            }
+            (None, Line::Pass, 2 | 3 | 12) => {
+                self.lose(&mut lines, line);
+                true
+            }
+            (None, Line::DontPass, 2 | 3) => {
+                self.win(&mut lines, line, 1);
+                true
+            }
+            (None, Line::DontPass, 12) => {
+                self.returned += self.stake as i64;
+                lines.push(format!("{line} pushes on 12."));
+                true
+            }
+            (None, Line::DontPass, 7 | 11) => {
+                self.lose(&mut lines, line);
+                true
+            }
+            (None, _, n) => {
+                self.point = Some(n);
+                lines.push(format!("The point is **{n}**."));
+                false
+            }
+            (Some(point), Line::Pass, n) if n == point => {
+                self.win(&mut lines, line, 1);
+                true
+            }
+            (Some(point), Line::DontPass, n) if n == point => {
+                self.lose(&mut lines, line);
+                true
+            }
+            (Some(_), Line::Pass, 7) => {
+                lines.push("Seven out!".to_string());
+                self.lose(&mut lines, line);
+                true
+            }
+            (Some(_), Line::DontPass, 7) => {
+                lines.push("Seven out!".to_string());
+                self.win(&mut lines, line, 1);
+                true
+            }
+            (Some(_), _, _) => false,
+        };
+
+        // come bets still waiting on their number when the round ends are given back
+        if done {
+            for point in std::mem::take(&mut self.come).into_iter().flatten() {
+                self.returned += self.stake as i64;
+                lines.push(format!("The come bet on {point} is returned."));
+            }
+        }
+
+        (lines, done)
+    }
+
+    fn render(&self, lines: &[String]) -> String {
+        let mut output = format!(
+            "**Line**: {} for {}\n**Point**: {}\n",
+            match self.line {
+                Line::Pass => "pass",
+                Line::DontPass => "don't pass",
+            },
+            self.stake,
+            self.point
+                .map(|p| p.to_string())
+                .unwrap_or("off".to_string()),
+        );
+
+        let come: Vec<String> = self
+            .come
+            .iter()
+            .map(|come| match come {
+                Some(point) => format!("on {point}"),
+                None => "waiting on the next roll".to_string(),
+            })
+            .collect();
+
+        if !come.is_empty() {
+            output += &format!("**Come bets**: {}\n", come.join(", "));
+        }
+
+        if self.field {
+            output += &format!("**Field**: {} on the next roll\n", self.stake);
+        }
+
+        if let Some(dice) = self.rolls.last() {
+            output += &format!("\n**Roll**: {}\n", show(*dice));
+        }
+
+        for line in lines {
+            output += &format!("{line}\n");
+        }
+
+        output
+    }
+}
+
+fn buttons(route: &Route, round: &Round) -> Vec<serenity::CreateActionRow> {
+    vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(route.id("roll"))
+            .label("Roll")
+            .style(serenity::ButtonStyle::Success),
+        serenity::CreateButton::new(route.id("come"))
+            .label(format!("Come ({})", round.stake))
+            .style(serenity::ButtonStyle::Primary)
+            .disabled(round.point.is_none()),
+        serenity::CreateButton::new(route.id("field"))
+            .label(format!("Field ({})", round.stake))
+            .style(serenity::ButtonStyle::Primary)
+            .disabled(round.field),
+    ])]
+}
+
+/// Shoot dice on a pass or don't pass line, with come and field bets of the same size along the way
+#[poise::command(slash_command, prefix_command)]
+pub async fn craps(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[description = "The line to bet on, pass by default"] line: Option<Line>,
+    #[autocomplete = "gambling::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let user = ctx.author().id;
+    let mut tx = db.begin().await?;
+
+    let Some(gambling::Stake {
+        amount, effects, ..
+    }) = gambling::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
+        return Ok(());
+    };
+
+    let (mut rng, nonce) = fair::next(&mut tx, user, &fair::Draw::Dice).await?;
+    let mut round = Round::new(line.unwrap_or(Line::Pass), amount);
+
+    let msg = ctx.reply("Just a second...").await?;
+    let message = msg.message().await?.id;
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), message, &round).await?;
+    session.hold(&mut tx, user, amount).await?;
+    tx.commit().await?;
+
+    session
+        .refund_on_error(db, async {
+            let mut route = Route::new(&GAME.key(), session.id);
+            route.restrict(vec![user]);
+
+            let mut lines = vec!["Roll the dice to come out.".to_string()];
+
+            loop {
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(round.render(&lines))
+                        .components(buttons(&route, &round)),
+                )
+                .await?;
+
+                // a player who walks away has their dice rolled for them
+                let action = match route.next(Instant::now() + Duration::from_secs(120)).await {
+                    Some((mci, action)) => {
+                        // the buttons for bets that can't be made are disabled, but a click can race an edit
+                        let allowed = match action.as_str() {
+                            "come" => round.point.is_some(),
+                            "field" => !round.field,
+                            _ => false,
+                        };
+
+                        if allowed {
+                            let mut tx = db.begin().await?;
+                            let balance = gambling::get_balance(user, &mut *tx).await?;
+
+                            let refusal = if balance < round.stake {
+                                Some(format!(
+                                    "You do not have enough tokens (**{balance}**) for another bet."
+                                ))
+                            } else {
+                                gambling::limits::check(&mut tx, user, ctx.guild_id(), round.stake)
+                                    .await?
+                            };
+
+                            if let Some(refusal) = refusal {
+                                mci.create_response(
+                                    ctx,
+                                    serenity::CreateInteractionResponse::Message(
+                                        CreateInteractionResponseMessage::new()
+                                            .ephemeral(true)
+                                            .content(refusal),
+                                    ),
+                                )
+                                .await?;
+                                continue;
+                            }
+
+                            if action == "come" {
+                                round.come.push(None);
+                            } else {
+                                round.field = true;
+                            }
+
+                            // every bet is taken from the balance first, so the total fits in an i32
+                            round.staked += round.stake as i64;
+                            session
+                                .hold(&mut tx, user, i32::try_from(round.staked)?)
+                                .await?;
+                            session.save(&mut tx, &round).await?;
+                            tx.commit().await?;
+                        }
+
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        action
+                    }
+                    None => "roll".to_string(),
+                };
+
+                if action != "roll" {
+                    lines.clear();
+                    continue;
+                }
+
+                let done;
+                (lines, done) = round.resolve(super::roll(&mut rng));
+
+                if done {
+                    break;
+                }
+
+                let mut tx = db.begin().await?;
+                session.save(&mut tx, &round).await?;
+                tx.commit().await?;
+            }
+
+            drop(route);
+
+            // the whole round is settled as one bet so that effects apply once
+            let staked = i32::try_from(round.staked)?;
+            let net = i32::try_from(round.returned - round.staked).unwrap_or(i32::MAX);
+            let mut settlement = Settlement::net(staked, net);
+            effects.settle(&mut settlement);
+
+            let mut tx = db.begin().await?;
+            let held = session.release(&mut tx, user).await?;
+            let balance = gambling::get_balance(user, &mut *tx)
+                .await?
+                .saturating_add(held)
+                .saturating_add(settlement.net);
+            gambling::change_balance(user, balance, &mut *tx).await?;
+            effects.finish(&mut tx).await?;
+            gambling::history::record(
+                &mut tx,
+                user,
+                session.channel,
+                &GAME,
+                staked,
+                settlement.net,
+            )
+            .await?;
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+
+            let summary = if settlement.net > 0 {
+                format!("You've won **{}** token(s)!", settlement.net)
+            } else if settlement.net < 0 {
+                format!("You've lost **{}** token(s).", -settlement.net)
+            } else {
+                "You broke even.".to_string()
+            };
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(format!(
+                        "{}\n{summary} You now have **{balance}**.\n{}",
+                        round.render(&lines),
+                        fair::footer(nonce)
+                    ))
+                    .components(vec![]),
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await
+}
@@ -0,0 +1,257 @@
+use super::show;
+use crate::{
+    commands::gambling::{
+        self,
+        amount::TokenAmount,
+        effects::Settlement,
+        fair,
+        session::{Session, Status},
+    },
+    common::{Context, Error},
+    games,
+    interactions::Route,
+};
+use poise::serenity_prelude as serenity;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The registry entry that high-low's sessions, history and stats are kept under
+pub const GAME: games::Game = games::Game {
+    id: 450,
+    name: "High-Low",
+};
+
+/// The share of every correct guess the house keeps
+const EDGE: f64 = 0.03;
+
+/// The highest the multiplier goes, a player who reaches it can only cash out
+const MAX_MULTIPLIER: f64 = 1000.0;
+
+/// The chance of two dice adding up to each total from 2 to 12, out of 36
+const WAYS: [u32; 11] = [1, 2, 3, 4, 5, 6, 5, 4, 3, 2, 1];
+
+fn chance(total: u8, higher: bool) -> f64 {
+    let ways: u32 = (2..=12u8)
+        .zip(WAYS)
+        .filter(|(n, _)| if higher { *n > total } else { *n < total })
+        .map(|(_, ways)| ways)
+        .sum();
+
+    ways as f64 / 36.0
+}
+
+/// What the multiplier grows by for guessing right, ties count as a wrong guess
+fn step(total: u8, higher: bool) -> Option<f64> {
+    let chance = chance(total, higher);
+    (chance > 0.0).then(|| (1.0 - EDGE) / chance)
+}
+
+#[derive(Serialize)]
+struct Game {
+    stake: i32,
+    dice: (u8, u8),
+    multiplier: f64,
+}
+
+impl Game {
+    fn total(&self) -> u8 {
+        self.dice.0 + self.dice.1
+    }
+
+    /// What the multiplier grows by for guessing right from the current roll, `None` if
+    /// the guess can't be made
+    fn step(&self, higher: bool) -> Option<f64> {
+        step(self.total(), higher).filter(|_| self.multiplier < MAX_MULTIPLIER)
+    }
+
+    fn payout(&self) -> i32 {
+        let payout = (self.stake as f64 * self.multiplier).floor() as i64;
+        i32::try_from(payout).unwrap_or(i32::MAX)
+    }
+
+    fn render(&self, notice: &str) -> String {
+        format!(
+            "**Roll**: {}\n**Multiplier**: {:.2}x, cashing out pays **{}**\n\n{notice}",
+            show(self.dice),
+            self.multiplier,
+            self.payout()
+        )
+    }
+}
+
+fn buttons(route: &Route, game: &Game) -> Vec<serenity::CreateActionRow> {
+    let guess = |action: &str, label: &str, higher: bool| {
+        let step = game.step(higher);
+
+        serenity::CreateButton::new(route.id(action))
+            .label(match step {
+                Some(step) => format!("{label} ({step:.2}x)"),
+                None => label.to_string(),
+            })
+            .style(serenity::ButtonStyle::Primary)
+            .disabled(step.is_none())
+    };
+
+    vec![serenity::CreateActionRow::Buttons(vec![
+        guess("higher", "Higher", true),
+        guess("lower", "Lower", false),
+        serenity::CreateButton::new(route.id("cashout"))
+            .label("Cash Out")
+            .style(serenity::ButtonStyle::Success),
+    ])]
+}
+
+/// Guess whether the next roll of two dice is higher or lower, cash out whenever you like
+#[poise::command(slash_command, prefix_command, aliases("hl"))]
+pub async fn highlow(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[autocomplete = "gambling::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let user = ctx.author().id;
+    let mut tx = db.begin().await?;
+
+    let Some(gambling::Stake {
+        amount, effects, ..
+    }) = gambling::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
+        return Ok(());
+    };
+
+    let (mut rng, nonce) = fair::next(&mut tx, user, &fair::Draw::Dice).await?;
+
+    let mut game = Game {
+        stake: amount,
+        dice: super::roll(&mut rng),
+        multiplier: 1.0,
+    };
+
+    let msg = ctx.reply("Just a second...").await?;
+    let message = msg.message().await?.id;
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), message, &game).await?;
+    session.hold(&mut tx, user, amount).await?;
+    tx.commit().await?;
+
+    session
+        .refund_on_error(db, async {
+            let mut route = Route::new(&GAME.key(), session.id);
+            route.restrict(vec![user]);
+
+            let mut notice = "Will the next roll be higher or lower?".to_string();
+
+            let won = loop {
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(game.render(&notice))
+                        .components(buttons(&route, &game)),
+                )
+                .await?;
+
+                // walking away cashes out
+                let Some((mci, action)) =
+                    route.next(Instant::now() + Duration::from_secs(60)).await
+                else {
+                    break true;
+                };
+
+                mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                    .await?;
+
+                let higher = match action.as_str() {
+                    "higher" => true,
+                    "lower" => false,
+                    _ => break true,
+                };
+
+                let Some(step) = game.step(higher) else {
+                    continue;
+                };
+
+                let before = game.total();
+                game.dice = super::roll(&mut rng);
+
+                let right = if higher {
+                    game.total() > before
+                } else {
+                    game.total() < before
+                };
+
+                if !right {
+                    break false;
+                }
+
+                game.multiplier = (game.multiplier * step).min(MAX_MULTIPLIER);
+                notice = format!("Right! It was {}.", show(game.dice));
+
+                if game.multiplier >= MAX_MULTIPLIER {
+                    notice += " That's as high as the multiplier goes, cash out to collect it.";
+                }
+
+                let mut tx = db.begin().await?;
+                session.save(&mut tx, &game).await?;
+                tx.commit().await?;
+            };
+
+            drop(route);
+
+            let mut settlement = if won {
+                Settlement::net(amount, game.payout() - amount)
+            } else {
+                Settlement::loss(amount)
+            };
+
+            effects.settle(&mut settlement);
+
+            let mut tx = db.begin().await?;
+            let held = session.release(&mut tx, user).await?;
+            let balance = gambling::get_balance(user, &mut *tx)
+                .await?
+                .saturating_add(held)
+                .saturating_add(settlement.net);
+            gambling::change_balance(user, balance, &mut *tx).await?;
+            effects.finish(&mut tx).await?;
+            gambling::history::record(
+                &mut tx,
+                user,
+                session.channel,
+                &GAME,
+                amount,
+                settlement.net,
+            )
+            .await?;
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+
+            let result = if won {
+                format!(
+                    "You cashed out at **{:.2}x**. You now have **{balance}** token(s).",
+                    game.multiplier
+                )
+            } else {
+                format!(
+                    "Wrong guess, you've lost **{}** token(s). You now have **{balance}**.",
+                    -settlement.net
+                )
+            };
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(format!(
+                        "**Roll**: {}\n\n{result}\n{}",
+                        show(game.dice),
+                        fair::footer(nonce)
+                    ))
+                    .components(vec![]),
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await
+}
@@ -0,0 +1,18 @@
+use rand::Rng;
+
+pub mod craps;
+pub mod highlow;
+
+/// Roll two six sided dice
+pub fn roll(rng: &mut impl Rng) -> (u8, u8) {
+    (rng.gen_range(1..=6), rng.gen_range(1..=6))
+}
+
+/// The face of a die showing `n`
+fn face(n: u8) -> char {
+    char::from_u32(0x2680 + n as u32 - 1).unwrap_or('?')
+}
+
+fn show((a, b): (u8, u8)) -> String {
+    format!("{} {} (**{}**)", face(a), face(b), a + b)
+}
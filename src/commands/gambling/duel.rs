@@ -0,0 +1,230 @@
+use super::{
+    amount::TokenAmount,
+    fair,
+    session::{Session, Status},
+};
+use crate::{
+    commands::settings,
+    common::{BigBirbError, Context, Error},
+    games::Game,
+    interactions::Route,
+};
+use poise::serenity_prelude::{self as serenity, UserId};
+use rand::Rng;
+use serde::Serialize;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The registry entry that duel's sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 444,
+    name: "Duel",
+};
+
+/// Flip the coin, heads wins it for the challenger
+pub fn flip(rng: &mut impl Rng) -> bool {
+    rng.gen_bool(0.5)
+}
+
+#[derive(Serialize)]
+struct Duel {
+    challenger: UserId,
+    opponent: UserId,
+    amount: i32,
+}
+
+async fn respond(
+    ctx: Context<'_>,
+    mci: &serenity::ComponentInteraction,
+    text: &str,
+) -> Result<(), Error> {
+    mci.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::Message(
+            serenity::CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(text),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Challenge someone to a coin flip, the winner takes both stakes
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn duel(
+    ctx: Context<'_>,
+    user: serenity::User,
+    amount: TokenAmount,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let db = &ctx.data().database;
+    let challenger = ctx.author().id;
+
+    if user.bot {
+        ctx.reply("Bots don't gamble, challenge someone else!")
+            .await?;
+        return Ok(());
+    }
+
+    if user.id == challenger {
+        ctx.reply("You cannot duel yourself!").await?;
+        return Ok(());
+    }
+
+    let mut tx = db.begin().await?;
+    let balance = super::get_balance(challenger, &mut *tx).await?;
+
+    let Some(amount) = amount.resolve(ctx, balance).await? else {
+        return Ok(());
+    };
+
+    if !super::limits::allowed(ctx, &mut tx, amount).await? {
+        return Ok(());
+    }
+
+    let duel = Duel {
+        challenger,
+        opponent: user.id,
+        amount,
+    };
+
+    let round = fair::Round::start(&mut tx, &fair::Draw::Duel).await?;
+
+    let challenge = format!(
+        "{user}, {} has challenged you to a coin flip for **{amount}** token(s)! Match the stake to accept.\n{}",
+        ctx.author(),
+        round.commitment()
+    );
+
+    // the buttons need the session's id, the challenge is sent first so the opponent is pinged
+    let msg = ctx.reply(&challenge).await?;
+    let message = msg.message().await?.id;
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), message, &duel).await?;
+    session.hold(&mut tx, challenger, amount).await?;
+    tx.commit().await?;
+
+    session
+        .refund_on_error(db, async {
+            let mut route = Route::new(&GAME.key(), session.id);
+            route.restrict(vec![user.id]);
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(challenge)
+                    .components(vec![serenity::CreateActionRow::Buttons(vec![
+                        serenity::CreateButton::new(route.id("accept"))
+                            .label("Accept")
+                            .style(serenity::ButtonStyle::Success),
+                        serenity::CreateButton::new(route.id("decline"))
+                            .label("Decline")
+                            .style(serenity::ButtonStyle::Danger),
+                    ])]),
+            )
+            .await?;
+
+            let deadline = Instant::now() + Duration::from_secs(120);
+
+            let accepted = loop {
+                let Some((mci, action)) = route.next(deadline).await else {
+                    break None;
+                };
+
+                if action == "decline" {
+                    mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                        .await?;
+                    break None;
+                }
+
+                let mut tx = db.begin().await?;
+                let balance = super::get_balance(user.id, &mut *tx).await?;
+
+                if balance < amount {
+                    respond(
+                        ctx,
+                        &mci,
+                        &format!("You do not have enough tokens (**{balance}**) to match this stake."),
+                    )
+                    .await?;
+                    continue;
+                }
+
+                if let Some(reason) = super::limits::check(&mut tx, user.id, Some(guild), amount).await? {
+                    respond(ctx, &mci, &reason).await?;
+                    continue;
+                }
+
+                mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                    .await?;
+                session.hold(&mut tx, user.id, amount).await?;
+
+                break Some(tx);
+            };
+
+            let Some(mut tx) = accepted else {
+                let mut tx = db.begin().await?;
+                session.refund(&mut tx).await?;
+                tx.commit().await?;
+
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(format!(
+                            "{user} didn't take the duel, your **{amount}** token(s) have been refunded."
+                        ))
+                        .components(vec![]),
+                )
+                .await?;
+
+                return Ok(());
+            };
+
+            let (winner, loser) = if flip(&mut round.rng()) {
+                (challenger, user.id)
+            } else {
+                (user.id, challenger)
+            };
+
+            // two big stakes don't always fit in an i32 together
+            let pot = session.release(&mut tx, loser).await? as i64
+                + session.release(&mut tx, winner).await? as i64;
+            let cut = pot * settings::get_duel_cut(&mut *tx, guild).await? as i64 / 100;
+            let won = i32::try_from(pot - cut).unwrap_or(i32::MAX);
+            let balance = super::get_balance(winner, &mut *tx).await?.saturating_add(won);
+            super::change_balance(winner, balance, &mut *tx).await?;
+            super::history::record(
+                &mut tx,
+                winner,
+                session.channel,
+                &GAME,
+                amount,
+                won.saturating_sub(amount),
+            )
+            .await?;
+            super::history::record(&mut tx, loser, session.channel, &GAME, amount, -amount).await?;
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+
+            let cut = if cut > 0 {
+                format!(" after the house took **{cut}**")
+            } else {
+                String::new()
+            };
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(format!(
+                        "The coin has been flipped... <@{winner}> wins the pot of **{pot}** token(s){cut} against <@{loser}>!\n{}",
+                        round.reveal()
+                    ))
+                    .components(vec![]),
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await
+}
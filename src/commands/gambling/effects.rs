@@ -0,0 +1,330 @@
+use super::{Effect, Item};
+use crate::common::{Context, Error};
+use poise::serenity_prelude::UserId;
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgConnection, Row};
+use std::fmt::Display;
+
+/// The most tokens of a bet or its winnings that a multiplier or chance effect counts, so that
+/// a cheap item isn't worth more the more is bet
+pub const MOST_BOOSTED: i32 = 250;
+
+/// The most tokens a refund on a loss gives back
+pub const MOST_REFUNDED: i32 = 25;
+
+/// About how much seeing the dealer's hidden card adds to the return of blackjack, as a share
+/// of the bet, for a player who changes how they play to match it
+const PEEK_RETURN: f64 = 0.10;
+
+/// About how much taking back the card that makes a hand bust adds to the return of blackjack,
+/// as a share of the bet. Basic strategy busts around one hand in six, most of which would
+/// otherwise still have had a chance.
+const FORGIVENESS_RETURN: f64 = 0.15;
+
+/// How a game pays out a bet of [`MOST_BOOSTED`] tokens, the biggest bet effects are worth the
+/// most on, as shares of the bet. Where a game can't say exactly, these are the most they can be.
+#[derive(Clone, Copy)]
+pub struct Odds {
+    /// What is paid back on average without any effects
+    pub expected: f64,
+    pub win: f64,
+    pub loss: f64,
+    /// The winnings on top of the bet on average, counting no more than the bet on any one win
+    pub winnings: f64,
+}
+
+impl Odds {
+    /// What is paid back on average with every one of `effects`, any chance of winning they
+    /// add has to be in the odds already
+    pub fn with(&self, effects: &[&Effect]) -> f64 {
+        let bet = MOST_BOOSTED as f64;
+
+        // each multiplier also multiplies what the ones before it added
+        let multiplier: f64 = effects
+            .iter()
+            .filter_map(|effect| match effect {
+                Effect::Multiplier(m) => Some(*m),
+                _ => None,
+            })
+            .product();
+
+        effects.iter().fold(
+            self.expected + self.winnings * (multiplier - 1.0),
+            |total, effect| {
+                total
+                    + match effect {
+                        Effect::RefundOnLoss(fraction) => {
+                            self.loss * fraction.min(MOST_REFUNDED as f64 / bet)
+                        }
+                        Effect::BonusOnWin(bonus) => self.win * *bonus as f64 / bet * multiplier,
+                        Effect::Peek => PEEK_RETURN,
+                        Effect::BustForgiveness => FORGIVENESS_RETURN,
+                        Effect::Multiplier(_) | Effect::Chance(_) => 0.0,
+                    }
+            },
+        )
+    }
+}
+
+/// Values a game decides its outcome with, before the outcome is rolled
+pub struct Roll {
+    /// The chance of winning, only meaningful for games with a single win probability
+    pub chance: f64,
+    /// The tokens being bet
+    pub bet: i32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Push,
+}
+
+/// The result of a bet as it is being paid out
+pub struct Settlement {
+    pub outcome: Outcome,
+    /// The tokens the player put forward
+    pub bet: i32,
+    /// The change to the player's balance, negative when they lose tokens
+    pub net: i32,
+}
+
+impl Settlement {
+    /// A win paying `winnings` on top of the bet being returned
+    pub fn win(bet: i32, winnings: i32) -> Self {
+        Self {
+            outcome: Outcome::Win,
+            bet,
+            net: winnings,
+        }
+    }
+
+    pub fn loss(bet: i32) -> Self {
+        Self {
+            outcome: Outcome::Loss,
+            bet,
+            net: -bet,
+        }
+    }
+
+    pub fn push(bet: i32) -> Self {
+        Self {
+            outcome: Outcome::Push,
+            bet,
+            net: 0,
+        }
+    }
+
+    /// A bet made up of several smaller ones that has already been paid out as a whole,
+    /// it is a win, loss or push depending on which way the balance went
+    pub fn net(bet: i32, net: i32) -> Self {
+        match net {
+            net if net > 0 => Self::win(bet, net),
+            0 => Self::push(bet),
+            net => Self::loss(-net),
+        }
+    }
+}
+
+/// Points in a game at which effects may change the outcome of a bet. Games with a single
+/// chance of winning, like wager, call `before_roll` before rolling. Every game then runs the
+/// settlement through `on_win` or `on_loss`, then `on_payout`, with [`Effects::settle`].
+pub trait Hook {
+    fn before_roll(&self, _roll: &mut Roll) {}
+    fn on_win(&self, _settlement: &mut Settlement) {}
+    fn on_loss(&self, _settlement: &mut Settlement) {}
+    fn on_payout(&self, _settlement: &mut Settlement) {}
+}
+
+impl Hook for Effect {
+    fn before_roll(&self, roll: &mut Roll) {
+        if let Effect::Chance(c) = self {
+            // spread over a bigger bet, the extra chance is worth no more than on the most boosted
+            let share = (MOST_BOOSTED as f64 / roll.bet as f64).min(1.0);
+            roll.chance = (roll.chance + c * share).min(1.0);
+        }
+    }
+
+    fn on_win(&self, settlement: &mut Settlement) {
+        if let Effect::BonusOnWin(bonus) = self {
+            settlement.net += bonus;
+        }
+    }
+
+    fn on_loss(&self, settlement: &mut Settlement) {
+        if let Effect::RefundOnLoss(fraction) = self {
+            settlement.net += ((settlement.bet as f64 * fraction) as i32).min(MOST_REFUNDED);
+        }
+    }
+
+    fn on_payout(&self, settlement: &mut Settlement) {
+        if let Effect::Multiplier(m) = self
+            && settlement.net > 0
+        {
+            let extra = settlement.net.min(MOST_BOOSTED) as f64 * (m - 1.0);
+            settlement.net = settlement.net.saturating_add(extra as i32);
+        }
+    }
+}
+
+impl Display for Effect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Multiplier(m) => write!(
+                f,
+                "{m}x multiplier on up to {MOST_BOOSTED} token(s) of earnings"
+            ),
+            Self::Chance(c) => write!(
+                f,
+                "+{:.0}% chance of winning, less on bets over {MOST_BOOSTED} token(s)",
+                c * 100.0
+            ),
+            Self::Peek => write!(f, "see the dealer's hidden card"),
+            Self::BustForgiveness => write!(f, "forgive your first bust"),
+            Self::RefundOnLoss(r) => write!(
+                f,
+                "{:.0}% of your bet refunded on a loss, up to {MOST_REFUNDED} token(s)",
+                r * 100.0
+            ),
+            Self::BonusOnWin(b) => write!(f, "{b} bonus token(s) on a win"),
+        }
+    }
+}
+
+/// All of the effects that apply to a single bet: those of the item used for it
+/// and any buffs the player has active.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Effects {
+    effects: Vec<Effect>,
+    buffs: Vec<i64>,
+}
+
+impl Effects {
+    pub async fn load(
+        db: &mut PgConnection,
+        user: UserId,
+        item: Option<&Item>,
+    ) -> Result<Self, Error> {
+        let rows = sqlx::query("SELECT id, effect FROM buffs WHERE userid = $1 AND bets > 0")
+            .bind(user.get() as i64)
+            .fetch_all(db)
+            .await?;
+
+        let mut effects: Vec<Effect> = item.map(|item| item.effects.to_vec()).unwrap_or_default();
+        let mut buffs = vec![];
+
+        for row in rows {
+            let Json(effect): Json<Effect> = row.try_get(1)?;
+            buffs.push(row.try_get(0)?);
+            effects.push(effect);
+        }
+
+        Ok(Self { effects, buffs })
+    }
+
+    /// Whether any effect matches, for effects that only make sense in one game
+    pub fn any(&self, f: impl Fn(&Effect) -> bool) -> bool {
+        self.effects.iter().any(f)
+    }
+
+    /// Run a settlement through the win or loss hooks, then the payout hooks
+    pub fn settle(&self, settlement: &mut Settlement) {
+        match settlement.outcome {
+            Outcome::Win => self.on_win(settlement),
+            Outcome::Loss => self.on_loss(settlement),
+            Outcome::Push => (),
+        }
+
+        self.on_payout(settlement);
+    }
+
+    /// Count the bet against every active buff and remove the ones that have run out
+    pub async fn finish(self, db: &mut PgConnection) -> Result<(), Error> {
+        sqlx::query("UPDATE buffs SET bets = bets - 1 WHERE id = ANY($1)")
+            .bind(&self.buffs)
+            .execute(&mut *db)
+            .await?;
+
+        sqlx::query("DELETE FROM buffs WHERE id = ANY($1) AND bets <= 0")
+            .bind(&self.buffs)
+            .execute(&mut *db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Hook for Effects {
+    fn before_roll(&self, roll: &mut Roll) {
+        self.effects.iter().for_each(|e| e.before_roll(roll));
+    }
+
+    fn on_win(&self, settlement: &mut Settlement) {
+        self.effects.iter().for_each(|e| e.on_win(settlement));
+    }
+
+    fn on_loss(&self, settlement: &mut Settlement) {
+        self.effects.iter().for_each(|e| e.on_loss(settlement));
+    }
+
+    fn on_payout(&self, settlement: &mut Settlement) {
+        self.effects.iter().for_each(|e| e.on_payout(settlement));
+    }
+}
+
+/// Give a user an effect that applies to their next `bets` bets in any game
+pub async fn give_buff(
+    db: &mut PgConnection,
+    user: UserId,
+    effect: &Effect,
+    bets: i32,
+) -> Result<(), Error> {
+    // getting a buff that is already active makes it last longer instead of stacking it, so a
+    // bet never has more of one buff than servers' odds are checked with
+    let extended = sqlx::query(
+        "UPDATE buffs SET bets = bets + $3 WHERE id = (SELECT id FROM buffs WHERE userid = $1 AND effect::jsonb = $2 AND bets > 0 LIMIT 1)",
+    )
+    .bind(user.get() as i64)
+    .bind(Json(effect))
+    .bind(bets)
+    .execute(&mut *db)
+    .await?;
+
+    if extended.rows_affected() == 0 {
+        sqlx::query("INSERT INTO buffs (userid, effect, bets) VALUES ($1, $2, $3)")
+            .bind(user.get() as i64)
+            .bind(Json(effect))
+            .bind(bets)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// List the buffs you have active
+#[poise::command(slash_command, prefix_command)]
+pub async fn buffs(ctx: Context<'_>) -> Result<(), Error> {
+    let rows = sqlx::query("SELECT effect, bets FROM buffs WHERE userid = $1 AND bets > 0")
+        .bind(ctx.author().id.get() as i64)
+        .fetch_all(&ctx.data().database)
+        .await?;
+
+    if rows.is_empty() {
+        ctx.reply("You have no active buffs.").await?;
+        return Ok(());
+    }
+
+    let mut output = String::new();
+
+    for row in rows {
+        let Json(effect): Json<Effect> = row.try_get(0)?;
+        let bets: i32 = row.try_get(1)?;
+        output += &format!("- {effect} for your next **{bets}** bet(s)\n");
+    }
+
+    ctx.reply(output).await?;
+
+    Ok(())
+}
@@ -0,0 +1,475 @@
+use super::{
+    cards::{self, Shoe},
+    crash, dice, duel, lottery, mines, race, roulette, slots,
+};
+use crate::common::{self, Context, Error};
+
+use hmac::{Hmac, Mac};
+use poise::serenity_prelude::UserId;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, Row};
+
+/// The seeds a user's gambling outcomes are generated from. The server seed is kept secret
+/// and only its hash is shown until the seeds are rotated, so it can't be changed after
+/// the fact without the hash giving it away.
+struct Seeds {
+    server: String,
+    client: String,
+    /// How many bets have been made with these seeds
+    nonce: i32,
+}
+
+impl Seeds {
+    fn hash(&self) -> String {
+        hash(&self.server)
+    }
+}
+
+/// A random hex string of `bytes` bytes
+fn random_seed(bytes: usize) -> String {
+    let mut seed = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut seed);
+    hex::encode(seed)
+}
+
+fn hash(server: &str) -> String {
+    hex::encode(Sha256::digest(server.as_bytes()))
+}
+
+/// The random number generator a bet is played with, anyone can rebuild it from the seeds
+/// once the server seed is revealed
+pub fn rng(server: &str, client: &str, nonce: i32) -> ChaCha20Rng {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(server.as_bytes()).expect("HMAC can take keys of any size");
+    mac.update(format!("{client}:{nonce}").as_bytes());
+
+    ChaCha20Rng::from_seed(mac.finalize().into_bytes().into())
+}
+
+async fn get_seeds(db: &mut PgConnection, user: UserId) -> Result<Seeds, Error> {
+    let row = sqlx::query(
+        "INSERT INTO fair_seeds (userid, server_seed, client_seed) VALUES ($1, $2, $3) ON CONFLICT (userid) DO UPDATE SET userid = EXCLUDED.userid RETURNING server_seed, client_seed, nonce",
+    )
+    .bind(user.get() as i64)
+    .bind(random_seed(32))
+    .bind(random_seed(8))
+    .fetch_one(db)
+    .await?;
+
+    Ok(Seeds {
+        server: row.try_get(0)?,
+        client: row.try_get(1)?,
+        nonce: row.try_get(2)?,
+    })
+}
+
+/// Use up the next nonce of a user's seeds for a bet, keeping what else it is played with so
+/// `/verify` can work it out later. Returns the generator the bet is played with, along with
+/// the nonce so the player can verify it.
+pub async fn next(
+    db: &mut PgConnection,
+    user: UserId,
+    draw: &Draw,
+) -> Result<(ChaCha20Rng, i32), Error> {
+    let seeds = get_seeds(&mut *db, user).await?;
+
+    sqlx::query("UPDATE fair_seeds SET nonce = nonce + 1 WHERE userid = $1")
+        .bind(user.get() as i64)
+        .execute(&mut *db)
+        .await?;
+
+    sqlx::query("INSERT INTO fair_bets (userid, seed_hash, nonce, draw) VALUES ($1, $2, $3, $4)")
+        .bind(user.get() as i64)
+        .bind(seeds.hash())
+        .bind(seeds.nonce)
+        .bind(serde_json::to_string(draw)?)
+        .execute(db)
+        .await?;
+
+    Ok((rng(&seeds.server, &seeds.client, seeds.nonce), seeds.nonce))
+}
+
+/// The seed of an outcome that several players share, like where a crash round crashes. Its hash
+/// is shown before anyone bets and the seed itself once the round is over, so it can't have been
+/// picked with the bets in mind.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Round {
+    seed: String,
+}
+
+impl Round {
+    /// A new round with a random seed, keeping what else it is played with for `/verify`
+    pub async fn start(db: &mut PgConnection, draw: &Draw) -> Result<Self, Error> {
+        let round = Self {
+            seed: random_seed(32),
+        };
+
+        sqlx::query("INSERT INTO fair_rounds (seed_hash, draw) VALUES ($1, $2)")
+            .bind(hash(&round.seed))
+            .bind(serde_json::to_string(draw)?)
+            .execute(db)
+            .await?;
+
+        Ok(round)
+    }
+
+    pub fn rng(&self) -> ChaCha20Rng {
+        rng(&self.seed, "", 0)
+    }
+
+    /// A note shown while the round is taking bets
+    pub fn commitment(&self) -> String {
+        format!("-# Round seed hash: `{}`", hash(&self.seed))
+    }
+
+    /// A note shown once the round is over so it can be checked with `/verify round`
+    pub fn reveal(&self) -> String {
+        format!(
+            "-# Round seed: `{}`, check it with /verify round.",
+            self.seed
+        )
+    }
+}
+
+/// A note shown under the result of a bet so it can be looked up with `/verify`
+pub fn footer(nonce: i32) -> String {
+    format!("-# Bet #{nonce}, check it with /verify bet once you rotate your seeds.")
+}
+
+/// See the hash of your current server seed, your client seed and how many bets they were used for
+#[poise::command(slash_command, prefix_command)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+    let seeds = get_seeds(&mut tx, ctx.author().id).await?;
+    tx.commit().await?;
+
+    ctx.reply(format!(
+        "**Server seed hash**: `{}`\n**Client seed**: `{}`\n**Bets made**: {}",
+        seeds.hash(),
+        seeds.client,
+        seeds.nonce
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Reveal your current server seed and start using a new one
+#[poise::command(slash_command, prefix_command)]
+async fn rotate(
+    ctx: Context<'_>,
+    #[description = "A new client seed, your current one is kept otherwise"] client: Option<String>,
+) -> Result<(), Error> {
+    let user = ctx.author().id;
+    let mut tx = ctx.data().database.begin().await?;
+    let old = get_seeds(&mut tx, user).await?;
+
+    let new = Seeds {
+        server: random_seed(32),
+        client: client.unwrap_or(old.client.clone()),
+        nonce: 0,
+    };
+
+    sqlx::query(
+        "UPDATE fair_seeds SET server_seed = $2, client_seed = $3, nonce = 0 WHERE userid = $1",
+    )
+    .bind(user.get() as i64)
+    .bind(&new.server)
+    .bind(&new.client)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "Your old seeds were used for {} bet(s):\n**Server seed**: `{}`\n**Server seed hash**: `{}`\n**Client seed**: `{}`\n\nYour new server seed hash is `{}` with the client seed `{}`.",
+            old.nonce,
+            old.server,
+            old.hash(),
+            old.client,
+            new.hash(),
+            new.client
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Choose the client seed your next bets are made with
+#[poise::command(slash_command, prefix_command)]
+async fn client(ctx: Context<'_>, #[rest] seed: String) -> Result<(), Error> {
+    let user = ctx.author().id;
+    let mut tx = ctx.data().database.begin().await?;
+
+    // make sure the user has seeds before changing them
+    get_seeds(&mut tx, user).await?;
+
+    sqlx::query("UPDATE fair_seeds SET client_seed = $2 WHERE userid = $1")
+        .bind(user.get() as i64)
+        .bind(&seed)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    common::no_ping_reply(&ctx, format!("Your client seed is now `{seed}`.")).await?;
+
+    Ok(())
+}
+
+/// Provably fair gambling, every outcome comes from your seeds and can be checked with `/verify`
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("show", "rotate", "client"),
+    subcommand_required
+)]
+pub async fn fair(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Everything an outcome is drawn with besides its seeds
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Draw {
+    /// A roll against the chance to win, after items
+    Wager {
+        chance: f64,
+    },
+    /// A shoe for the player's hands, then one for the dealer's when they play alone
+    Blackjack {
+        decks: usize,
+    },
+    /// The dealer's shoe at a table of several players
+    Dealer {
+        decks: usize,
+    },
+    Mines {
+        mines: usize,
+    },
+    Roulette,
+    /// The weight of every stop on each reel
+    Slots {
+        reels: Vec<Vec<u32>>,
+    },
+    /// Pairs of dice rolled one after another
+    Dice,
+    Crash {
+        edge: i32,
+    },
+    Duel,
+    /// The kind of race, which the entrants' names are picked from. Rounds from before the names
+    /// were drawn from the seed leave it empty.
+    Race {
+        #[serde(default)]
+        kind: String,
+        entrants: usize,
+        stride: usize,
+    },
+    /// A single deck for one hand of poker
+    Poker,
+    /// Everyone holding lottery tickets and how many they hold, in the order they are drawn from
+    Lottery {
+        holders: Vec<(u64, i32)>,
+    },
+}
+
+impl Draw {
+    fn outcome(&self, rng: &mut ChaCha20Rng) -> String {
+        match self {
+            Self::Wager { chance } => format!(
+                "rolled **{:.4}**, winning if it is under the chance to win of **{chance:.4}**",
+                rng.r#gen::<f64>()
+            ),
+            Self::Blackjack { decks } => {
+                let player = Shoe::shuffled(*decks, &mut *rng);
+                let dealer = Shoe::shuffled(*decks, &mut *rng);
+
+                format!(
+                    "dealt your cards from {decks} deck(s) starting with {}, and the dealer's starting with {} if you played alone",
+                    cards::show(&player.top(10)),
+                    cards::show(&dealer.top(10))
+                )
+            }
+            Self::Dealer { decks } => format!(
+                "dealt the dealer's cards from {decks} deck(s) starting with {}",
+                cards::show(&Shoe::shuffled(*decks, rng).top(10))
+            ),
+            Self::Mines { mines } => {
+                let tiles: Vec<String> = mines::order(rng)
+                    .iter()
+                    .take(*mines)
+                    .map(|tile| (tile + 1).to_string())
+                    .collect();
+
+                format!(
+                    "placed the mines on tiles {}, counting from the top left",
+                    tiles.join(", ")
+                )
+            }
+            Self::Roulette => format!(
+                "landed the ball on **{}**",
+                roulette::spin(rng)
+            ),
+            Self::Slots { reels } => {
+                let stops: Vec<String> = slots::stops(reels, rng)
+                    .iter()
+                    .map(|stop| (stop + 1).to_string())
+                    .collect();
+
+                format!(
+                    "stopped the reels on positions {}, counting from the top of each strip",
+                    stops.join(", ")
+                )
+            }
+            Self::Dice => {
+                let rolls: Vec<String> = (0..10)
+                    .map(|_| {
+                        let (a, b) = dice::roll(&mut *rng);
+                        format!("{a}+{b}")
+                    })
+                    .collect();
+
+                format!("rolled {} in that order", rolls.join(", "))
+            }
+            Self::Crash { edge } => format!(
+                "crashed at **{:.2}x** with a house edge of {edge}%",
+                crash::crash_point(*edge, rng)
+            ),
+            Self::Duel => format!(
+                "flipped **{}**, heads wins it for the challenger",
+                if duel::flip(rng) { "heads" } else { "tails" }
+            ),
+            Self::Race {
+                kind,
+                entrants,
+                stride,
+            } => {
+                if kind.is_empty() {
+                    return format!(
+                        "was won by entrant **{}**",
+                        race::simulate(*entrants, *stride, rng).1 + 1
+                    );
+                }
+
+                let field = race::field(kind, *entrants, &mut *rng);
+                let winner = race::simulate(*entrants, *stride, rng).1;
+
+                format!(
+                    "was run by {} and won by entrant **{}**, {}",
+                    field.join(", "),
+                    winner + 1,
+                    field[winner]
+                )
+            }
+            Self::Poker => format!(
+                "shuffled the deck as {}, dealing two cards to each player from the first seat and then the board",
+                cards::show(&Shoe::shuffled(1, rng).top(23))
+            ),
+            Self::Lottery { holders } => {
+                let counts: Vec<i32> = holders.iter().map(|(_, count)| *count).collect();
+                let sold: i32 = counts.iter().sum();
+
+                match lottery::pick(&counts, rng) {
+                    Ok(winner) => format!(
+                        "drew <@{}> as the winner out of {sold} ticket(s)",
+                        holders[winner].0
+                    ),
+                    Err(_) => "had no tickets to draw from".to_string(),
+                }
+            }
+        }
+    }
+}
+
+/// Work out the outcome of one of your past bets from its seeds
+#[poise::command(slash_command, prefix_command)]
+async fn bet(
+    ctx: Context<'_>,
+    #[description = "The revealed server seed"] server: String,
+    #[description = "The client seed the bet was made with"] client: String,
+    #[description = "The number of the bet"] nonce: i32,
+) -> Result<(), Error> {
+    let draw = match sqlx::query(
+        "SELECT draw FROM fair_bets WHERE userid = $1 AND seed_hash = $2 AND nonce = $3",
+    )
+    .bind(ctx.author().id.get() as i64)
+    .bind(hash(&server))
+    .bind(nonce)
+    .fetch_one(&ctx.data().database)
+    .await
+    {
+        Ok(row) => serde_json::from_str::<Draw>(row.try_get(0)?)?,
+        Err(sqlx::Error::RowNotFound) => {
+            ctx.reply(format!(
+                "You haven't made a bet #{nonce} with that server seed."
+            ))
+            .await?;
+            return Ok(());
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let outcome = draw.outcome(&mut rng(&server, &client, nonce));
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "**Server seed hash**: `{}`\nBet #{nonce} {outcome}.",
+            hash(&server)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Work out the outcome of a round several players bet on from its revealed seed
+#[poise::command(slash_command, prefix_command)]
+async fn round(
+    ctx: Context<'_>,
+    #[description = "The revealed round seed"] seed: String,
+) -> Result<(), Error> {
+    let draw = match sqlx::query("SELECT draw FROM fair_rounds WHERE seed_hash = $1")
+        .bind(hash(&seed))
+        .fetch_one(&ctx.data().database)
+        .await
+    {
+        Ok(row) => serde_json::from_str::<Draw>(row.try_get(0)?)?,
+        Err(sqlx::Error::RowNotFound) => {
+            ctx.reply("No round was played with that seed.").await?;
+            return Ok(());
+        }
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    let outcome = draw.outcome(&mut Round { seed: seed.clone() }.rng());
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "**Round seed hash**: `{}`\nThe round {outcome}.",
+            hash(&seed)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Work out the outcome of a past bet or round from its seeds
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("bet", "round"),
+    subcommand_required
+)]
+pub async fn verify(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
@@ -1,21 +1,20 @@
+use super::amount::TokenAmount;
 use crate::{common, Context, Error};
 use poise::serenity_prelude as serenity;
 
 /// Generously donate your tokens to someone else
 #[poise::command(slash_command, prefix_command, aliases("g"))]
-pub async fn give(ctx: Context<'_>, user: serenity::User, amount: i32) -> Result<(), Error> {
+pub async fn give(
+    ctx: Context<'_>,
+    user: serenity::User,
+    amount: TokenAmount,
+) -> Result<(), Error> {
     if user.bot {
         ctx.reply("Don't waste your tokens by giving them to a bot!")
             .await?;
         return Ok(());
     }
 
-    if amount < 1 {
-        ctx.reply("You cannot give someone a negative amount of money (that's stealing!).")
-            .await?;
-        return Ok(());
-    }
-
     if user == *ctx.author() {
         ctx.reply("You cannot give yourself money!").await?;
         return Ok(());
@@ -24,27 +23,28 @@ pub async fn give(ctx: Context<'_>, user: serenity::User, amount: i32) -> Result
     let mut tx = ctx.data().database.begin().await?;
     let balance = super::get_balance(ctx.author().id, &mut *tx).await?;
 
-    if balance < amount {
-        ctx.reply(format!(
-            "You do not have a high enough balance (**{balance}**) to complete this transaction."
-        ))
-        .await?;
-    } else {
-        super::change_balance(
-            user.id,
-            super::get_balance(user.id, &mut *tx).await? + amount,
-            &mut *tx,
-        )
-        .await?;
-        super::change_balance(ctx.author().id, balance - amount, &mut *tx).await?;
-        tx.commit().await?;
-
-        common::no_ping_reply(
-            &ctx,
-            format!("You've given {} **{}** tokens!", user, amount),
-        )
-        .await?;
-    }
+    let Some(amount) = amount.resolve(ctx, balance).await? else {
+        return Ok(());
+    };
+
+    super::change_balance(
+        user.id,
+        super::get_balance(user.id, &mut *tx).await? + amount,
+        &mut *tx,
+    )
+    .await?;
+    super::change_balance(ctx.author().id, balance - amount, &mut *tx).await?;
+
+    let (author, channel) = (ctx.author().id, ctx.channel_id());
+    super::achievements::add(&mut tx, author, channel, "gifts", 1).await?;
+    super::achievements::add(&mut tx, author, channel, "tokens_given", amount as i64).await?;
+    tx.commit().await?;
+
+    common::no_ping_reply(
+        &ctx,
+        format!("You've given {} **{}** tokens!", user, amount),
+    )
+    .await?;
 
     Ok(())
 }
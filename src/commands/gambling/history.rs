@@ -0,0 +1,56 @@
+use super::achievements;
+use crate::{common::Error, games::Game};
+
+use poise::serenity_prelude::{ChannelId, UserId};
+use sqlx::{PgConnection, Row};
+
+/// Keep track of a settled bet, add it to the player's stats for the game and count it toward
+/// their achievements. `net` is what the player's balance changed by in the end, negative when
+/// they lost, and `channel` is where the bet was played.
+pub async fn record(
+    db: &mut PgConnection,
+    user: UserId,
+    channel: ChannelId,
+    game: &Game,
+    bet: i32,
+    net: i32,
+) -> Result<(), Error> {
+    sqlx::query("INSERT INTO gambling_history (userid, game, bet, net) VALUES ($1, $2, $3, $4)")
+        .bind(user.get() as i64)
+        .bind(game.id as i64)
+        .bind(bet)
+        .bind(net)
+        .execute(&mut *db)
+        .await?;
+
+    super::stats::add(&mut *db, user, game, bet, net).await?;
+
+    let won = i64::from(net > 0);
+
+    achievements::add(&mut *db, user, channel, "bets", i64::from(bet > 0)).await?;
+    achievements::add(&mut *db, user, channel, "wagered", bet as i64).await?;
+    achievements::add(&mut *db, user, channel, "wins", won).await?;
+    achievements::add(
+        &mut *db,
+        user,
+        channel,
+        &format!("{}_wins", game.key()),
+        won,
+    )
+    .await?;
+    achievements::reach(db, user, channel, "biggest_win", net as i64).await
+}
+
+/// How many tokens a user has lost overall in the past `hours` hours, 0 if they are up
+pub async fn losses(db: &mut PgConnection, user: UserId, hours: i32) -> Result<i64, Error> {
+    let net: i64 = sqlx::query(
+        "SELECT COALESCE(SUM(net), 0)::BIGINT FROM gambling_history WHERE userid = $1 AND at > now() - $2 * INTERVAL '1 hour'",
+    )
+    .bind(user.get() as i64)
+    .bind(hours)
+    .fetch_one(db)
+    .await?
+    .try_get(0)?;
+
+    Ok((-net).max(0))
+}
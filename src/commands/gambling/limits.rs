@@ -0,0 +1,603 @@
+use super::{amount::TokenAmount, history};
+use crate::{
+    common::{self, BigBirbError, Context, Error},
+    interactions::Route,
+};
+
+use poise::serenity_prelude::{self as serenity, GuildId, Member, UserId};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection, Row,
+};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Limits a user sets for themselves are stored under this guild id, they apply everywhere
+const OWN: i64 = 0;
+
+/// How long raising or removing your own limit takes to apply
+const COOLDOWN_HOURS: u64 = 24;
+
+#[derive(Clone, Copy, poise::ChoiceParameter)]
+pub enum Kind {
+    #[name = "daily loss"]
+    Daily,
+    #[name = "weekly loss"]
+    Weekly,
+    #[name = "bet"]
+    Bet,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+            Self::Bet => "bet",
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Daily => "daily loss limit",
+            Self::Weekly => "weekly loss limit",
+            Self::Bet => "bet limit",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Daily => "Daily loss limit",
+            Self::Weekly => "Weekly loss limit",
+            Self::Bet => "Bet limit",
+        }
+    }
+
+    /// The window losses are counted over
+    fn hours(&self) -> Option<i32> {
+        match self {
+            Self::Daily => Some(24),
+            Self::Weekly => Some(24 * 7),
+            Self::Bet => None,
+        }
+    }
+}
+
+/// A limit that currently applies, and whether a server imposed it
+struct Limit {
+    amount: i32,
+    imposed: bool,
+}
+
+async fn get_limit(
+    db: &mut PgConnection,
+    user: UserId,
+    guild: i64,
+    kind: Kind,
+) -> Result<Option<i32>, Error> {
+    match sqlx::query("SELECT amount FROM gambling_limits WHERE userid = $1 AND guildid = $2 AND kind = $3 AND starts <= now() ORDER BY starts DESC LIMIT 1")
+        .bind(user.get() as i64)
+        .bind(guild)
+        .bind(kind.as_str())
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => Ok(row.try_get(0)?),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// A change to a user's own limit that hasn't applied yet
+async fn get_pending(
+    db: &mut PgConnection,
+    user: UserId,
+    kind: Kind,
+) -> Result<Option<(Option<i32>, DateTime<Utc>)>, Error> {
+    match sqlx::query("SELECT amount, starts FROM gambling_limits WHERE userid = $1 AND guildid = $2 AND kind = $3 AND starts > now() ORDER BY starts DESC LIMIT 1")
+        .bind(user.get() as i64)
+        .bind(OWN)
+        .bind(kind.as_str())
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => Ok(Some((row.try_get(0)?, row.try_get(1)?))),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// The strictest of a user's own limit and the one the server imposed on them
+async fn effective(
+    db: &mut PgConnection,
+    user: UserId,
+    guild: Option<GuildId>,
+    kind: Kind,
+) -> Result<Option<Limit>, Error> {
+    let own = get_limit(&mut *db, user, OWN, kind)
+        .await?
+        .map(|amount| Limit {
+            amount,
+            imposed: false,
+        });
+
+    let imposed = match guild {
+        Some(guild) => get_limit(&mut *db, user, guild.get() as i64, kind)
+            .await?
+            .map(|amount| Limit {
+                amount,
+                imposed: true,
+            }),
+        None => None,
+    };
+
+    Ok(match (own, imposed) {
+        (Some(own), Some(imposed)) if imposed.amount < own.amount => Some(imposed),
+        (Some(own), _) => Some(own),
+        (None, imposed) => imposed,
+    })
+}
+
+/// The latest time a user is excluded from gambling until, and whether a server suspended them
+async fn excluded(
+    db: &mut PgConnection,
+    user: UserId,
+    guild: Option<GuildId>,
+) -> Result<Option<(DateTime<Utc>, bool)>, Error> {
+    let guild = guild.map(|g| g.get() as i64).unwrap_or(OWN);
+
+    match sqlx::query("SELECT until, guildid <> $2 FROM gambling_exclusions WHERE userid = $1 AND guildid IN ($2, $3) AND until > now() ORDER BY until DESC LIMIT 1")
+        .bind(user.get() as i64)
+        .bind(OWN)
+        .bind(guild)
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => Ok(Some((row.try_get(0)?, row.try_get(1)?))),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Tokens a user has in games that haven't finished yet, they count towards loss limits. That
+/// includes lottery tickets, which aren't recorded as bets until they are drawn.
+async fn in_play(db: &mut PgConnection, user: UserId) -> Result<i64, Error> {
+    Ok(sqlx::query(
+        "SELECT (COALESCE((SELECT SUM(escrow.amount) FROM escrow JOIN sessions ON sessions.id = escrow.session WHERE escrow.userid = $1 AND sessions.status = 'active'), 0) + COALESCE((SELECT SUM(spent) FROM lottery_tickets WHERE userid = $1), 0))::BIGINT",
+    )
+    .bind(user.get() as i64)
+    .fetch_one(db)
+    .await?
+    .try_get(0)?)
+}
+
+/// Check a bet against a user's limits. Returns why it was refused, or `None` if it can be made.
+pub async fn check(
+    db: &mut PgConnection,
+    user: UserId,
+    guild: Option<GuildId>,
+    amount: i32,
+) -> Result<Option<String>, Error> {
+    if let Some((until, imposed)) = excluded(&mut *db, user, guild).await? {
+        return Ok(Some(if imposed {
+            format!(
+                "You have been suspended from gambling in this server until <t:{}:f>.",
+                until.timestamp()
+            )
+        } else {
+            format!(
+                "You have excluded yourself from gambling until <t:{}:f>.",
+                until.timestamp()
+            )
+        }));
+    }
+
+    let whose = |limit: &Limit| {
+        if limit.imposed {
+            "Your server-imposed"
+        } else {
+            "Your"
+        }
+    };
+
+    let cap = effective(&mut *db, user, guild, Kind::Bet).await?;
+
+    if let Some(limit) = cap.filter(|limit| amount > limit.amount) {
+        return Ok(Some(format!(
+            "{} bet limit is **{}**, so you can't bet **{amount}**.",
+            whose(&limit),
+            limit.amount
+        )));
+    }
+
+    for kind in [Kind::Daily, Kind::Weekly] {
+        let Some(limit) = effective(&mut *db, user, guild, kind).await? else {
+            continue;
+        };
+
+        let lost = history::losses(&mut *db, user, kind.hours().unwrap_or_default()).await?;
+        let held = in_play(&mut *db, user).await?;
+        let left = limit.amount as i64 - lost - held;
+
+        if amount as i64 > left {
+            return Ok(Some(format!(
+                "{} {} is **{}**. You've lost **{lost}** and have **{held}** in games that are still going, so you can bet at most **{}** right now.",
+                whose(&limit),
+                kind.describe(),
+                limit.amount,
+                left.max(0)
+            )));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Check a bet from a command against the author's limits, telling them why if it was refused
+pub async fn allowed(ctx: Context<'_>, db: &mut PgConnection, amount: i32) -> Result<bool, Error> {
+    match check(db, ctx.author().id, ctx.guild_id(), amount).await? {
+        Some(reason) => {
+            ctx.reply(reason).await?;
+            Ok(false)
+        }
+        None => Ok(true),
+    }
+}
+
+fn describe_amount(amount: Option<i32>) -> String {
+    amount
+        .map(|a| format!("**{a}**"))
+        .unwrap_or("no limit".to_string())
+}
+
+/// See your gambling limits, or a member's if you manage the server
+#[poise::command(slash_command, prefix_command)]
+async fn show(ctx: Context<'_>, member: Option<Member>) -> Result<(), Error> {
+    let user = match &member {
+        Some(member) if member.user.id != ctx.author().id => {
+            let author = ctx.author_member().await.ok_or(BigBirbError::GuildOnly)?;
+
+            if !author.permissions(ctx).iter().any(|p| p.manage_guild()) {
+                ctx.reply("You do not have permission to see other members' limits.")
+                    .await?;
+                return Ok(());
+            }
+
+            member.user.id
+        }
+        _ => ctx.author().id,
+    };
+
+    let guild = ctx.guild_id();
+    let mut tx = ctx.data().database.begin().await?;
+    let mut output = String::new();
+
+    for kind in [Kind::Daily, Kind::Weekly, Kind::Bet] {
+        let own = get_limit(&mut tx, user, OWN, kind).await?;
+        output += &format!("**{}**: {}", kind.title(), describe_amount(own));
+
+        let imposed = match guild {
+            Some(guild) => get_limit(&mut tx, user, guild.get() as i64, kind).await?,
+            None => None,
+        };
+
+        if let Some(imposed) = imposed {
+            output += &format!(", **{imposed}** set by this server");
+        }
+
+        if let Some((amount, starts)) = get_pending(&mut tx, user, kind).await? {
+            output += &format!(
+                ", changing to {} <t:{}:R>",
+                describe_amount(amount),
+                starts.timestamp()
+            );
+        }
+
+        if let Some(hours) = kind.hours() {
+            output += &format!(
+                " (**{}** lost so far)",
+                history::losses(&mut tx, user, hours).await?
+            );
+        }
+
+        output += "\n";
+    }
+
+    if let Some((until, imposed)) = excluded(&mut tx, user, guild).await? {
+        output += &format!(
+            "**{}** until <t:{}:f>\n",
+            if imposed {
+                "Suspended by this server"
+            } else {
+                "Self-excluded"
+            },
+            until.timestamp()
+        );
+    }
+
+    common::no_ping_reply(&ctx, output).await?;
+
+    Ok(())
+}
+
+/// Set one of your own limits, lowering it applies right away but raising or removing it takes a day
+#[poise::command(slash_command, prefix_command)]
+async fn set(
+    ctx: Context<'_>,
+    kind: Kind,
+    #[description = "Leave this out to remove the limit"] amount: Option<TokenAmount>,
+) -> Result<(), Error> {
+    let user = ctx.author().id;
+
+    let amount = match amount.map(|amount| amount.fixed()).transpose() {
+        Ok(amount) => amount,
+        Err(e) => {
+            ctx.reply(e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    if amount.is_some_and(|a| a < 1) {
+        ctx.reply("A limit has to be at least 1 token.").await?;
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().database.begin().await?;
+    let current = get_limit(&mut tx, user, OWN, kind).await?;
+
+    let stricter = match (current, amount) {
+        (None, _) => true,
+        (Some(current), Some(amount)) => amount <= current,
+        (Some(_), None) => false,
+    };
+
+    let starts = if stricter {
+        Utc::now()
+    } else {
+        Utc::now() + Duration::from_secs(COOLDOWN_HOURS * 60 * 60)
+    };
+
+    // a newer change replaces any that is still waiting to apply
+    sqlx::query("DELETE FROM gambling_limits WHERE userid = $1 AND guildid = $2 AND kind = $3 AND starts > now()")
+        .bind(user.get() as i64)
+        .bind(OWN)
+        .bind(kind.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("INSERT INTO gambling_limits (userid, guildid, kind, amount, starts) VALUES ($1, $2, $3, $4, $5)")
+        .bind(user.get() as i64)
+        .bind(OWN)
+        .bind(kind.as_str())
+        .bind(amount)
+        .bind(starts)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    let reply = if stricter {
+        format!(
+            "Your {} is now {}.",
+            kind.describe(),
+            describe_amount(amount)
+        )
+    } else {
+        format!(
+            "Your {} will change to {} <t:{}:R>. Until then it stays at {}.",
+            kind.describe(),
+            describe_amount(amount),
+            starts.timestamp(),
+            describe_amount(current)
+        )
+    };
+
+    ctx.reply(reply).await?;
+
+    Ok(())
+}
+
+/// Stop yourself from gambling for a number of days, this can't be undone until it runs out
+#[poise::command(slash_command, prefix_command)]
+async fn exclude(
+    ctx: Context<'_>,
+    #[description = "How many days to stop gambling for (1-3650)"]
+    #[min = 1]
+    #[max = 3650]
+    days: u32,
+) -> Result<(), Error> {
+    if !(1..=3650).contains(&days) {
+        ctx.reply("You can exclude yourself for 1 to 3650 days.")
+            .await?;
+        return Ok(());
+    }
+
+    let until = Utc::now() + Duration::from_secs(days as u64 * 24 * 60 * 60);
+
+    let mut route = Route::unique("exclude");
+    route.restrict(vec![ctx.author().id]);
+
+    let msg = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "You won't be able to gamble until <t:{}:f>, and this can't be cancelled early. Are you sure?",
+                    until.timestamp()
+                ))
+                .components(vec![serenity::CreateActionRow::Buttons(vec![
+                    serenity::CreateButton::new(route.id("confirm"))
+                        .label("Exclude me")
+                        .style(serenity::ButtonStyle::Danger),
+                    serenity::CreateButton::new(route.id("cancel"))
+                        .label("Cancel")
+                        .style(serenity::ButtonStyle::Secondary),
+                ])]),
+        )
+        .await?;
+
+    let confirmed = match route.next(Instant::now() + Duration::from_secs(60)).await {
+        Some((mci, action)) => {
+            mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                .await?;
+            action == "confirm"
+        }
+        None => false,
+    };
+
+    let reply = if confirmed {
+        // an exclusion can only ever be made longer
+        let until: DateTime<Utc> = sqlx::query("INSERT INTO gambling_exclusions (userid, guildid, until) VALUES ($1, $2, $3) ON CONFLICT (userid, guildid) DO UPDATE SET until = GREATEST(gambling_exclusions.until, EXCLUDED.until) RETURNING until")
+            .bind(ctx.author().id.get() as i64)
+            .bind(OWN)
+            .bind(until)
+            .fetch_one(&ctx.data().database)
+            .await?
+            .try_get(0)?;
+
+        format!(
+            "You've excluded yourself from gambling until <t:{}:f>.",
+            until.timestamp()
+        )
+    } else {
+        "You have not been excluded.".to_string()
+    };
+
+    msg.edit(
+        ctx,
+        poise::CreateReply::default()
+            .content(reply)
+            .components(vec![]),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Put a limit on a member's gambling in this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn impose(
+    ctx: Context<'_>,
+    member: Member,
+    kind: Kind,
+    #[description = "Leave this out to remove the limit"] amount: Option<TokenAmount>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    let amount = match amount.map(|amount| amount.fixed()).transpose() {
+        Ok(amount) => amount,
+        Err(e) => {
+            ctx.reply(e.to_string()).await?;
+            return Ok(());
+        }
+    };
+
+    if amount.is_some_and(|a| a < 1) {
+        ctx.reply("A limit has to be at least 1 token.").await?;
+        return Ok(());
+    }
+
+    let mut tx = ctx.data().database.begin().await?;
+
+    sqlx::query("DELETE FROM gambling_limits WHERE userid = $1 AND guildid = $2 AND kind = $3")
+        .bind(member.user.id.get() as i64)
+        .bind(guild.get() as i64)
+        .bind(kind.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    if let Some(amount) = amount {
+        sqlx::query("INSERT INTO gambling_limits (userid, guildid, kind, amount, starts) VALUES ($1, $2, $3, $4, now())")
+            .bind(member.user.id.get() as i64)
+            .bind(guild.get() as i64)
+            .bind(kind.as_str())
+            .bind(amount)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "{member}'s {} in this server is now {}.",
+            kind.describe(),
+            describe_amount(amount)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Stop a member from gambling in this server for a number of days, leave the days out to lift it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn suspend(
+    ctx: Context<'_>,
+    member: Member,
+    #[description = "How many days to suspend them for (1-3650)"]
+    #[min = 1]
+    #[max = 3650]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let db = &ctx.data().database;
+
+    let Some(days) = days else {
+        sqlx::query("DELETE FROM gambling_exclusions WHERE userid = $1 AND guildid = $2")
+            .bind(member.user.id.get() as i64)
+            .bind(guild.get() as i64)
+            .execute(db)
+            .await?;
+
+        common::no_ping_reply(&ctx, format!("{member} can gamble in this server again.")).await?;
+        return Ok(());
+    };
+
+    if !(1..=3650).contains(&days) {
+        ctx.reply("Members can be suspended for 1 to 3650 days.")
+            .await?;
+        return Ok(());
+    }
+
+    let until = Utc::now() + Duration::from_secs(days as u64 * 24 * 60 * 60);
+
+    sqlx::query("INSERT INTO gambling_exclusions (userid, guildid, until) VALUES ($1, $2, $3) ON CONFLICT (userid, guildid) DO UPDATE SET until = EXCLUDED.until")
+        .bind(member.user.id.get() as i64)
+        .bind(guild.get() as i64)
+        .bind(until)
+        .execute(db)
+        .await?;
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "{member} can't gamble in this server until <t:{}:f>.",
+            until.timestamp()
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Limits on how much you can lose or bet, and time away from gambling
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("show", "set", "exclude", "impose", "suspend"),
+    subcommand_required
+)]
+pub async fn limits(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
@@ -0,0 +1,350 @@
+use super::{effects, items, Effect, Item, Rarity};
+use crate::{
+    common::{BigBirbError, Context, Error},
+    inventory::Inventory,
+};
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{self as serenity, futures::StreamExt, GuildId, UserId};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::Deserialize;
+use sqlx::{PgExecutor, Row};
+use std::{collections::HashMap, time::Duration};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Reward {
+    Tokens { min: i32, max: i32 },
+    Item { id: u64, count: u32 },
+    Buff { effect: Effect, bets: i32 },
+}
+
+#[derive(Deserialize)]
+struct Drop {
+    /// Identifies the drop in recorded openings, so it has to stay the same when the table is edited.
+    key: String,
+    weight: u32,
+    reward: Reward,
+    /// Only used for token and buff rewards, items use the rarity from the catalog.
+    #[serde(default)]
+    rarity: Rarity,
+}
+
+impl Drop {
+    fn item(&self) -> Option<&'static Item> {
+        match self.reward {
+            Reward::Item { id, .. } => items::get_item_by_id(id),
+            Reward::Tokens { .. } | Reward::Buff { .. } => None,
+        }
+    }
+
+    fn rarity(&self) -> Rarity {
+        self.item().map(|item| item.rarity).unwrap_or(self.rarity)
+    }
+
+    fn describe(&self) -> String {
+        match self.reward {
+            Reward::Tokens { min, max } => format!("{min}-{max} tokens"),
+            Reward::Item { count, .. } => format!("{count}x {}", self.item().unwrap().name),
+            Reward::Buff { ref effect, bets } => format!("{effect} for {bets} bet(s)"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DropTable {
+    /// The catalog id of the crate this table belongs to
+    item: u64,
+    drops: Vec<Drop>,
+}
+
+static DROP_TABLES: Lazy<HashMap<u64, DropTable>> = Lazy::new(|| {
+    let tables: Vec<DropTable> =
+        serde_json::from_str(include_str!("crates.json")).expect("crates.json is malformed");
+
+    for table in &tables {
+        assert!(
+            items::get_item_by_id(table.item).is_some(),
+            "crates.json references unknown crate {}",
+            table.item
+        );
+
+        for (i, drop) in table.drops.iter().enumerate() {
+            assert!(
+                table.drops[..i].iter().all(|other| other.key != drop.key),
+                "crates.json has more than one drop keyed {} in crate {}",
+                drop.key,
+                table.item
+            );
+
+            if let Reward::Item { id, .. } = drop.reward {
+                assert!(
+                    items::get_item_by_id(id).is_some(),
+                    "crates.json references unknown item {id}"
+                );
+            }
+        }
+    }
+
+    tables
+        .into_iter()
+        .map(|table| (table.item, table))
+        .collect()
+});
+
+/// Every effect a crate can give as a buff
+pub fn buffs() -> impl Iterator<Item = &'static Effect> {
+    DROP_TABLES
+        .values()
+        .flat_map(|table| &table.drops)
+        .filter_map(|drop| match &drop.reward {
+            Reward::Buff { effect, .. } => Some(effect),
+            Reward::Tokens { .. } | Reward::Item { .. } => None,
+        })
+}
+
+/// How many fake rolls are shown before the real one
+const FRAMES: usize = 4;
+
+fn roll(table: &DropTable, rng: &mut impl Rng) -> usize {
+    let dist = WeightedIndex::new(table.drops.iter().map(|drop| drop.weight))
+        .expect("drop table has no valid weights");
+    dist.sample(rng)
+}
+
+async fn record_opening<'a, E>(
+    db: E,
+    user: UserId,
+    guild: Option<GuildId>,
+    item: u64,
+    drop: &str,
+    tokens: i32,
+) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query(
+        r#"
+        INSERT INTO crate_openings (userid, guildid, crate, drop_key, tokens)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(user.get() as i64)
+    .bind(guild.map(|g| g.get() as i64))
+    .bind(item as i64)
+    .bind(drop)
+    .bind(tokens)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+async fn autocomplete_crates<'a>(
+    ctx: Context<'a>,
+    partial: &'a str,
+) -> impl Iterator<Item = serenity::AutocompleteChoice> + use<'a> {
+    let db = &ctx.data().database;
+
+    let owned = Inventory::new(ctx.author().id, Some(super::ID))
+        .items(db)
+        .await
+        .fold(HashMap::<i64, usize>::new(), |mut acc, item| async {
+            if let Ok(item) = item {
+                *acc.entry(item.item).or_default() += 1;
+            }
+            acc
+        })
+        .await;
+
+    owned
+        .into_iter()
+        .filter(|(id, _)| DROP_TABLES.contains_key(&(*id as u64)))
+        .filter_map(|(id, count)| items::get_item_by_id(id as u64).map(|item| (item, count)))
+        .filter(move |(item, _)| item.name.contains(partial))
+        .map(|(item, count)| {
+            serenity::AutocompleteChoice::new(format!("{} ({count}x)", item.name), item.name)
+        })
+}
+
+async fn autocomplete_crate_names<'a>(
+    _ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = &'static str> + use<'a> {
+    DROP_TABLES
+        .keys()
+        .filter_map(|id| items::get_item_by_id(*id))
+        .map(|item| item.name)
+        .filter(move |name| name.contains(partial))
+}
+
+/// Open a crate from your inventory
+#[poise::command(slash_command, prefix_command)]
+pub async fn open(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_crates"]
+    #[rest]
+    item: String,
+) -> Result<(), Error> {
+    let Some(item) = items::get_item_by_name(&item) else {
+        ctx.reply(format!("item {item} does not exist.")).await?;
+        return Ok(());
+    };
+
+    let Some(table) = DROP_TABLES.get(&item.id) else {
+        ctx.reply(format!("A(n) {} cannot be opened.", item.name))
+            .await?;
+        return Ok(());
+    };
+
+    let user = ctx.author().id;
+    let mut tx = ctx.data().database.begin().await?;
+    let inventory = Inventory::new(user, Some(super::ID));
+
+    let Some(owned) = inventory.get_item_of_type(&mut *tx, item.id).await? else {
+        ctx.reply(format!("You do not have a(n) {} to open.", item.name))
+            .await?;
+        return Ok(());
+    };
+
+    inventory.remove_item(&mut *tx, owned.id).await?;
+
+    let (frames, index, tokens) = {
+        let mut rng = rand::thread_rng();
+        let frames: Vec<usize> = (0..FRAMES).map(|_| roll(table, &mut rng)).collect();
+        let index = roll(table, &mut rng);
+
+        let tokens = match table.drops[index].reward {
+            Reward::Tokens { min, max } => rng.gen_range(min..=max),
+            Reward::Item { .. } | Reward::Buff { .. } => 0,
+        };
+
+        (frames, index, tokens)
+    };
+
+    let drop = &table.drops[index];
+
+    match &drop.reward {
+        Reward::Tokens { .. } => {
+            let balance = super::get_balance(user, &mut *tx).await?;
+            super::change_balance(user, balance.saturating_add(tokens), &mut *tx).await?;
+        }
+        Reward::Item { count, .. } => {
+            let reward = drop.item().unwrap();
+
+            for _ in 0..*count {
+                inventory
+                    .give_item(&mut *tx, reward.clone().inv_item())
+                    .await?;
+            }
+        }
+        Reward::Buff { effect, bets } => {
+            effects::give_buff(&mut *tx, user, effect, *bets).await?;
+        }
+    }
+
+    record_opening(&mut *tx, user, ctx.guild_id(), item.id, &drop.key, tokens).await?;
+    tx.commit().await?;
+
+    let msg = ctx
+        .reply(format!("Opening a(n) **{}**...", item.name))
+        .await?;
+
+    for frame in frames {
+        tokio::time::sleep(Duration::from_millis(600)).await;
+
+        let drop = &table.drops[frame];
+        msg.edit(
+            ctx,
+            poise::CreateReply::default().content(format!(
+                "Opening a(n) **{}**...\n> {} ({})",
+                item.name,
+                drop.describe(),
+                drop.rarity()
+            )),
+        )
+        .await?;
+    }
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+
+    let found = match drop.reward {
+        Reward::Tokens { .. } => format!("**{tokens}** token(s)"),
+        Reward::Item { .. } | Reward::Buff { .. } => format!("**{}**", drop.describe()),
+    };
+
+    msg.edit(
+        ctx,
+        poise::CreateReply::default().content(format!(
+            "You opened a(n) **{}** and found {found}! ({})",
+            item.name,
+            drop.rarity()
+        )),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Compare the drop rates of a crate in this server against its drop table
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn droprates(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_crate_names"]
+    #[rest]
+    item: String,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    let Some(table) = items::get_item_by_name(&item).and_then(|item| DROP_TABLES.get(&item.id))
+    else {
+        ctx.reply(format!("{item} is not a crate.")).await?;
+        return Ok(());
+    };
+
+    let rows = sqlx::query(
+        r#"
+        SELECT drop_key, COUNT(*) FROM crate_openings
+        WHERE crate = $1 AND guildid = $2
+        GROUP BY drop_key
+        "#,
+    )
+    .bind(table.item as i64)
+    .bind(guild.get() as i64)
+    .fetch_all(&ctx.data().database)
+    .await?;
+
+    let observed: HashMap<String, i64> = rows.iter().map(|row| (row.get(0), row.get(1))).collect();
+
+    let opened: i64 = observed.values().sum();
+    let total_weight: u32 = table.drops.iter().map(|drop| drop.weight).sum();
+
+    let mut output = String::new();
+
+    for drop in &table.drops {
+        let count = observed.get(&drop.key).copied().unwrap_or(0);
+        let expected = drop.weight as f64 / total_weight as f64 * 100.0;
+        let actual = if opened > 0 {
+            count as f64 / opened as f64 * 100.0
+        } else {
+            0.0
+        };
+
+        output += &format!(
+            "{} ({}) - expected {expected:.2}%, observed {actual:.2}% ({count})\n",
+            drop.describe(),
+            drop.rarity()
+        );
+    }
+
+    ctx.reply(format!(
+        "**{item}** has been opened **{opened}** time(s) in this server.\n```\n{output}```"
+    ))
+    .await?;
+
+    Ok(())
+}
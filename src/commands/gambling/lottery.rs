@@ -0,0 +1,360 @@
+use super::{amount::TokenAmount, fair};
+use crate::{
+    common::{self, BigBirbError, Context, Error},
+    games::Game,
+};
+
+use poise::serenity_prelude::{self as serenity, ChannelId, GuildId, UserId};
+use rand::{
+    distributions::{WeightedError, WeightedIndex},
+    prelude::Distribution,
+    Rng,
+};
+use sqlx::{
+    types::chrono::{DateTime, Utc},
+    PgConnection, PgPool, Row,
+};
+use std::time::Duration;
+
+/// The registry entry that lottery's history and stats are kept under
+pub const GAME: Game = Game {
+    id: 448,
+    name: "Lottery",
+};
+
+struct Lottery {
+    channel: ChannelId,
+    price: i32,
+    pot: i32,
+    next: DateTime<Utc>,
+}
+
+/// A player's tickets in the next draw
+struct Tickets {
+    user: UserId,
+    count: i32,
+    /// What the tickets cost, settled as one bet when they are drawn
+    spent: i32,
+}
+
+async fn get_lottery(db: &mut PgConnection, guild: GuildId) -> Result<Option<Lottery>, Error> {
+    match sqlx::query("SELECT channel, price, pot, next FROM lotteries WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => Ok(Some(Lottery {
+            channel: ChannelId::new(row.try_get::<i64, usize>(0)? as u64),
+            price: row.try_get(1)?,
+            pot: row.try_get(2)?,
+            next: row.try_get(3)?,
+        })),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+async fn get_tickets(db: &mut PgConnection, guild: GuildId) -> Result<Vec<Tickets>, Error> {
+    let rows = sqlx::query(
+        "SELECT userid, count, spent FROM lottery_tickets WHERE guildid = $1 ORDER BY userid",
+    )
+    .bind(guild.get() as i64)
+    .fetch_all(db)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(Tickets {
+                user: UserId::new(row.try_get::<i64, usize>(0)? as u64),
+                count: row.try_get(1)?,
+                spent: row.try_get(2)?,
+            })
+        })
+        .collect()
+}
+
+/// Pick which holder wins, each with a chance as big as their share of the tickets
+pub fn pick(counts: &[i32], rng: &mut impl Rng) -> Result<usize, WeightedError> {
+    Ok(WeightedIndex::new(counts)?.sample(rng))
+}
+
+/// Draw the winner of a guild's lottery, or roll the pot over if nobody bought a ticket.
+/// Returns the announcement for the lottery's channel.
+async fn draw(
+    db: &mut PgConnection,
+    guild: GuildId,
+    channel: ChannelId,
+    pot: i32,
+) -> Result<String, Error> {
+    let tickets = get_tickets(&mut *db, guild).await?;
+
+    if tickets.is_empty() {
+        return Ok(format!(
+            "Nobody bought a lottery ticket this time, so the pot of **{pot}** token(s) rolls over to the next draw!"
+        ));
+    }
+
+    let holders: Vec<(u64, i32)> = tickets
+        .iter()
+        .map(|tickets| (tickets.user.get(), tickets.count))
+        .collect();
+
+    let round = fair::Round::start(&mut *db, &fair::Draw::Lottery { holders }).await?;
+
+    let winner = {
+        let counts: Vec<i32> = tickets.iter().map(|tickets| tickets.count).collect();
+        tickets[pick(&counts, &mut round.rng())?].user
+    };
+
+    let sold: i32 = tickets.iter().map(|tickets| tickets.count).sum();
+
+    let balance = super::get_balance(winner, &mut *db).await?;
+    super::change_balance(winner, balance.saturating_add(pot), &mut *db).await?;
+
+    // everyone's tickets are settled as a single bet now that the draw has decided them
+    for tickets in &tickets {
+        let won = if tickets.user == winner { pot } else { 0 };
+        super::history::record(
+            &mut *db,
+            tickets.user,
+            channel,
+            &GAME,
+            tickets.spent,
+            won.saturating_sub(tickets.spent),
+        )
+        .await?;
+    }
+
+    sqlx::query("DELETE FROM lottery_tickets WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .execute(&mut *db)
+        .await?;
+
+    sqlx::query("UPDATE lotteries SET pot = 0 WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .execute(&mut *db)
+        .await?;
+
+    Ok(format!(
+        "\u{1F389} <@{winner}> won the lottery and takes home **{pot}** token(s)! {sold} ticket(s) were sold.\n{}",
+        round.reveal()
+    ))
+}
+
+/// Draw every lottery that is due
+async fn draw_due(ctx: &serenity::Context, db: &PgPool) -> Result<(), Error> {
+    let mut tx = db.begin().await?;
+
+    // the draw and moving the schedule along happen in one transaction, so a restart
+    // in the middle of it draws again instead of skipping the draw
+    let rows = sqlx::query(
+        "UPDATE lotteries SET next = GREATEST(next + every * INTERVAL '1 hour', now()) WHERE next <= now() RETURNING guildid, channel, pot",
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let mut announcements = vec![];
+
+    for row in rows {
+        let guild = GuildId::new(row.try_get::<i64, usize>(0)? as u64);
+        let channel = ChannelId::new(row.try_get::<i64, usize>(1)? as u64);
+        let pot: i32 = row.try_get(2)?;
+
+        announcements.push((channel, draw(&mut tx, guild, channel, pot).await?));
+    }
+
+    tx.commit().await?;
+
+    for (channel, announcement) in announcements {
+        // the channel might be gone, the draw has happened either way
+        let _ = channel
+            .send_message(
+                ctx,
+                serenity::CreateMessage::new()
+                    .content(announcement)
+                    .allowed_mentions(serenity::CreateAllowedMentions::new().all_users(true)),
+            )
+            .await;
+    }
+
+    Ok(())
+}
+
+/// Check for lottery draws that are due every minute
+pub fn run_schedule(ctx: serenity::Context, db: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = draw_due(&ctx, &db).await {
+                eprintln!("Failed to draw lotteries: {e}");
+            }
+        }
+    });
+}
+
+/// Buy tickets for this server's lottery
+#[poise::command(slash_command, prefix_command, guild_only)]
+async fn buy(
+    ctx: Context<'_>,
+    #[description = "How many tickets to buy, like 5, max or half"] count: Option<TokenAmount>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let user = ctx.author().id;
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(lottery) = get_lottery(&mut tx, guild).await? else {
+        ctx.reply("This server doesn't have a lottery.").await?;
+        return Ok(());
+    };
+
+    let balance = super::get_balance(user, &mut *tx).await?;
+
+    // max buys as many tickets as the balance covers
+    let count = match count.map(|count| count.of(balance / lottery.price)) {
+        Some(Ok(count)) => count,
+        Some(Err(e)) => {
+            ctx.reply(e.to_string()).await?;
+            return Ok(());
+        }
+        None => 1,
+    };
+
+    if count < 1 {
+        ctx.reply("You have to buy at least 1 ticket.").await?;
+        return Ok(());
+    }
+
+    let cost = lottery.price.saturating_mul(count);
+
+    if balance < cost {
+        ctx.reply(format!(
+            "You do not have enough tokens (**{balance}**) to buy {count} ticket(s) for **{cost}**."
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    if !super::limits::allowed(ctx, &mut tx, cost).await? {
+        return Ok(());
+    }
+
+    // the tickets are only recorded as a bet once they are drawn
+    super::change_balance(user, balance - cost, &mut *tx).await?;
+
+    sqlx::query("UPDATE lotteries SET pot = pot + $2 WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .bind(cost)
+        .execute(&mut *tx)
+        .await?;
+
+    let owned: i32 = sqlx::query("INSERT INTO lottery_tickets (guildid, userid, count, spent) VALUES ($1, $2, $3, $4) ON CONFLICT (guildid, userid) DO UPDATE SET count = lottery_tickets.count + EXCLUDED.count, spent = lottery_tickets.spent + EXCLUDED.spent RETURNING count")
+        .bind(guild.get() as i64)
+        .bind(user.get() as i64)
+        .bind(count)
+        .bind(cost)
+        .fetch_one(&mut *tx)
+        .await?
+        .try_get(0)?;
+
+    tx.commit().await?;
+
+    ctx.reply(format!(
+        "You bought {count} ticket(s) for **{cost}** token(s) and now have {owned} in the next draw, <t:{}:R>. The pot is now **{}**.",
+        lottery.next.timestamp(),
+        lottery.pot + cost
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// See the pot, your tickets and when the next draw is
+#[poise::command(slash_command, prefix_command, guild_only)]
+async fn info(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(lottery) = get_lottery(&mut tx, guild).await? else {
+        ctx.reply("This server doesn't have a lottery.").await?;
+        return Ok(());
+    };
+
+    let tickets = get_tickets(&mut tx, guild).await?;
+    let sold: i32 = tickets.iter().map(|tickets| tickets.count).sum();
+    let owned = tickets
+        .iter()
+        .find(|tickets| tickets.user == ctx.author().id)
+        .map(|tickets| tickets.count)
+        .unwrap_or(0);
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "**Pot**: {} token(s)\n**Ticket price**: {}\n**Tickets sold**: {sold}\n**Your tickets**: {owned}\n**Next draw**: <t:{}:R> in <#{}>",
+            lottery.pot,
+            lottery.price,
+            lottery.next.timestamp(),
+            lottery.channel
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Start this server's lottery or change how it runs, draws are announced in the given channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn setup(
+    ctx: Context<'_>,
+    channel: serenity::GuildChannel,
+    #[description = "How many tokens a ticket costs"]
+    #[min = 1]
+    price: Option<i32>,
+    #[description = "How many hours between draws (1-720)"]
+    #[min = 1]
+    #[max = 720]
+    hours: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let price = price.unwrap_or(10).max(1);
+    let hours = hours.unwrap_or(24).clamp(1, 720);
+
+    // changing the schedule of a running lottery keeps its pot and tickets
+    sqlx::query("INSERT INTO lotteries (guildid, channel, price, every, next) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (guildid) DO UPDATE SET channel = EXCLUDED.channel, price = EXCLUDED.price, every = EXCLUDED.every, next = EXCLUDED.next")
+        .bind(guild.get() as i64)
+        .bind(channel.id.get() as i64)
+        .bind(price)
+        .bind(hours)
+        .bind(Utc::now() + Duration::from_secs(hours as u64 * 60 * 60))
+        .execute(&ctx.data().database)
+        .await?;
+
+    common::no_ping_reply(
+        &ctx,
+        format!(
+            "Lottery tickets cost **{price}** token(s) and a winner is drawn every {hours} hour(s) in {channel}."
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Buy tickets for a jackpot that is drawn on a schedule
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("buy", "info", "setup"),
+    subcommand_required
+)]
+pub async fn lottery(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
@@ -0,0 +1,397 @@
+use super::{
+    amount::TokenAmount,
+    effects::{Effects, Settlement},
+    fair,
+    session::{self, Session, Status},
+};
+use crate::{
+    common::{Context, Error},
+    games::Game,
+    interactions::Route,
+};
+use poise::serenity_prelude::{self as serenity, MessageId, UserId};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The registry entry that mines' sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 451,
+    name: "Mines",
+};
+
+/// The board is a square of this many tiles on each side, as many buttons as a message can hold
+const SIZE: usize = 5;
+const TILES: usize = SIZE * SIZE;
+
+/// The share of a cash out the house keeps
+const EDGE: f64 = 0.03;
+
+/// The most a cash out can multiply the stake by, clearing a board full of mines would
+/// otherwise pay millions of times the bet
+const MAX_MULTIPLIER: f64 = 10_000.0;
+
+#[derive(Serialize, Deserialize)]
+struct Board {
+    user: UserId,
+    stake: i32,
+    mines: Vec<bool>,
+    revealed: Vec<bool>,
+    /// The message with the cash out button, the board takes up every row of its own message
+    controls: Option<MessageId>,
+    effects: Effects,
+    /// The bet's number for verifying where the mines were placed
+    #[serde(default)]
+    nonce: Option<i32>,
+}
+
+/// Every tile in the order mines are placed on them, a board with `n` mines uses the first `n`
+pub fn order(rng: &mut impl Rng) -> Vec<usize> {
+    let mut tiles: Vec<usize> = (0..TILES).collect();
+    tiles.shuffle(rng);
+    tiles
+}
+
+impl Board {
+    fn new(
+        user: UserId,
+        stake: i32,
+        mines: usize,
+        effects: Effects,
+        rng: &mut impl Rng,
+        nonce: i32,
+    ) -> Self {
+        let mut tiles = vec![false; TILES];
+
+        for i in order(rng).into_iter().take(mines) {
+            tiles[i] = true;
+        }
+
+        Self {
+            user,
+            stake,
+            mines: tiles,
+            revealed: vec![false; TILES],
+            controls: None,
+            effects,
+            nonce: Some(nonce),
+        }
+    }
+
+    fn safe_revealed(&self) -> usize {
+        self.revealed.iter().filter(|r| **r).count()
+    }
+
+    fn mine_count(&self) -> usize {
+        self.mines.iter().filter(|m| **m).count()
+    }
+
+    /// What the stake is multiplied by when cashing out after `revealed` safe tiles, the inverse of
+    /// the chance of picking that many safe tiles in a row, up to [`MAX_MULTIPLIER`]
+    fn multiplier_at(&self, revealed: usize) -> f64 {
+        if revealed == 0 {
+            return 1.0;
+        }
+
+        let safe = TILES - self.mine_count();
+        let odds: f64 = (0..revealed)
+            .map(|i| (TILES - i) as f64 / (safe - i) as f64)
+            .product();
+
+        (odds * (1.0 - EDGE)).min(MAX_MULTIPLIER)
+    }
+
+    fn multiplier(&self) -> f64 {
+        self.multiplier_at(self.safe_revealed())
+    }
+
+    fn payout(&self) -> i32 {
+        let payout = (self.stake as f64 * self.multiplier()).floor() as i64;
+        i32::try_from(payout).unwrap_or(i32::MAX)
+    }
+
+    fn cleared(&self) -> bool {
+        self.safe_revealed() + self.mine_count() == TILES
+    }
+
+    fn render(&self) -> String {
+        let mut output = format!(
+            "**Mines**: {}\n**Multiplier**: {:.2}x, cashing out pays **{}**",
+            self.mine_count(),
+            self.multiplier(),
+            self.payout()
+        );
+
+        if !self.cleared() {
+            output += &format!(
+                "\n**Next tile**: {:.2}x",
+                self.multiplier_at(self.safe_revealed() + 1)
+            );
+        }
+
+        output
+    }
+
+    /// The board as buttons, every mine is shown once the game is over
+    fn buttons(&self, route: &Route, over: bool) -> Vec<serenity::CreateActionRow> {
+        (0..SIZE)
+            .map(|row| {
+                serenity::CreateActionRow::Buttons(
+                    (row * SIZE..(row + 1) * SIZE)
+                        .map(|i| {
+                            let button = serenity::CreateButton::new(route.id(&i.to_string()));
+
+                            match (self.revealed[i], self.mines[i]) {
+                                (true, _) => button
+                                    .label("\u{1F48E}")
+                                    .style(serenity::ButtonStyle::Success)
+                                    .disabled(true),
+                                (false, true) if over => button
+                                    .label("\u{1F4A3}")
+                                    .style(serenity::ButtonStyle::Danger)
+                                    .disabled(true),
+                                _ => button
+                                    .label("?")
+                                    .style(serenity::ButtonStyle::Secondary)
+                                    .disabled(over),
+                            }
+                        })
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Reveal tiles until the player cashes out, hits a mine or clears the board.
+/// Walking away for two minutes cashes out. Returns whether the player hit a mine.
+async fn run(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: &Session,
+    board: &mut Board,
+) -> Result<bool, Error> {
+    let mut route = Route::new(&GAME.key(), session.id);
+    route.restrict(vec![board.user]);
+
+    // the cash out button might not have been sent before a restart
+    if board.controls.is_none() {
+        let controls = session
+            .channel
+            .send_message(
+                ctx,
+                serenity::CreateMessage::new()
+                    .content("Cash out whenever you like.")
+                    .components(vec![serenity::CreateActionRow::Buttons(vec![
+                        serenity::CreateButton::new(route.id("cashout"))
+                            .label("Cash Out")
+                            .style(serenity::ButtonStyle::Success),
+                    ])]),
+            )
+            .await?;
+
+        board.controls = Some(controls.id);
+
+        let mut tx = db.begin().await?;
+        session.save(&mut tx, board).await?;
+        tx.commit().await?;
+    }
+
+    while !board.cleared() {
+        session
+            .channel
+            .edit_message(
+                ctx,
+                session.message,
+                serenity::EditMessage::new()
+                    .content(board.render())
+                    .components(board.buttons(&route, false)),
+            )
+            .await?;
+
+        let Some((mci, action)) = route.next(Instant::now() + Duration::from_secs(120)).await
+        else {
+            break;
+        };
+
+        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        let Ok(tile) = action.parse::<usize>() else {
+            break;
+        };
+
+        if tile >= TILES || board.revealed[tile] {
+            continue;
+        }
+
+        if board.mines[tile] {
+            return Ok(true);
+        }
+
+        board.revealed[tile] = true;
+
+        let mut tx = db.begin().await?;
+        session.save(&mut tx, board).await?;
+        tx.commit().await?;
+    }
+
+    Ok(false)
+}
+
+/// Pay the player out of escrow, end the session and show the whole board
+async fn finish(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: &Session,
+    mut board: Board,
+    hit: bool,
+) -> Result<(), Error> {
+    let mut settlement = if hit {
+        Settlement::loss(board.stake)
+    } else {
+        Settlement::net(board.stake, board.payout() - board.stake)
+    };
+
+    board.effects.settle(&mut settlement);
+
+    let mut tx = db.begin().await?;
+    let held = session.release(&mut tx, board.user).await?;
+    let balance = super::get_balance(board.user, &mut *tx)
+        .await?
+        .saturating_add(held)
+        .saturating_add(settlement.net);
+    super::change_balance(board.user, balance, &mut *tx).await?;
+    std::mem::take(&mut board.effects).finish(&mut tx).await?;
+    super::history::record(
+        &mut tx,
+        board.user,
+        session.channel,
+        &GAME,
+        board.stake,
+        settlement.net,
+    )
+    .await?;
+    session.end(&mut tx, Status::Finished).await?;
+    tx.commit().await?;
+
+    if let Some(controls) = board.controls {
+        // someone might have deleted it already
+        let _ = session.channel.delete_message(ctx, controls).await;
+    }
+
+    let result = if hit {
+        format!(
+            "\u{1F4A5} You hit a mine and lost **{}** token(s). You now have **{balance}**.",
+            -settlement.net
+        )
+    } else {
+        format!(
+            "You cashed out at **{:.2}x** for **{}** token(s). You now have **{balance}**.",
+            board.multiplier(),
+            board.payout()
+        )
+    };
+
+    let footer = board
+        .nonce
+        .map(|nonce| format!("\n{}", fair::footer(nonce)))
+        .unwrap_or_default();
+
+    // the route is only needed for the ids of the disabled buttons
+    let route = Route::new(&GAME.key(), session.id);
+
+    session
+        .channel
+        .edit_message(
+            ctx,
+            session.message,
+            serenity::EditMessage::new()
+                .content(format!("{}\n\n{result}{footer}", board.render()))
+                .components(board.buttons(&route, true)),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn play_out(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: Session,
+    mut board: Board,
+) -> Result<(), Error> {
+    session
+        .refund_on_error(db, async {
+            let hit = run(ctx, db, &session, &mut board).await?;
+            finish(ctx, db, &session, board, hit).await
+        })
+        .await
+}
+
+/// Pick a saved game back up, handing the session back if its state can't be read
+pub fn resume(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    session: Session,
+    state: serde_json::Value,
+) -> Result<(), Session> {
+    let Some(board) = session::state::<Board>(state) else {
+        return Err(session);
+    };
+
+    let ctx = ctx.clone();
+    let db = db.clone();
+
+    tokio::spawn(async move {
+        if let Err(e) = play_out(&ctx, &db, session, board).await {
+            eprintln!("Failed to resume a game of mines: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+/// Uncover tiles without hitting a mine, every safe tile raises the payout
+#[poise::command(slash_command, prefix_command)]
+pub async fn mines(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[description = "How many mines are hidden on the board (1-24)"]
+    #[min = 1]
+    #[max = 24]
+    mines: usize,
+    #[autocomplete = "super::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    if !(1..TILES).contains(&mines) {
+        ctx.reply(format!("There can be between 1 and {} mines.", TILES - 1))
+            .await?;
+        return Ok(());
+    }
+
+    let db = &ctx.data().database;
+    let mut tx = db.begin().await?;
+    let Some(super::Stake {
+        amount, effects, ..
+    }) = super::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
+        return Ok(());
+    };
+
+    let (mut rng, nonce) =
+        fair::next(&mut tx, ctx.author().id, &fair::Draw::Mines { mines }).await?;
+    let board = Board::new(ctx.author().id, amount, mines, effects, &mut rng, nonce);
+
+    let msg = ctx.reply("Just a second...").await?;
+    let message = msg.message().await?.id;
+
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), message, &board).await?;
+    session.hold(&mut tx, ctx.author().id, amount).await?;
+    tx.commit().await?;
+
+    play_out(ctx.serenity_context(), db, session, board).await
+}
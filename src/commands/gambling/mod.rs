@@ -1,23 +1,95 @@
+pub mod achievements;
+pub mod amount;
 pub mod balance;
 pub mod blackjack;
+pub mod cards;
+pub mod craft;
+pub mod crash;
 pub mod daily;
+pub mod dice;
+pub mod duel;
+pub mod effects;
+pub mod fair;
 pub mod give;
+pub mod history;
 pub mod leaderboard;
+pub mod limits;
+pub mod loot;
+pub mod lottery;
+pub mod mines;
+pub mod odds;
+pub mod poker;
+pub mod race;
+pub mod roulette;
+pub mod session;
 pub mod shop;
+pub mod slots;
+pub mod stats;
 pub mod wager;
 
 use crate::{
     common::{Context, Error},
+    games::Game,
     inventory::{self, Inventory},
 };
+use amount::TokenAmount;
 use poise::serenity_prelude::{self as serenity, futures::StreamExt, UserId};
-use sqlx::{PgExecutor, Row};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgExecutor, Row};
+use std::{collections::HashMap, fmt::Display};
 
-#[derive(Clone)]
+/// What an item or buff does. The generic effects are applied by every game through
+/// the hooks in [`effects::Hook`], game specific ones are checked by the game itself.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Effect {
     Multiplier(f64),
     Chance(f64),
+    RefundOnLoss(f64),
+    BonusOnWin(i32),
+    /// Blackjack: reveals the dealer's hole card
+    Peek,
+    /// Blackjack: takes back the card that made you bust, once
+    BustForgiveness,
+}
+
+impl Effect {
+    /// Whether a game makes use of the effect. The payout effects work in every game, the
+    /// rest only in the games that check for them.
+    pub fn works_in(&self, game: &Game) -> bool {
+        match self {
+            Self::Chance(_) => game.id == wager::GAME.id,
+            Self::Peek | Self::BustForgiveness => game.id == blackjack::GAME.id,
+            Self::Multiplier(_) | Self::RefundOnLoss(_) | Self::BonusOnWin(_) => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl Display for Rarity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Common => "Common",
+                Self::Uncommon => "Uncommon",
+                Self::Rare => "Rare",
+                Self::Epic => "Epic",
+                Self::Legendary => "Legendary",
+            }
+        )
+    }
 }
 
 #[derive(Clone)]
@@ -25,6 +97,7 @@ pub struct Item {
     pub name: &'static str,
     pub desc: &'static str,
     pub effects: &'static [Effect],
+    pub rarity: Rarity,
     pub id: u64,
 }
 
@@ -42,32 +115,143 @@ impl Item {
 
 const ID: u64 = 440;
 
+pub const GAME: Game = Game {
+    id: ID,
+    name: "Gambling",
+};
+
 mod items {
-    use super::{Effect, Item};
+    use super::{Effect, Item, Rarity};
 
     pub const DIRT: Item = Item {
         name: "Pile of Dirt",
-        desc: "Returns a 1.01x multiplier on all earnings",
+        desc: "Returns a 1.01x multiplier on up to 250 tokens of earnings",
         effects: &[Effect::Multiplier(1.01)],
+        rarity: Rarity::Common,
         id: id::DIRT,
     };
 
     pub const SAND: Item = Item {
         name: "Pile of Sand",
-        desc: "Increase your odds of winning by 1%",
-        effects: &[Effect::Chance(0.51)],
+        desc: "Adds 1% to your chance of winning a wager, less on bets over 250 tokens",
+        effects: &[Effect::Chance(0.01)],
+        rarity: Rarity::Common,
         id: id::SAND,
     };
 
+    pub const WOODEN_CRATE: Item = Item {
+        name: "Wooden Crate",
+        desc: "Open it with /open to see what's inside",
+        effects: &[],
+        rarity: Rarity::Common,
+        id: id::WOODEN_CRATE,
+    };
+
+    pub const GOLDEN_CRATE: Item = Item {
+        name: "Golden Crate",
+        desc: "Open it with /open to see what's inside",
+        effects: &[],
+        rarity: Rarity::Rare,
+        id: id::GOLDEN_CRATE,
+    };
+
+    pub const FOUR_LEAF_CLOVER: Item = Item {
+        name: "Four Leaf Clover",
+        desc: "Adds 5% to your chance of winning a wager, less on bets over 250 tokens",
+        effects: &[Effect::Chance(0.05)],
+        rarity: Rarity::Rare,
+        id: id::FOUR_LEAF_CLOVER,
+    };
+
+    pub const LUCKY_COIN: Item = Item {
+        name: "Lucky Coin",
+        desc: "Returns a 1.25x multiplier on up to 250 tokens of earnings",
+        effects: &[Effect::Multiplier(1.25)],
+        rarity: Rarity::Epic,
+        id: id::LUCKY_COIN,
+    };
+
+    pub const GLASS: Item = Item {
+        name: "Pane of Glass",
+        desc: "Returns a 1.05x multiplier on up to 250 tokens of earnings and adds 3% to your chance of winning a wager, less on bets over 250 tokens",
+        effects: &[Effect::Multiplier(1.05), Effect::Chance(0.03)],
+        rarity: Rarity::Uncommon,
+        id: id::GLASS,
+    };
+
+    pub const MAGNIFYING_GLASS: Item = Item {
+        name: "Magnifying Glass",
+        desc: "Lets you see the dealer's hidden card in blackjack",
+        effects: &[Effect::Peek],
+        rarity: Rarity::Uncommon,
+        id: id::MAGNIFYING_GLASS,
+    };
+
+    pub const HORSESHOE: Item = Item {
+        name: "Horseshoe",
+        desc: "Forgives your first bust in blackjack",
+        effects: &[Effect::BustForgiveness],
+        rarity: Rarity::Rare,
+        id: id::HORSESHOE,
+    };
+
+    pub const GOLD_CHIP: Item = Item {
+        name: "Gold Chip",
+        desc: "Returns a 1.5x multiplier on up to 250 tokens of earnings",
+        effects: &[Effect::Multiplier(1.5)],
+        rarity: Rarity::Epic,
+        id: id::GOLD_CHIP,
+    };
+
+    pub const SAFETY_NET: Item = Item {
+        name: "Safety Net",
+        desc: "Refunds half of your bet, up to 25 tokens, if you lose",
+        effects: &[Effect::RefundOnLoss(0.5)],
+        rarity: Rarity::Uncommon,
+        id: id::SAFETY_NET,
+    };
+
     mod id {
         pub const DIRT: u64 = 1;
         pub const SAND: u64 = 2;
+        pub const WOODEN_CRATE: u64 = 3;
+        pub const GOLDEN_CRATE: u64 = 4;
+        pub const FOUR_LEAF_CLOVER: u64 = 5;
+        pub const LUCKY_COIN: u64 = 6;
+        pub const GLASS: u64 = 7;
+        pub const MAGNIFYING_GLASS: u64 = 8;
+        pub const HORSESHOE: u64 = 9;
+        pub const GOLD_CHIP: u64 = 10;
+        pub const SAFETY_NET: u64 = 11;
     }
 
+    pub const ALL: [&Item; 11] = [
+        &DIRT,
+        &SAND,
+        &WOODEN_CRATE,
+        &GOLDEN_CRATE,
+        &FOUR_LEAF_CLOVER,
+        &LUCKY_COIN,
+        &GLASS,
+        &MAGNIFYING_GLASS,
+        &HORSESHOE,
+        &GOLD_CHIP,
+        &SAFETY_NET,
+    ];
+
     pub fn get_item_by_id(id: u64) -> Option<&'static Item> {
         match id {
             id::DIRT => Some(&DIRT),
             id::SAND => Some(&SAND),
+            id::WOODEN_CRATE => Some(&WOODEN_CRATE),
+            id::GOLDEN_CRATE => Some(&GOLDEN_CRATE),
+            id::FOUR_LEAF_CLOVER => Some(&FOUR_LEAF_CLOVER),
+            id::LUCKY_COIN => Some(&LUCKY_COIN),
+            id::GLASS => Some(&GLASS),
+            id::MAGNIFYING_GLASS => Some(&MAGNIFYING_GLASS),
+            id::HORSESHOE => Some(&HORSESHOE),
+            id::GOLD_CHIP => Some(&GOLD_CHIP),
+            id::SAFETY_NET => Some(&SAFETY_NET),
             _ => None,
         }
     }
@@ -76,11 +260,56 @@ mod items {
         match name {
             "Pile of Dirt" => Some(&DIRT),
             "Pile of Sand" => Some(&SAND),
+            "Wooden Crate" => Some(&WOODEN_CRATE),
+            "Golden Crate" => Some(&GOLDEN_CRATE),
+            "Four Leaf Clover" => Some(&FOUR_LEAF_CLOVER),
+            "Lucky Coin" => Some(&LUCKY_COIN),
+            "Pane of Glass" => Some(&GLASS),
+            "Magnifying Glass" => Some(&MAGNIFYING_GLASS),
+            "Horseshoe" => Some(&HORSESHOE),
+            "Gold Chip" => Some(&GOLD_CHIP),
+            "Safety Net" => Some(&SAFETY_NET),
             _ => None,
         }
     }
 }
 
+/// The most a game pays back on average, as a percent of the bet, when played with the effects
+/// of whichever item does the most in it along with every buff a crate gives. `odds` gives the
+/// game's odds with a chance of winning added to them. Servers' odds are checked with this so
+/// that no item can push them past the most allowed.
+pub fn best_return(game: &Game, odds: impl Fn(f64) -> effects::Odds) -> f64 {
+    let mut buffs: Vec<&Effect> = vec![];
+
+    for buff in loot::buffs() {
+        if !buffs.contains(&buff) {
+            buffs.push(buff);
+        }
+    }
+
+    items::ALL
+        .iter()
+        .map(|item| {
+            let effects: Vec<&Effect> = item
+                .effects
+                .iter()
+                .chain(buffs.iter().copied())
+                .filter(|effect| effect.works_in(game))
+                .collect();
+
+            let chance = effects
+                .iter()
+                .map(|effect| match effect {
+                    Effect::Chance(c) => *c,
+                    _ => 0.0,
+                })
+                .sum();
+
+            odds(chance).with(&effects) * 100.0
+        })
+        .fold(0.0, f64::max)
+}
+
 pub async fn get_balance<'a, E>(id: UserId, db: E) -> Result<i32, Error>
 where
     E: PgExecutor<'a>,
@@ -112,6 +341,85 @@ where
     Ok(())
 }
 
+/// Take one of the named item out of the author's inventory so its effects can be used in
+/// `game`. Replies to the user and returns `None` if the item doesn't exist, does nothing in
+/// the game or they don't own one.
+async fn use_item(
+    ctx: Context<'_>,
+    db: &mut PgConnection,
+    game: &Game,
+    name: &str,
+) -> Result<Option<&'static Item>, Error> {
+    let Some(item) = items::get_item_by_name(name) else {
+        ctx.reply(format!("item {name} does not exist.")).await?;
+        return Ok(None);
+    };
+
+    if !item.effects.iter().any(|effect| effect.works_in(game)) {
+        ctx.reply(format!("A(n) {} does nothing in {}.", item.name, game.name))
+            .await?;
+        return Ok(None);
+    }
+
+    let inventory = Inventory::new(ctx.author().id, Some(ID));
+
+    match inventory.get_item_of_type(&mut *db, item.id).await? {
+        Some(owned) => {
+            inventory.remove_item(&mut *db, owned.id).await?;
+            Ok(Some(item))
+        }
+        None => {
+            ctx.reply(format!("You do not have a(n) {} to use.", item.name))
+                .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// A bet that is ready to be played: the amount, checked against the player's balance,
+/// and the effects of the item they used along with their buffs
+pub struct Stake {
+    pub amount: i32,
+    pub balance: i32,
+    pub effects: effects::Effects,
+}
+
+/// Work out the amount the author wants to bet on `game` and use the item they bet with.
+/// Replies to the user and returns `None` if either isn't valid.
+pub async fn stake(
+    ctx: Context<'_>,
+    db: &mut PgConnection,
+    game: &Game,
+    amount: &TokenAmount,
+    item: Option<&str>,
+) -> Result<Option<Stake>, Error> {
+    let balance = get_balance(ctx.author().id, &mut *db).await?;
+
+    let Some(amount) = amount.resolve(ctx, balance).await? else {
+        return Ok(None);
+    };
+
+    if !limits::allowed(ctx, &mut *db, amount).await? {
+        return Ok(None);
+    }
+
+    let item = match item {
+        Some(item) => match use_item(ctx, &mut *db, game, item).await? {
+            Some(item) => Some(item),
+            None => return Ok(None),
+        },
+        None => None,
+    };
+
+    let effects = effects::Effects::load(&mut *db, ctx.author().id, item).await?;
+
+    Ok(Some(Stake {
+        amount,
+        balance,
+        effects,
+    }))
+}
+
 async fn autocomplete_inventory<'a>(
     ctx: Context<'a>,
     partial: &'a str,
@@ -0,0 +1,46 @@
+use super::{crash, slots};
+use crate::{
+    commands::settings,
+    common::{self, Context, Error},
+};
+
+/// See the odds of this server's games and how much of every bet they pay back on average
+#[poise::command(slash_command, prefix_command)]
+pub async fn odds(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let guild = ctx.guild_id();
+
+    let (chance, payout) = settings::get_wager_odds(db, guild).await?;
+    let rules = settings::get_blackjack_rules(db, guild).await?;
+    let (slots, best_slots) = slots::expected_return(db, guild).await?;
+
+    let mut output = format!(
+        "**Wager**: {chance}% chance to win, pays {payout}% of the bet, returns {:.2}% or up to {:.2}% with items\n**Blackjack**: {}, returns about {:.2}% or up to {:.2}% with items\n**Slots**: returns {slots:.2}% or up to {best_slots:.2}% with items\n",
+        settings::wager_return(chance, payout),
+        settings::best_wager_return(chance, payout),
+        rules.describe(),
+        rules.expected_return(),
+        rules.best_return()
+    );
+
+    if let Some(guild) = guild {
+        let crash = settings::get_crash_edge(db, guild).await?;
+        let race = settings::get_race_cut(db, guild).await?;
+        let duel = settings::get_duel_cut(db, guild).await?;
+
+        output += &format!(
+            "**Crash**: {crash}% house edge, returns {}% or up to {:.2}% with items\n**Races**: the house keeps {race}% of the pool\n**Duels**: the house keeps {duel}% of the pot\n",
+            100 - crash,
+            crash::best_return(crash)
+        );
+    }
+
+    output += &format!(
+        "\nServers can set their games up to return at most {}% of every bet.",
+        ctx.data().max_return
+    );
+
+    common::no_ping_reply(&ctx, output).await?;
+
+    Ok(())
+}
@@ -0,0 +1,484 @@
+use super::hand;
+use crate::commands::gambling::{
+    cards::{show, Card, Shoe},
+    fair::Round,
+};
+
+use poise::serenity_prelude::UserId;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+pub struct Player {
+    pub user: UserId,
+    pub stack: i32,
+    /// What the player sat down with
+    pub bought: i32,
+    cards: Vec<Card>,
+    /// Chips put in during the current betting round
+    bet: i32,
+    /// Chips put in during the whole hand
+    committed: i32,
+    folded: bool,
+    /// Whether the player has acted since the last raise
+    acted: bool,
+    /// The player is cashed out once the hand is over
+    pub leaving: bool,
+    /// Decisions in a row the player let run out
+    pub timeouts: u8,
+}
+
+impl Player {
+    pub fn new(user: UserId, stack: i32) -> Self {
+        Self {
+            user,
+            stack,
+            bought: stack,
+            cards: vec![],
+            bet: 0,
+            committed: 0,
+            folded: false,
+            acted: false,
+            leaving: false,
+            timeouts: 0,
+        }
+    }
+
+    /// What the player had in front of them when the hand started
+    pub fn buy_in(&self) -> i32 {
+        self.stack + self.committed
+    }
+
+    pub fn cards(&self) -> String {
+        show(&self.cards)
+    }
+
+    fn can_act(&self) -> bool {
+        !self.folded && self.stack > 0
+    }
+
+    fn put(&mut self, amount: i32) {
+        let amount = amount.min(self.stack);
+        self.stack -= amount;
+        self.bet += amount;
+        self.committed += amount;
+    }
+}
+
+pub enum Action {
+    Fold,
+    Check,
+    Call,
+    /// Raise the bet to this total for the round
+    Raise(i32),
+    AllIn,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Table {
+    pub players: Vec<Player>,
+    button: usize,
+    pub big_blind: i32,
+    deck: Shoe,
+    board: Vec<Card>,
+    /// The highest bet in the current betting round
+    current: i32,
+    /// The least a raise has to add on top of the current bet
+    min_raise: i32,
+    /// The player whose turn it is
+    pub turn: usize,
+    /// What happened last, shown above the table
+    pub last: String,
+    /// The seed the current hand was shuffled with, revealed once the hand is over
+    #[serde(default)]
+    round: Option<Round>,
+}
+
+impl Table {
+    pub fn new(players: Vec<Player>, big_blind: i32) -> Self {
+        Self {
+            players,
+            button: 0,
+            big_blind,
+            deck: Shoe::new(1),
+            board: vec![],
+            current: 0,
+            min_raise: big_blind,
+            turn: 0,
+            last: String::new(),
+            round: None,
+        }
+    }
+
+    fn small_blind(&self) -> i32 {
+        (self.big_blind / 2).max(1)
+    }
+
+    /// The first player after seat `from` that matches, going around the table once
+    fn next_from(&self, from: usize, f: impl Fn(&Player) -> bool) -> Option<usize> {
+        let n = self.players.len();
+
+        (1..=n)
+            .map(|offset| (from + offset) % n)
+            .find(|i| f(&self.players[*i]))
+    }
+
+    fn needs_action(&self, player: &Player) -> bool {
+        player.can_act() && (!player.acted || player.bet < self.current)
+    }
+
+    pub fn in_hand(&self) -> usize {
+        self.players.iter().filter(|p| !p.folded).count()
+    }
+
+    pub fn board_full(&self) -> bool {
+        self.board.len() == 5
+    }
+
+    /// Shuffle up with the round's seed, deal two cards to everyone and post the blinds
+    pub fn deal(&mut self, round: Round) {
+        self.deck = Shoe::shuffled(1, &mut round.rng());
+        self.round = Some(round);
+        self.board.clear();
+
+        for player in &mut self.players {
+            player.cards = vec![self.deck.draw(), self.deck.draw()];
+            player.bet = 0;
+            player.committed = 0;
+            player.folded = false;
+            player.acted = false;
+        }
+
+        let n = self.players.len();
+        self.button %= n;
+
+        // heads up the button posts the small blind and acts first before the flop
+        let (small, big) = if n == 2 {
+            (self.button, (self.button + 1) % n)
+        } else {
+            ((self.button + 1) % n, (self.button + 2) % n)
+        };
+
+        let (sb, bb) = (self.small_blind(), self.big_blind);
+        self.players[small].put(sb);
+        self.players[big].put(bb);
+
+        self.current = bb;
+        self.min_raise = bb;
+        self.turn = self.next_from(big, Player::can_act).unwrap_or(big);
+        self.last = format!(
+            "<@{}> posts the small blind of {sb} and <@{}> posts the big blind of {bb}.",
+            self.players[small].user, self.players[big].user
+        );
+    }
+
+    /// Whether the betting round is over, either because everyone has matched the bet or
+    /// because there is nobody left to bet against
+    pub fn round_over(&self) -> bool {
+        if self.in_hand() <= 1 {
+            return true;
+        }
+
+        let mut active = self.players.iter().filter(|p| p.can_act());
+
+        if active.clone().count() <= 1 {
+            return active.all(|p| p.bet >= self.current);
+        }
+
+        !self.players.iter().any(|p| self.needs_action(p))
+    }
+
+    fn advance(&mut self) {
+        if let Some(next) = self.next_from(self.turn, |p| self.needs_action(p)) {
+            self.turn = next;
+        }
+    }
+
+    pub fn can_check(&self) -> bool {
+        self.players[self.turn].bet >= self.current
+    }
+
+    /// How much the player whose turn it is has to put in to call
+    pub fn to_call(&self) -> i32 {
+        let player = &self.players[self.turn];
+        (self.current - player.bet).min(player.stack)
+    }
+
+    /// Everything the player whose turn it is could bet this round
+    pub fn all_in(&self) -> i32 {
+        let player = &self.players[self.turn];
+        player.bet + player.stack
+    }
+
+    pub fn apply(&mut self, action: Action) -> Result<(), String> {
+        let i = self.turn;
+        let user = self.players[i].user;
+
+        let to = match action {
+            Action::Fold => {
+                self.players[i].folded = true;
+                self.last = format!("<@{user}> folds.");
+                None
+            }
+            Action::Check if self.can_check() => {
+                self.last = format!("<@{user}> checks.");
+                None
+            }
+            Action::Check => return Err("You cannot check while facing a bet.".to_string()),
+            Action::Call if self.can_check() => {
+                return Err("There is nothing to call, check instead.".to_string());
+            }
+            Action::Call => {
+                let amount = self.to_call();
+                self.players[i].put(amount);
+                self.last = format!("<@{user}> calls {amount}.");
+                None
+            }
+            Action::Raise(to) if to >= self.all_in() => Some(self.all_in()),
+            Action::Raise(to) if to < self.current + self.min_raise => {
+                return Err(format!(
+                    "You have to raise to at least {}.",
+                    self.current + self.min_raise
+                ));
+            }
+            Action::Raise(to) => Some(to),
+            Action::AllIn => Some(self.all_in()),
+        };
+
+        if let Some(to) = to {
+            let player = &mut self.players[i];
+            player.put(to - player.bet);
+
+            if to > self.current {
+                // a short all in doesn't change how much the next raise has to be
+                self.min_raise = self.min_raise.max(to - self.current);
+                self.current = to;
+
+                for (j, other) in self.players.iter_mut().enumerate() {
+                    if j != i {
+                        other.acted = false;
+                    }
+                }
+            }
+
+            self.last = if self.players[i].stack == 0 {
+                format!("<@{user}> is all in for {to}.")
+            } else {
+                format!("<@{user}> raises to {to}.")
+            };
+        }
+
+        self.players[i].acted = true;
+        self.advance();
+
+        Ok(())
+    }
+
+    /// Fold a player's hand out of turn, when they leave the table
+    pub fn fold(&mut self, i: usize) {
+        if self.players[i].folded {
+            return;
+        }
+
+        self.players[i].folded = true;
+
+        if self.turn == i {
+            self.advance();
+        }
+    }
+
+    /// Gather the bets and deal the next street
+    pub fn next_street(&mut self) {
+        for player in &mut self.players {
+            player.bet = 0;
+            player.acted = false;
+        }
+
+        self.current = 0;
+        self.min_raise = self.big_blind;
+
+        let count = if self.board.is_empty() { 3 } else { 1 };
+
+        for _ in 0..count {
+            self.board.push(self.deck.draw());
+        }
+
+        self.turn = self
+            .next_from(self.button, Player::can_act)
+            .unwrap_or(self.button);
+    }
+
+    fn pot(&self) -> i32 {
+        self.players.iter().map(|p| p.committed).sum()
+    }
+
+    /// Split the chips into the main pot and side pots, along with the players that can win each.
+    /// A player can only win as much from each opponent as they put in themselves.
+    fn pots(&self) -> Vec<(i32, Vec<usize>)> {
+        let mut levels: Vec<i32> = self
+            .players
+            .iter()
+            .filter(|p| !p.folded)
+            .map(|p| p.committed)
+            .collect();
+        levels.sort_unstable();
+        levels.dedup();
+
+        let mut pots = vec![];
+        let mut previous = 0;
+
+        for (k, level) in levels.iter().enumerate() {
+            // the last pot also sweeps up anything folded players put in above it
+            let last = k == levels.len() - 1;
+
+            let amount: i32 = self
+                .players
+                .iter()
+                .map(|p| {
+                    let top = if last {
+                        p.committed
+                    } else {
+                        p.committed.min(*level)
+                    };
+
+                    top - p.committed.min(previous)
+                })
+                .sum();
+
+            let eligible: Vec<usize> = (0..self.players.len())
+                .filter(|i| !self.players[*i].folded && self.players[*i].committed >= *level)
+                .collect();
+
+            if amount > 0 {
+                pots.push((amount, eligible));
+            }
+
+            previous = *level;
+        }
+
+        pots
+    }
+
+    /// Pay out the hand and move the button, returns what happened
+    pub fn settle(&mut self) -> Vec<String> {
+        let n = self.players.len();
+        let mut won = vec![0; n];
+        let mut lines = vec![];
+
+        if self.in_hand() == 1 {
+            let winner = self.players.iter().position(|p| !p.folded).unwrap();
+            won[winner] = self.pot();
+        } else {
+            let scores: Vec<Option<hand::Score>> = self
+                .players
+                .iter()
+                .map(|p| {
+                    if p.folded {
+                        return None;
+                    }
+
+                    let mut cards = p.cards.clone();
+                    cards.extend(self.board.iter().cloned());
+                    hand::best(&cards)
+                })
+                .collect();
+
+            for (i, player) in self.players.iter().enumerate() {
+                if let Some(score) = &scores[i] {
+                    lines.push(format!(
+                        "<@{}> shows {} for a {}.",
+                        player.user,
+                        show(&player.cards),
+                        score.category
+                    ));
+                }
+            }
+
+            for (amount, eligible) in self.pots() {
+                let best = eligible.iter().filter_map(|i| scores[*i].as_ref()).max();
+
+                // the odd chips of a split pot go to the first winner after the button
+                let mut winners: Vec<usize> = eligible
+                    .into_iter()
+                    .filter(|i| scores[*i].as_ref() == best)
+                    .collect();
+                winners.sort_by_key(|i| (i + n - self.button - 1) % n);
+
+                let share = amount / winners.len() as i32;
+                let odd = amount % winners.len() as i32;
+
+                for (k, i) in winners.iter().enumerate() {
+                    won[*i] += share + if (k as i32) < odd { 1 } else { 0 };
+                }
+            }
+        }
+
+        for (i, player) in self.players.iter_mut().enumerate() {
+            player.stack += won[i];
+
+            if won[i] > 0 {
+                lines.push(format!("<@{}> wins **{}**.", player.user, won[i]));
+            }
+        }
+
+        self.button = (self.button + 1) % n;
+
+        if let Some(round) = self.round.take() {
+            lines.push(round.reveal());
+        }
+
+        lines
+    }
+
+    pub fn render(&self) -> String {
+        let mut output = format!(
+            "**Blinds**: {}/{}\n**Board**: {}\n**Pot**: {}\n\n",
+            self.small_blind(),
+            self.big_blind,
+            if self.board.is_empty() {
+                "No cards yet".to_string()
+            } else {
+                show(&self.board)
+            },
+            self.pot()
+        );
+
+        for (i, player) in self.players.iter().enumerate() {
+            let marker = if i == self.turn && !self.round_over() {
+                "\u{25B6} "
+            } else {
+                ""
+            };
+
+            let dealer = if i == self.button % self.players.len() {
+                " (dealer)"
+            } else {
+                ""
+            };
+
+            let status = if player.folded {
+                ", folded".to_string()
+            } else if player.stack == 0 && player.committed > 0 {
+                ", all in".to_string()
+            } else if player.bet > 0 {
+                format!(", bet {}", player.bet)
+            } else {
+                String::new()
+            };
+
+            output += &format!(
+                "- {marker}<@{}>{dealer}: {} chips{status}\n",
+                player.user, player.stack
+            );
+        }
+
+        if !self.last.is_empty() {
+            output += &format!("\n{}", self.last);
+        }
+
+        if let Some(round) = &self.round {
+            output += &format!("\n{}", round.commitment());
+        }
+
+        output
+    }
+}
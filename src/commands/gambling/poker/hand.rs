@@ -0,0 +1,179 @@
+use crate::commands::gambling::cards::Card;
+
+use std::fmt::Display;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Category {
+    HighCard,
+    Pair,
+    TwoPair,
+    Trips,
+    Straight,
+    Flush,
+    FullHouse,
+    Quads,
+    StraightFlush,
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::HighCard => "High Card",
+                Self::Pair => "Pair",
+                Self::TwoPair => "Two Pair",
+                Self::Trips => "Three of a Kind",
+                Self::Straight => "Straight",
+                Self::Flush => "Flush",
+                Self::FullHouse => "Full House",
+                Self::Quads => "Four of a Kind",
+                Self::StraightFlush => "Straight Flush",
+            }
+        )
+    }
+}
+
+/// How strong a five card hand is, a higher score beats a lower one and equal scores split the pot
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub struct Score {
+    pub category: Category,
+    /// The ranks that break ties within the category, most important first
+    ranks: Vec<u8>,
+}
+
+fn score(cards: &[&Card]) -> Score {
+    let mut ranks: Vec<u8> = cards.iter().map(|card| card.rank.order()).collect();
+    ranks.sort_unstable_by(|a, b| b.cmp(a));
+
+    let flush = cards.iter().all(|card| card.suite == cards[0].suite);
+
+    let mut unique = ranks.clone();
+    unique.dedup();
+
+    let straight = match unique.as_slice() {
+        // the wheel, where the ace plays low
+        [14, 5, 4, 3, 2] => Some(5),
+        [high, .., low] if unique.len() == 5 && high - low == 4 => Some(*high),
+        _ => None,
+    };
+
+    // ranks grouped by how often they appear, bigger groups first and then higher ranks
+    let mut groups: Vec<(usize, u8)> = unique
+        .iter()
+        .map(|rank| (ranks.iter().filter(|r| *r == rank).count(), *rank))
+        .collect();
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+
+    let counts: Vec<usize> = groups.iter().map(|(count, _)| *count).collect();
+    let grouped: Vec<u8> = groups.iter().map(|(_, rank)| *rank).collect();
+
+    let (category, ranks) = match (straight, flush, counts.as_slice()) {
+        (Some(high), true, _) => (Category::StraightFlush, vec![high]),
+        (_, _, [4, 1]) => (Category::Quads, grouped),
+        (_, _, [3, 2]) => (Category::FullHouse, grouped),
+        (_, true, _) => (Category::Flush, ranks),
+        (Some(high), _, _) => (Category::Straight, vec![high]),
+        (_, _, [3, ..]) => (Category::Trips, grouped),
+        (_, _, [2, 2, 1]) => (Category::TwoPair, grouped),
+        (_, _, [2, ..]) => (Category::Pair, grouped),
+        _ => (Category::HighCard, ranks),
+    };
+
+    Score { category, ranks }
+}
+
+/// The best five card hand that can be made out of a player's hole cards and the board
+pub fn best(cards: &[Card]) -> Option<Score> {
+    if cards.len() < 5 {
+        return None;
+    }
+
+    (0u32..1 << cards.len())
+        .filter(|mask| mask.count_ones() == 5)
+        .map(|mask| {
+            let hand: Vec<&Card> = cards
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, card)| card)
+                .collect();
+
+            score(&hand)
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{best, Category};
+    use crate::commands::gambling::cards::{Card, Rank, Suite};
+
+    /// Cards written like `Ah 10s 2c`
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace()
+            .map(|card| {
+                let (rank, suite) = card.split_at(card.len() - 1);
+
+                let rank = match rank {
+                    "J" => Rank::Jack,
+                    "Q" => Rank::Queen,
+                    "K" => Rank::King,
+                    "A" => Rank::Ace,
+                    n => Rank::Pip(n.parse().unwrap()),
+                };
+
+                let suite = match suite {
+                    "h" => Suite::Hearts,
+                    "d" => Suite::Diamonds,
+                    "c" => Suite::Clubs,
+                    _ => Suite::Spades,
+                };
+
+                Card::new(suite, rank)
+            })
+            .collect()
+    }
+
+    fn category(s: &str) -> Category {
+        best(&cards(s)).unwrap().category
+    }
+
+    #[test]
+    fn categories() {
+        assert!(category("Ah Kd 9c 7s 2h") == Category::HighCard);
+        assert!(category("Ah Ad 9c 7s 2h") == Category::Pair);
+        assert!(category("Ah Ad 9c 9s 2h") == Category::TwoPair);
+        assert!(category("Ah Ad Ac 9s 2h") == Category::Trips);
+        assert!(category("Ah Kd Qc Js 10h") == Category::Straight);
+        assert!(category("Ah 2d 3c 4s 5h") == Category::Straight);
+        assert!(category("Ah 9h 7h 4h 2h") == Category::Flush);
+        assert!(category("Ah Ad Ac 9s 9h") == Category::FullHouse);
+        assert!(category("Ah Ad Ac As 9h") == Category::Quads);
+        assert!(category("5h 6h 7h 8h 9h") == Category::StraightFlush);
+    }
+
+    #[test]
+    fn best_five_of_seven() {
+        assert!(category("Ah Ad Ac 9s 9h 2c 3d") == Category::FullHouse);
+        assert!(category("2h 7h 9h Jh Kh Ks Kd") == Category::Flush);
+    }
+
+    #[test]
+    fn the_wheel_is_the_lowest_straight() {
+        assert!(best(&cards("Ah 2d 3c 4s 5h")) < best(&cards("2d 3c 4s 5h 6d")));
+    }
+
+    #[test]
+    fn kickers() {
+        assert!(best(&cards("Ah Ad Kc 9s 2h")) > best(&cards("Ah Ad Qc 9s 2h")));
+        assert!(best(&cards("Ah Ad 9c 9s 2h")) > best(&cards("Kh Kd Qc Qs Ah")));
+        assert!(best(&cards("Ah Kd 9c 7s 2h")) == best(&cards("As Kc 9d 7h 2c")));
+    }
+
+    #[test]
+    fn needs_five_cards() {
+        assert!(best(&cards("Ah Ad Ac As")).is_none());
+    }
+}
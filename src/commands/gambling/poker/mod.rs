@@ -0,0 +1,556 @@
+use super::session::{Session, Status};
+use crate::{
+    commands::gambling::{self, amount::TokenAmount},
+    common::{self, Context, Error},
+    games::Game,
+    interactions::Route,
+};
+use game::{Action, Player, Table};
+use poise::serenity_prelude::{
+    self as serenity,
+    futures::{stream::FuturesUnordered, StreamExt},
+    CreateInteractionResponseMessage, UserId,
+};
+use sqlx::PgConnection;
+use std::time::Duration;
+use tokio::time::Instant;
+
+mod game;
+mod hand;
+
+/// The registry entry that poker's sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 445,
+    name: "Poker",
+};
+
+/// The most players that can sit at one table
+const SEATS: usize = 9;
+
+/// The smallest buy in, in big blinds
+const MIN_BUY_IN: i32 = 10;
+
+/// The highest big blind a table can be opened with
+const MAX_BIG_BLIND: i32 = 100_000;
+
+#[derive(poise::Modal)]
+#[name = "Join the poker table"]
+struct Join {
+    #[name = "How many tokens do you want to buy in with?"]
+    #[placeholder = "200, 1k, half"]
+    buy_in: String,
+}
+
+#[derive(poise::Modal)]
+#[name = "Raise"]
+struct Raise {
+    #[name = "Raise the bet to"]
+    #[placeholder = "40"]
+    to: String,
+}
+
+async fn respond(
+    ctx: Context<'_>,
+    mci: &serenity::ComponentInteraction,
+    text: impl Into<String>,
+) -> Result<(), Error> {
+    mci.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(text),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn lobby(
+    host: UserId,
+    seconds: u64,
+    big_blind: i32,
+    players: &[(UserId, i32)],
+    notice: Option<&str>,
+) -> String {
+    let mut output = format!(
+        "<@{host}> opened a poker table with a big blind of {big_blind}! Buy in within {seconds} seconds, the least you can buy in with is {}.\n\n**Players** ({}/{SEATS}):\n",
+        big_blind * MIN_BUY_IN,
+        players.len()
+    );
+
+    if players.is_empty() {
+        output += "Nobody has joined yet.\n";
+    }
+
+    for (user, buy_in) in players {
+        output += &format!("- <@{user}>: {buy_in} token(s)\n");
+    }
+
+    if let Some(notice) = notice {
+        output += &format!("\n{notice}");
+    }
+
+    output
+}
+
+fn lobby_buttons(route: &Route) -> Vec<serenity::CreateActionRow> {
+    vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(route.id("join"))
+            .label("Join")
+            .style(serenity::ButtonStyle::Primary),
+        serenity::CreateButton::new(route.id("start"))
+            .label("Start")
+            .style(serenity::ButtonStyle::Success),
+    ])]
+}
+
+fn buttons(route: &Route, table: &Table) -> Vec<serenity::CreateActionRow> {
+    let call = if table.can_check() {
+        serenity::CreateButton::new(route.id("check")).label("Check")
+    } else {
+        serenity::CreateButton::new(route.id("call")).label(format!("Call {}", table.to_call()))
+    };
+
+    vec![
+        serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(route.id("fold"))
+                .label("Fold")
+                .style(serenity::ButtonStyle::Danger),
+            call.style(serenity::ButtonStyle::Primary),
+            serenity::CreateButton::new(route.id("raise"))
+                .label("Raise")
+                .style(serenity::ButtonStyle::Success),
+            serenity::CreateButton::new(route.id("allin"))
+                .label(format!("All In ({})", table.all_in()))
+                .style(serenity::ButtonStyle::Secondary),
+        ]),
+        serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(route.id("cards"))
+                .label("My Cards")
+                .style(serenity::ButtonStyle::Secondary),
+            serenity::CreateButton::new(route.id("leave"))
+                .label("Leave")
+                .style(serenity::ButtonStyle::Secondary),
+        ]),
+    ]
+}
+
+/// Give a player their chips back as tokens
+async fn cash_out(db: &mut PgConnection, session: &Session, player: &Player) -> Result<(), Error> {
+    session.release(&mut *db, player.user).await?;
+    let balance = gambling::get_balance(player.user, &mut *db).await?;
+    gambling::change_balance(player.user, balance + player.stack, &mut *db).await?;
+    gambling::history::record(
+        db,
+        player.user,
+        session.channel,
+        &GAME,
+        player.bought,
+        player.stack - player.bought,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Play hands until fewer than two players are left at the table
+async fn play(ctx: Context<'_>, session: &Session, table: &mut Table) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let msg = session.message;
+    let channel = session.channel;
+
+    let mut route = Route::new(&GAME.key(), session.id);
+    route.restrict(table.players.iter().map(|p| p.user).collect());
+
+    let mut results = vec![];
+
+    loop {
+        let mut tx = db.begin().await?;
+        let mut left = vec![];
+
+        for player in std::mem::take(&mut table.players) {
+            if player.leaving || player.stack == 0 {
+                cash_out(&mut tx, session, &player).await?;
+                left.push(player);
+            } else {
+                table.players.push(player);
+            }
+        }
+
+        for player in left {
+            results.push(format!(
+                "<@{}> left the table with **{}** token(s).",
+                player.user, player.stack
+            ));
+        }
+
+        if table.players.len() < 2 {
+            for player in &table.players {
+                cash_out(&mut tx, session, player).await?;
+                results.push(format!(
+                    "<@{}> left the table with **{}** token(s).",
+                    player.user, player.stack
+                ));
+            }
+
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+            break;
+        }
+
+        let round = gambling::fair::Round::start(&mut tx, &gambling::fair::Draw::Poker).await?;
+        table.deal(round);
+
+        // chips only change hands once a hand is over, so a restart refunds the hand being played
+        for player in &table.players {
+            session
+                .escrow(&mut tx, player.user, player.buy_in())
+                .await?;
+        }
+
+        session.save(&mut tx, &*table).await?;
+        tx.commit().await?;
+
+        loop {
+            while !table.round_over() {
+                channel
+                    .edit_message(
+                        ctx,
+                        msg,
+                        serenity::EditMessage::new()
+                            .content(table.render())
+                            .components(buttons(&route, table)),
+                    )
+                    .await?;
+
+                let Some((mci, action)) =
+                    route.next(Instant::now() + Duration::from_secs(60)).await
+                else {
+                    let turn = table.turn;
+                    let player = &mut table.players[turn];
+                    player.timeouts += 1;
+
+                    // two timeouts in a row is taken as the player having left
+                    if player.timeouts >= 2 {
+                        player.leaving = true;
+                    }
+
+                    let action = if table.can_check() {
+                        Action::Check
+                    } else {
+                        Action::Fold
+                    };
+
+                    table
+                        .apply(action)
+                        .expect("checking or folding is always allowed");
+                    continue;
+                };
+
+                let Some(i) = table.players.iter().position(|p| p.user == mci.user.id) else {
+                    respond(ctx, &mci, "You are no longer at this table.").await?;
+                    continue;
+                };
+
+                match action.as_str() {
+                    "cards" => {
+                        respond(
+                            ctx,
+                            &mci,
+                            format!("Your cards are {}.", table.players[i].cards()),
+                        )
+                        .await?;
+                        continue;
+                    }
+                    "leave" => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+
+                        table.players[i].leaving = true;
+                        table.fold(i);
+                        table.last =
+                            format!("<@{}> is leaving the table after this hand.", mci.user.id);
+                        continue;
+                    }
+                    _ if i != table.turn => {
+                        respond(ctx, &mci, "It is not your turn yet.").await?;
+                        continue;
+                    }
+                    _ => (),
+                }
+
+                let action = match action.as_str() {
+                    "fold" => Action::Fold,
+                    "check" => Action::Check,
+                    "call" => Action::Call,
+                    "allin" => Action::AllIn,
+                    "raise" => {
+                        let Some(raise) = poise::execute_modal_on_component_interaction::<Raise>(
+                            ctx,
+                            mci,
+                            None,
+                            Some(Duration::from_secs(60)),
+                        )
+                        .await?
+                        else {
+                            continue;
+                        };
+
+                        // all and shares of it are worked out against everything the player could bet
+                        match raise
+                            .to
+                            .parse::<TokenAmount>()
+                            .and_then(|to| to.of(table.all_in()))
+                        {
+                            Ok(to) => {
+                                table.players[i].timeouts = 0;
+
+                                if let Err(e) = table.apply(Action::Raise(to)) {
+                                    table.last = e;
+                                }
+                            }
+                            Err(e) => {
+                                table.last = e.to_string();
+                            }
+                        }
+
+                        continue;
+                    }
+                    _ => continue,
+                };
+
+                table.players[i].timeouts = 0;
+
+                match table.apply(action) {
+                    Ok(()) => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+                    }
+                    Err(e) => respond(ctx, &mci, e).await?,
+                }
+            }
+
+            if table.in_hand() <= 1 || table.board_full() {
+                break;
+            }
+
+            table.next_street();
+        }
+
+        let lines = table.settle();
+
+        channel
+            .edit_message(
+                ctx,
+                msg,
+                serenity::EditMessage::new()
+                    .content(format!(
+                        "{}\n\n{}\n\nThe next hand starts in a few seconds.",
+                        table.render(),
+                        lines.join("\n")
+                    ))
+                    .components(vec![]),
+            )
+            .await?;
+
+        tokio::time::sleep(Duration::from_secs(8)).await;
+    }
+
+    channel
+        .edit_message(
+            ctx,
+            msg,
+            serenity::EditMessage::new()
+                .content(format!(
+                    "The poker table has closed.\n\n{}",
+                    results.join("\n")
+                ))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Open a no limit Texas hold'em table that other players can buy in to
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn poker(
+    ctx: Context<'_>,
+    #[description = "The big blind, the small blind is half of it"]
+    #[min = 2]
+    #[max = 100_000]
+    big_blind: Option<i32>,
+    #[description = "How many seconds players have to join (10-120)"] seconds: Option<u64>,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let host = ctx.author().id;
+    let big_blind = big_blind.unwrap_or(10);
+
+    if !(2..=MAX_BIG_BLIND).contains(&big_blind) {
+        ctx.reply(format!(
+            "The big blind has to be between 2 and {MAX_BIG_BLIND} tokens."
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let seconds = seconds.unwrap_or(60).clamp(10, 120);
+
+    let mut players: Vec<(UserId, i32)> = vec![];
+    let mut route = Route::unique("poker-table");
+
+    let msg = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(lobby(host, seconds, big_blind, &players, None))
+                .components(lobby_buttons(&route)),
+        )
+        .await?;
+
+    let id = msg.message().await?.id;
+
+    let mut modals = FuturesUnordered::new();
+    let deadline = Instant::now() + Duration::from_secs(seconds);
+
+    loop {
+        tokio::select! {
+            Some((user, join)) = modals.next() => {
+                let Ok(Some(Join { buy_in })) = join else {
+                    continue;
+                };
+
+                let balance = gambling::get_balance(user, db).await?;
+
+                let buy_in = buy_in.parse::<TokenAmount>().and_then(|buy_in| buy_in.of(balance));
+
+                let refusal = match buy_in {
+                    Ok(buy_in) if buy_in >= big_blind * MIN_BUY_IN && buy_in <= balance => {
+                        gambling::limits::check(&mut *db.acquire().await?, user, ctx.guild_id(), buy_in)
+                            .await?
+                    }
+                    _ => None,
+                };
+
+                let notice = match buy_in {
+                    Ok(buy_in) if buy_in < big_blind * MIN_BUY_IN => Some(format!(
+                        "<@{user}> has to buy in with at least {} tokens.",
+                        big_blind * MIN_BUY_IN
+                    )),
+                    Ok(buy_in) if buy_in > balance => Some(format!(
+                        "<@{user}> does not have enough tokens (**{balance}**) to buy in with this amount."
+                    )),
+                    Ok(_) if refusal.is_some() => {
+                        refusal.map(|reason| format!("<@{user}> can't join. {reason}"))
+                    }
+                    Ok(buy_in) => {
+                        match players.iter().position(|(u, _)| *u == user) {
+                            Some(i) => players[i].1 = buy_in,
+                            None if players.len() < SEATS => players.push((user, buy_in)),
+                            None => {}
+                        }
+
+                        None
+                    }
+                    Err(e) => Some(format!("<@{user}>: {e}")),
+                };
+
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(lobby(host, seconds, big_blind, &players, notice.as_deref()))
+                        .components(lobby_buttons(&route)),
+                )
+                .await?;
+            }
+            next = route.next(deadline) => {
+                let Some((mci, action)) = next else {
+                    break;
+                };
+
+                match action.as_str() {
+                    "start" if mci.user.id == host => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        break;
+                    }
+                    "start" => {
+                        respond(ctx, &mci, format!("Only <@{host}> can start the game.")).await?;
+                    }
+                    _ if players.len() >= SEATS
+                        && !players.iter().any(|(u, _)| *u == mci.user.id) =>
+                    {
+                        respond(ctx, &mci, "This table is full.").await?;
+                    }
+                    _ => modals.push(async move {
+                        let user = mci.user.id;
+                        let join = poise::execute_modal_on_component_interaction::<Join>(
+                            ctx,
+                            mci,
+                            None,
+                            Some(Duration::from_secs(60)),
+                        )
+                        .await;
+
+                        (user, join)
+                    }),
+                }
+            }
+        }
+    }
+
+    // the lobby's buttons stop working once the game takes over the message
+    drop(route);
+
+    let mut tx = db.begin().await?;
+    let mut seated = vec![];
+    let mut left = vec![];
+
+    for (user, buy_in) in players {
+        // balances may have changed since joining
+        let balance = gambling::get_balance(user, &mut *tx).await?;
+
+        if balance < buy_in {
+            left.push(format!("<@{user}>"));
+            continue;
+        }
+
+        seated.push(Player::new(user, buy_in));
+    }
+
+    if seated.len() < 2 {
+        msg.edit(
+            ctx,
+            poise::CreateReply::default()
+                .content("A poker table needs at least two players who can afford their buy in.")
+                .components(vec![]),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if !left.is_empty() {
+        common::no_ping_reply(
+            &ctx,
+            format!(
+                "{} can no longer afford their buy in and left the table.",
+                left.join(", ")
+            ),
+        )
+        .await?;
+    }
+
+    let mut table = Table::new(seated, big_blind);
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), id, &table).await?;
+
+    for player in &table.players {
+        session.hold(&mut tx, player.user, player.stack).await?;
+    }
+
+    tx.commit().await?;
+
+    session
+        .refund_on_error(db, play(ctx, &session, &mut table))
+        .await
+}
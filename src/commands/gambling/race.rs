@@ -0,0 +1,640 @@
+use super::{
+    amount::TokenAmount,
+    fair,
+    session::{Session, Status},
+};
+use crate::{
+    commands::settings,
+    common::{self, BigBirbError, Context, Error},
+    games::Game,
+};
+
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{self as serenity, ChannelId, GuildId, UserId};
+use rand::{seq::SliceRandom, Rng};
+use serde::Serialize;
+use sqlx::{types::chrono::Utc, PgPool, Row};
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use tokio::{sync::mpsc, time::Instant};
+
+/// The registry entry that race's sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 447,
+    name: "Race",
+};
+
+/// How many steps it takes to reach the finish line
+const TRACK: usize = 24;
+
+/// How often the race is redrawn
+const TICK: Duration = Duration::from_millis(1500);
+
+const HORSES: [&str; 10] = [
+    "Thunderhoof",
+    "Biscuit",
+    "Midnight Express",
+    "Sir Gallops",
+    "Buttercup",
+    "Dusty Trail",
+    "Lucky Clover",
+    "Hay Fever",
+    "Stormchaser",
+    "Old Reliable",
+];
+
+const SNAILS: [&str; 10] = [
+    "Turbo",
+    "Slimeball",
+    "Shelly",
+    "Gary",
+    "Sir Slowpoke",
+    "Escargot",
+    "Zoom Zoom",
+    "Trailblazer",
+    "Mossy",
+    "Speedy",
+];
+
+#[derive(Clone, Copy, poise::ChoiceParameter)]
+pub enum Kind {
+    Horse,
+    Snail,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Horse => "horse",
+            Self::Snail => "snail",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "snail" => Self::Snail,
+            _ => Self::Horse,
+        }
+    }
+
+    fn emoji(&self) -> &'static str {
+        match self {
+            Self::Horse => "\u{1F3C7}",
+            Self::Snail => "\u{1F40C}",
+        }
+    }
+
+    fn names(&self) -> &'static [&'static str] {
+        match self {
+            Self::Horse => &HORSES,
+            Self::Snail => &SNAILS,
+        }
+    }
+
+    /// The most steps an entrant can take in one tick
+    fn stride(&self) -> usize {
+        match self {
+            Self::Horse => 3,
+            Self::Snail => 2,
+        }
+    }
+}
+
+/// Pick who runs in a race of `kind`, drawn from the same generator that then runs the race
+pub fn field(kind: &str, entrants: usize, rng: &mut impl Rng) -> Vec<&'static str> {
+    Kind::parse(kind)
+        .names()
+        .choose_multiple(rng, entrants)
+        .cloned()
+        .collect()
+}
+
+/// Run a race of `entrants` who each move up to `stride` steps at a time, returning where
+/// everyone was at each step and who won. The race is decided up front, then played back.
+pub fn simulate(entrants: usize, stride: usize, rng: &mut impl Rng) -> (Vec<Vec<usize>>, usize) {
+    let mut positions = vec![0; entrants];
+    let mut frames = vec![positions.clone()];
+
+    while positions.iter().all(|p| *p < TRACK) {
+        for position in &mut positions {
+            *position += rng.gen_range(0..=stride);
+        }
+
+        frames.push(positions.clone());
+    }
+
+    let last = frames.last().unwrap();
+    let furthest = *last.iter().max().unwrap();
+    let leaders: Vec<usize> = (0..entrants).filter(|i| last[*i] == furthest).collect();
+
+    // a photo finish is settled by a coin toss between the leaders
+    let winner = *leaders.choose(rng).unwrap();
+
+    (frames, winner)
+}
+
+#[derive(Serialize)]
+struct Bet {
+    user: UserId,
+    entrant: usize,
+    amount: i32,
+}
+
+/// A race that is taking bets, `/race bet` in its channel sends bets to it
+struct Open {
+    session: Session,
+    entrants: usize,
+    sender: mpsc::UnboundedSender<Bet>,
+}
+
+static OPEN: Lazy<Mutex<HashMap<ChannelId, Open>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize)]
+struct State {
+    kind: &'static str,
+    entrants: Vec<&'static str>,
+    bets: Vec<Bet>,
+}
+
+impl State {
+    /// Every bet added up, in i64 since a busy race can hold more than an i32
+    fn pool(&self) -> i64 {
+        self.bets.iter().map(|bet| bet.amount as i64).sum()
+    }
+
+    fn on(&self, entrant: usize) -> i64 {
+        self.bets
+            .iter()
+            .filter(|bet| bet.entrant == entrant)
+            .map(|bet| bet.amount as i64)
+            .sum()
+    }
+
+    fn card(&self, kind: Kind, seconds: u64, cut: i32) -> String {
+        let mut output = format!(
+            "A {} race starts in {seconds} seconds! Bet with `/race bet <entrant> <amount>` in this channel.\n\n",
+            kind.as_str()
+        );
+
+        let pool = self.pool();
+
+        for (i, name) in self.entrants.iter().enumerate() {
+            let on = self.on(i);
+
+            // what a winning token would pay if betting closed now
+            let odds = if on > 0 {
+                format!(
+                    "pays {:.2}x",
+                    pool as f64 * (100 - cut) as f64 / 100.0 / on as f64
+                )
+            } else {
+                "no bets yet".to_string()
+            };
+
+            output += &format!("**{}**. {name}: {on} token(s), {odds}\n", i + 1);
+        }
+
+        output += &format!("\n**Pool**: {pool}, the house keeps {cut}%");
+
+        output
+    }
+}
+
+fn track(kind: Kind, entrants: &[&str], positions: &[usize]) -> String {
+    let mut output = String::new();
+
+    for (i, (name, position)) in entrants.iter().zip(positions).enumerate() {
+        let position = (*position).min(TRACK);
+
+        output += &format!(
+            "`{}{}{}`\u{1F3C1} **{}**. {name}\n",
+            "\u{00B7}".repeat(position),
+            kind.emoji(),
+            "\u{00B7}".repeat(TRACK - position),
+            i + 1
+        );
+    }
+
+    output
+}
+
+/// Run a race in a channel from the betting window through to paying out the pool
+async fn run(
+    ctx: &serenity::Context,
+    db: &PgPool,
+    guild: GuildId,
+    channel: ChannelId,
+    kind: Kind,
+    entrants: usize,
+    seconds: u64,
+) -> Result<(), Error> {
+    let cut = settings::get_race_cut(db, guild).await?;
+
+    let mut tx = db.begin().await?;
+    let round = fair::Round::start(
+        &mut tx,
+        &fair::Draw::Race {
+            kind: kind.as_str().to_string(),
+            entrants,
+            stride: kind.stride(),
+        },
+    )
+    .await?;
+
+    // the entrants and the race itself come from one generator, so both can be checked
+    let mut rng = round.rng();
+
+    let mut state = State {
+        kind: kind.as_str(),
+        entrants: field(kind.as_str(), entrants, &mut rng),
+        bets: vec![],
+    };
+
+    let mut msg = channel
+        .send_message(
+            ctx,
+            serenity::CreateMessage::new().content(format!(
+                "{}\n{}",
+                state.card(kind, seconds, cut),
+                round.commitment()
+            )),
+        )
+        .await?;
+
+    let session = Session::start(&mut tx, &GAME, channel, msg.id, &state).await?;
+    tx.commit().await?;
+
+    let result = session
+        .refund_on_error(db, async {
+            let (sender, mut receiver) = mpsc::unbounded_channel();
+
+            OPEN.lock().unwrap().insert(
+                channel,
+                Open {
+                    session: session.clone(),
+                    entrants,
+                    sender,
+                },
+            );
+
+            let start = Instant::now() + Duration::from_secs(seconds);
+
+            while let Ok(Some(bet)) = tokio::time::timeout_at(start, receiver.recv()).await {
+                state.bets.push(bet);
+
+                let mut tx = db.begin().await?;
+                session.save(&mut tx, &state).await?;
+                tx.commit().await?;
+
+                let left = start.saturating_duration_since(Instant::now()).as_secs();
+
+                msg.edit(
+                    ctx,
+                    serenity::EditMessage::new().content(format!(
+                        "{}\n{}",
+                        state.card(kind, left, cut),
+                        round.commitment()
+                    )),
+                )
+                .await?;
+            }
+
+            // bets that were sent just before betting closed still count
+            OPEN.lock().unwrap().remove(&channel);
+            receiver.close();
+
+            while let Ok(bet) = receiver.try_recv() {
+                state.bets.push(bet);
+            }
+
+            if state.bets.is_empty() {
+                let mut tx = db.begin().await?;
+                session.end(&mut tx, Status::Finished).await?;
+                tx.commit().await?;
+
+                msg.edit(
+                    ctx,
+                    serenity::EditMessage::new()
+                        .content("Nobody bet on the race, so it was called off."),
+                )
+                .await?;
+
+                return Ok(());
+            }
+
+            let (frames, winner) = simulate(entrants, kind.stride(), &mut rng);
+            let last = frames.last().unwrap();
+
+            for frame in &frames {
+                msg.edit(
+                    ctx,
+                    serenity::EditMessage::new().content(track(kind, &state.entrants, frame)),
+                )
+                .await?;
+
+                tokio::time::sleep(TICK).await;
+            }
+
+            let pool = state.pool();
+            let on_winner = state.on(winner);
+            let mut payouts: HashMap<UserId, i64> = HashMap::new();
+
+            let result = if on_winner == 0 {
+                // with no winning bets there is nobody to split the pool between
+                for bet in &state.bets {
+                    *payouts.entry(bet.user).or_default() += bet.amount as i64;
+                }
+
+                "Nobody bet on the winner, so every bet has been refunded.".to_string()
+            } else {
+                let share = pool - pool * cut as i64 / 100;
+
+                for bet in state.bets.iter().filter(|bet| bet.entrant == winner) {
+                    *payouts.entry(bet.user).or_default() += share * bet.amount as i64 / on_winner;
+                }
+
+                let mut winners: Vec<(&UserId, &i64)> = payouts.iter().collect();
+                winners.sort_by(|a, b| b.1.cmp(a.1));
+
+                winners
+                    .iter()
+                    .map(|(user, amount)| format!("- <@{user}> wins **{amount}**"))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            };
+
+            let mut tx = db.begin().await?;
+
+            let mut bettors: HashMap<UserId, i64> = HashMap::new();
+
+            for bet in &state.bets {
+                *bettors.entry(bet.user).or_default() += bet.amount as i64;
+            }
+
+            for (user, staked) in bettors {
+                // escrow is released once per bettor, whatever they won is paid in its place
+                session.release(&mut tx, user).await?;
+
+                let won = i32::try_from(payouts.remove(&user).unwrap_or(0)).unwrap_or(i32::MAX);
+                let staked = i32::try_from(staked).unwrap_or(i32::MAX);
+
+                if won > 0 {
+                    let balance = super::get_balance(user, &mut *tx).await?;
+                    super::change_balance(user, balance.saturating_add(won), &mut *tx).await?;
+                }
+
+                super::history::record(
+                    &mut tx,
+                    user,
+                    session.channel,
+                    &GAME,
+                    staked,
+                    won.saturating_sub(staked),
+                )
+                .await?;
+            }
+
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+
+            msg.edit(
+                ctx,
+                serenity::EditMessage::new().content(format!(
+                    "{}\n**{}** wins the race! The pool was **{pool}** token(s).\n{result}\n{}",
+                    track(kind, &state.entrants, last),
+                    state.entrants[winner],
+                    round.reveal()
+                )),
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await;
+
+    if result.is_err() {
+        // nobody can bet on a race that is no longer being run
+        let mut open = OPEN.lock().unwrap();
+
+        if open
+            .get(&channel)
+            .is_some_and(|open| open.session.id == session.id)
+        {
+            open.remove(&channel);
+        }
+    }
+
+    result
+}
+
+/// Check for scheduled races every minute and start the ones that are due
+pub fn run_schedule(ctx: serenity::Context, db: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+
+            let rows = match sqlx::query(
+                "UPDATE race_schedules SET next = now() + every * INTERVAL '1 minute' WHERE next <= now() RETURNING channel, guildid, kind",
+            )
+            .fetch_all(&db)
+            .await
+            {
+                Ok(rows) => rows,
+                Err(e) => {
+                    eprintln!("Failed to check race schedules: {e}");
+                    continue;
+                }
+            };
+
+            for row in rows {
+                let channel = ChannelId::new(row.get::<i64, usize>(0) as u64);
+                let guild = GuildId::new(row.get::<i64, usize>(1) as u64);
+                let kind = Kind::parse(row.get(2));
+
+                if OPEN.lock().unwrap().contains_key(&channel) {
+                    continue;
+                }
+
+                let (ctx, db) = (ctx.clone(), db.clone());
+
+                tokio::spawn(async move {
+                    if let Err(e) = run(&ctx, &db, guild, channel, kind, 5, 120).await {
+                        eprintln!("Failed to run a scheduled race in {channel}: {e}");
+                    }
+                });
+            }
+        }
+    });
+}
+
+/// Start a race in this channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn start(
+    ctx: Context<'_>,
+    kind: Option<Kind>,
+    #[description = "How many seconds to take bets for (30-600)"] seconds: Option<u64>,
+    #[description = "How many entrants are in the race (2-8)"] entrants: Option<usize>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let channel = ctx.channel_id();
+
+    if OPEN.lock().unwrap().contains_key(&channel) {
+        ctx.reply("There is already a race taking bets in this channel.")
+            .await?;
+        return Ok(());
+    }
+
+    ctx.reply("And they're off to the starting gate!").await?;
+
+    run(
+        ctx.serenity_context(),
+        &ctx.data().database,
+        guild,
+        channel,
+        kind.unwrap_or(Kind::Horse),
+        entrants.unwrap_or(5).clamp(2, 8),
+        seconds.unwrap_or(120).clamp(30, 600),
+    )
+    .await
+}
+
+/// Bet on an entrant in the race that is taking bets in this channel
+#[poise::command(slash_command, prefix_command, guild_only)]
+async fn bet(
+    ctx: Context<'_>,
+    #[description = "The number of the entrant to bet on"] entrant: usize,
+    amount: TokenAmount,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let user = ctx.author().id;
+
+    let open = OPEN
+        .lock()
+        .unwrap()
+        .get(&ctx.channel_id())
+        .map(|open| (open.session.clone(), open.entrants, open.sender.clone()));
+
+    let Some((session, entrants, sender)) = open else {
+        ctx.reply("There is no race taking bets in this channel.")
+            .await?;
+        return Ok(());
+    };
+
+    if entrant < 1 || entrant > entrants {
+        ctx.reply(format!("Pick an entrant between 1 and {entrants}."))
+            .await?;
+        return Ok(());
+    }
+
+    let mut tx = db.begin().await?;
+    let balance = super::get_balance(user, &mut *tx).await?;
+
+    let Some(amount) = amount.resolve(ctx, balance).await? else {
+        return Ok(());
+    };
+
+    if !super::limits::allowed(ctx, &mut tx, amount).await? {
+        return Ok(());
+    }
+
+    let held = session.held(&mut tx, user).await?;
+    session.hold(&mut tx, user, held + amount).await?;
+    tx.commit().await?;
+
+    let bet = Bet {
+        user,
+        entrant: entrant - 1,
+        amount,
+    };
+
+    if sender.send(bet).is_err() {
+        let mut tx = db.begin().await?;
+        session.hold(&mut tx, user, held).await?;
+        tx.commit().await?;
+
+        ctx.reply("Betting has already closed, your bet has been refunded.")
+            .await?;
+        return Ok(());
+    }
+
+    ctx.reply(format!(
+        "You've bet **{amount}** token(s) on number {entrant}."
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Start a race in this channel every so often
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn schedule(
+    ctx: Context<'_>,
+    #[description = "How many minutes between races (10-1440)"] minutes: i32,
+    kind: Option<Kind>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let minutes = minutes.clamp(10, 1440);
+
+    sqlx::query("INSERT INTO race_schedules (channel, guildid, kind, every, next) VALUES ($1, $2, $3, $4, $5) ON CONFLICT (channel) DO UPDATE SET kind = EXCLUDED.kind, every = EXCLUDED.every, next = EXCLUDED.next")
+        .bind(ctx.channel_id().get() as i64)
+        .bind(guild.get() as i64)
+        .bind(kind.unwrap_or(Kind::Horse).as_str())
+        .bind(minutes)
+        .bind(Utc::now() + Duration::from_secs(minutes as u64 * 60))
+        .execute(&ctx.data().database)
+        .await?;
+
+    ctx.reply(format!(
+        "A race will start in this channel every {minutes} minutes."
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Stop the races scheduled in this channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD"
+)]
+async fn unschedule(ctx: Context<'_>) -> Result<(), Error> {
+    let removed = sqlx::query("DELETE FROM race_schedules WHERE channel = $1")
+        .bind(ctx.channel_id().get() as i64)
+        .execute(&ctx.data().database)
+        .await?
+        .rows_affected();
+
+    let text = if removed > 0 {
+        "Races are no longer scheduled in this channel."
+    } else {
+        "There are no races scheduled in this channel."
+    };
+
+    common::no_ping_reply(&ctx, text).await?;
+
+    Ok(())
+}
+
+/// Bet on horse and snail races, the winning bettors split the pool
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("start", "bet", "schedule", "unschedule"),
+    subcommand_required
+)]
+pub async fn race(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
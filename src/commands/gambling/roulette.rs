@@ -0,0 +1,481 @@
+use super::{
+    amount::{self, TokenAmount},
+    effects::{Effects, Settlement},
+    fair,
+    session::{Session, Status},
+};
+use crate::{
+    common::{Context, Error},
+    games::Game,
+    interactions::Route,
+};
+use poise::serenity_prelude as serenity;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, time::Duration};
+use tokio::time::Instant;
+
+/// The registry entry that roulette's sessions, history and stats are kept under
+pub const GAME: Game = Game {
+    id: 442,
+    name: "Roulette",
+};
+
+const RED: [u8; 18] = [
+    1, 3, 5, 7, 9, 12, 14, 16, 18, 19, 21, 23, 25, 27, 30, 32, 34, 36,
+];
+
+fn is_red(n: u8) -> bool {
+    RED.contains(&n)
+}
+
+fn color(n: u8) -> &'static str {
+    match n {
+        0 => "green",
+        n if is_red(n) => "red",
+        _ => "black",
+    }
+}
+
+/// Where the ball lands on a single zero wheel
+pub fn spin(rng: &mut impl Rng) -> u8 {
+    rng.gen_range(0..=36)
+}
+
+/// A spot on the table a bet can be placed on. Streets, corners and lines are named by
+/// their lowest number.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum Kind {
+    Straight(u8),
+    Split(u8, u8),
+    Street(u8),
+    Corner(u8),
+    Line(u8),
+    Dozen(u8),
+    Column(u8),
+    Red,
+    Black,
+    Odd,
+    Even,
+    Low,
+    High,
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Straight(n) => write!(f, "{n}"),
+            Self::Split(a, b) => write!(f, "Split {a}/{b}"),
+            Self::Street(n) => write!(f, "Street {n}-{}", n + 2),
+            Self::Corner(n) => write!(f, "Corner {n}/{}/{}/{}", n + 1, n + 3, n + 4),
+            Self::Line(n) => write!(f, "Line {n}-{}", n + 5),
+            Self::Dozen(n) => write!(f, "Dozen {}-{}", n * 12 - 11, n * 12),
+            Self::Column(n) => write!(f, "Column {n}"),
+            Self::Red => write!(f, "Red"),
+            Self::Black => write!(f, "Black"),
+            Self::Odd => write!(f, "Odd"),
+            Self::Even => write!(f, "Even"),
+            Self::Low => write!(f, "Low (1-18)"),
+            Self::High => write!(f, "High (19-36)"),
+        }
+    }
+}
+
+impl Kind {
+    fn parse(input: &str) -> Option<Self> {
+        let input = input.trim().to_lowercase();
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let number = |s: &str| s.parse::<u8>().ok().filter(|n| *n <= 36);
+
+        let kind = match words.as_slice() {
+            ["red"] => Self::Red,
+            ["black"] => Self::Black,
+            ["odd"] => Self::Odd,
+            ["even"] => Self::Even,
+            ["low"] | ["1-18"] => Self::Low,
+            ["high"] | ["19-36"] => Self::High,
+            ["dozen", n] => Self::Dozen(number(n).filter(|n| (1..=3).contains(n))?),
+            ["column" | "col", n] => Self::Column(number(n).filter(|n| (1..=3).contains(n))?),
+            ["street", n] => {
+                let n = number(n).filter(|n| *n > 0)?;
+                Self::Street((n - 1) / 3 * 3 + 1)
+            }
+            ["line", n] => {
+                let n = number(n).filter(|n| (1..=33).contains(n))?;
+                Self::Line((n - 1) / 3 * 3 + 1)
+            }
+            ["corner", n] => {
+                let n = number(n).filter(|n| (1..=32).contains(n) && n % 3 != 0)?;
+                Self::Corner(n)
+            }
+            ["split", pair] | [pair] if pair.contains('/') => {
+                let (a, b) = pair.split_once('/')?;
+                let (a, b) = (number(a)?, number(b)?);
+                let (a, b) = (a.min(b), a.max(b));
+
+                let adjacent = match a {
+                    0 => (1..=3).contains(&b),
+                    a => b == a + 3 || (b == a + 1 && a % 3 != 0),
+                };
+
+                if !adjacent {
+                    return None;
+                }
+
+                Self::Split(a, b)
+            }
+            [n] => Self::Straight(number(n)?),
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    fn covers(&self, n: u8) -> bool {
+        match *self {
+            Self::Straight(a) => n == a,
+            Self::Split(a, b) => n == a || n == b,
+            Self::Street(a) => (a..a + 3).contains(&n),
+            Self::Corner(a) => [a, a + 1, a + 3, a + 4].contains(&n),
+            Self::Line(a) => (a..a + 6).contains(&n),
+            _ if n == 0 => false,
+            Self::Dozen(d) => (n - 1) / 12 + 1 == d,
+            Self::Column(c) => (n - 1) % 3 + 1 == c,
+            Self::Red => is_red(n),
+            Self::Black => !is_red(n),
+            Self::Odd => !n.is_multiple_of(2),
+            Self::Even => n.is_multiple_of(2),
+            Self::Low => n <= 18,
+            Self::High => n >= 19,
+        }
+    }
+
+    /// What a winning bet pays for every token bet. A single zero wheel pays as if
+    /// it had 36 pockets, which is where the house edge comes from.
+    fn payout(&self) -> i32 {
+        let covered = (0..=36).filter(|n| self.covers(*n)).count() as i32;
+        36 / covered - 1
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Bet {
+    kind: Kind,
+    amount: i32,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Slip {
+    bets: Vec<Bet>,
+}
+
+impl Slip {
+    fn total(&self) -> i32 {
+        self.bets.iter().map(|bet| bet.amount).sum()
+    }
+
+    fn render(&self, notice: Option<&str>) -> String {
+        let mut output = "**Bet slip**\n".to_string();
+
+        if self.bets.is_empty() {
+            output += "No bets yet.\n";
+        }
+
+        for bet in &self.bets {
+            output += &format!(
+                "- **{}** on {} ({}:1)\n",
+                bet.amount,
+                bet.kind,
+                bet.kind.payout()
+            );
+        }
+
+        output += &format!("**Total**: {}\n", self.total());
+
+        if let Some(notice) = notice {
+            output += &format!("\n{notice}");
+        }
+
+        output
+    }
+}
+
+#[derive(poise::Modal)]
+#[name = "Place a bet"]
+struct Place {
+    #[name = "Bet"]
+    #[placeholder = "red, 17, split 17/20, street 13, corner 1, line 4, dozen 2, column 3, odd, high"]
+    bet: String,
+    #[name = "Tokens"]
+    #[placeholder = "10, 1k, half, all - 100"]
+    amount: String,
+}
+
+fn buttons(route: &Route) -> Vec<serenity::CreateActionRow> {
+    vec![serenity::CreateActionRow::Buttons(vec![
+        serenity::CreateButton::new(route.id("add"))
+            .label("Add Bet")
+            .style(serenity::ButtonStyle::Primary),
+        serenity::CreateButton::new(route.id("clear"))
+            .label("Clear")
+            .style(serenity::ButtonStyle::Secondary),
+        serenity::CreateButton::new(route.id("spin"))
+            .label("Spin")
+            .style(serenity::ButtonStyle::Success),
+    ])]
+}
+
+/// Bet on where the ball lands on a single zero roulette wheel
+#[poise::command(slash_command, prefix_command)]
+pub async fn roulette(
+    ctx: Context<'_>,
+    #[autocomplete = "super::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    let db = &ctx.data().database;
+    let user = ctx.author().id;
+    let mut tx = db.begin().await?;
+
+    let item = match item {
+        Some(item) => match super::use_item(ctx, &mut *tx, &GAME, &item).await? {
+            Some(item) => Some(item),
+            None => return Ok(()),
+        },
+        None => None,
+    };
+
+    let effects = Effects::load(&mut *tx, user, item).await?;
+    let mut slip = Slip::default();
+
+    let msg = ctx.reply("Just a second...").await?;
+    let message = msg.message().await?.id;
+    let session = Session::start(&mut tx, &GAME, ctx.channel_id(), message, &slip).await?;
+    tx.commit().await?;
+
+    session
+        .refund_on_error(db, async {
+            let mut route = Route::new(&GAME.key(), session.id);
+            route.restrict(vec![user]);
+
+            let mut notice = Some("Add your bets, then spin the wheel.".to_string());
+
+            loop {
+                msg.edit(
+                    ctx,
+                    poise::CreateReply::default()
+                        .content(slip.render(notice.take().as_deref()))
+                        .components(buttons(&route)),
+                )
+                .await?;
+
+                let Some((mci, action)) = route.next(Instant::now() + Duration::from_secs(120)).await
+                else {
+                    let mut tx = db.begin().await?;
+                    session.refund(&mut tx).await?;
+                    tx.commit().await?;
+
+                    msg.edit(
+                        ctx,
+                        poise::CreateReply::default()
+                            .content("The bet slip expired and your bets were refunded.")
+                            .components(vec![]),
+                    )
+                    .await?;
+
+                    return Ok(());
+                };
+
+                match action.as_str() {
+                    "add" => {
+                        let Some(place) = poise::execute_modal_on_component_interaction::<Place>(
+                            ctx,
+                            mci,
+                            None,
+                            Some(Duration::from_secs(60)),
+                        )
+                        .await?
+                        else {
+                            continue;
+                        };
+
+                        let Some(kind) = Kind::parse(&place.bet) else {
+                            notice = Some(format!("{} is not a bet on this table.", place.bet.trim()));
+                            continue;
+                        };
+
+                        let mut tx = db.begin().await?;
+                        let balance = super::get_balance(user, &mut *tx).await?;
+
+                        let parsed = place.amount.parse::<TokenAmount>();
+                        let amount = match amount::check(parsed.and_then(|a| a.of(balance)), balance) {
+                            Ok(amount) => amount,
+                            Err(reason) => {
+                                notice = Some(reason);
+                                continue;
+                            }
+                        };
+
+                        if let Some(reason) =
+                            super::limits::check(&mut tx, user, ctx.guild_id(), amount).await?
+                        {
+                            notice = Some(reason);
+                            continue;
+                        }
+
+                        match slip.bets.iter_mut().find(|bet| bet.kind == kind) {
+                            Some(bet) => bet.amount += amount,
+                            None => slip.bets.push(Bet { kind, amount }),
+                        }
+
+                        session.hold(&mut tx, user, slip.total()).await?;
+                        session.save(&mut tx, &slip).await?;
+                        tx.commit().await?;
+                    }
+                    "clear" => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+
+                        slip.bets.clear();
+
+                        let mut tx = db.begin().await?;
+                        session.hold(&mut tx, user, 0).await?;
+                        session.save(&mut tx, &slip).await?;
+                        tx.commit().await?;
+                    }
+                    "spin" if slip.bets.is_empty() => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        notice = Some("Add a bet before spinning the wheel.".to_string());
+                    }
+                    "spin" => {
+                        mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                            .await?;
+                        break;
+                    }
+                    _ => (),
+                }
+            }
+
+            let mut tx = db.begin().await?;
+            let (mut rng, nonce) = fair::next(&mut tx, user, &fair::Draw::Roulette).await?;
+            let number = spin(&mut rng);
+
+            let mut results = vec![];
+            let mut returned: i64 = 0;
+
+            for bet in &slip.bets {
+                if bet.kind.covers(number) {
+                    // a straight up bet pays 35 times over, which doesn't always fit in an i32
+                    let won = bet.amount as i64 * bet.kind.payout() as i64;
+                    returned += bet.amount as i64 + won;
+                    results.push(format!("- {} on {}: won **{won}**", bet.amount, bet.kind));
+                } else {
+                    results.push(format!("- {} on {}: lost", bet.amount, bet.kind));
+                }
+            }
+
+            // the whole spin is settled as one bet so that effects apply once
+            let net = i32::try_from(returned - slip.total() as i64).unwrap_or(i32::MAX);
+            let mut settlement = Settlement::net(slip.total(), net);
+
+            effects.settle(&mut settlement);
+
+            let held = session.release(&mut tx, user).await?;
+            let balance = super::get_balance(user, &mut *tx)
+                .await?
+                .saturating_add(held)
+                .saturating_add(settlement.net);
+            super::change_balance(user, balance, &mut *tx).await?;
+            effects.finish(&mut tx).await?;
+            super::history::record(
+                &mut tx,
+                user,
+                session.channel,
+                &GAME,
+                slip.total(),
+                settlement.net,
+            )
+            .await?;
+            session.end(&mut tx, Status::Finished).await?;
+            tx.commit().await?;
+
+            let summary = if settlement.net > 0 {
+                format!("You've won **{}** token(s)!", settlement.net)
+            } else if settlement.net < 0 {
+                format!("You've lost **{}** token(s).", -settlement.net)
+            } else {
+                "You broke even.".to_string()
+            };
+
+            msg.edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(format!(
+                        "The ball lands on **{number}** ({})!\n{}\n\n{summary} You now have **{balance}**.\n{}",
+                        color(number),
+                        results.join("\n"),
+                        fair::footer(nonce)
+                    ))
+                    .components(vec![]),
+            )
+            .await?;
+
+            Ok(())
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Kind;
+
+    #[test]
+    fn payouts() {
+        assert_eq!(Kind::Straight(17).payout(), 35);
+        assert_eq!(Kind::Split(17, 20).payout(), 17);
+        assert_eq!(Kind::Street(16).payout(), 11);
+        assert_eq!(Kind::Corner(17).payout(), 8);
+        assert_eq!(Kind::Line(16).payout(), 5);
+        assert_eq!(Kind::Dozen(2).payout(), 2);
+        assert_eq!(Kind::Column(3).payout(), 2);
+        assert_eq!(Kind::Red.payout(), 1);
+        assert_eq!(Kind::Low.payout(), 1);
+    }
+
+    #[test]
+    fn every_bet_has_the_same_edge() {
+        for input in [
+            "0",
+            "17",
+            "0/2",
+            "17/20",
+            "street 16",
+            "corner 17",
+            "line 16",
+            "dozen 2",
+            "column 3",
+            "red",
+            "black",
+            "odd",
+            "even",
+            "low",
+            "high",
+        ] {
+            let kind = Kind::parse(input).unwrap();
+            let covered = (0..=36).filter(|n| kind.covers(*n)).count() as i32;
+
+            // what a bet pays back on all the pockets it covers, out of 37
+            assert_eq!(covered * (kind.payout() + 1), 36, "{input}");
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert!(Kind::parse("street 17") == Some(Kind::Street(16)));
+        assert!(Kind::parse("20/17") == Some(Kind::Split(17, 20)));
+        assert!(Kind::parse("3/4").is_none());
+        assert!(Kind::parse("corner 3").is_none());
+        assert!(Kind::parse("37").is_none());
+    }
+}
@@ -0,0 +1,247 @@
+use super::{blackjack, mines};
+use crate::{common::Error, games::Game};
+
+use poise::serenity_prelude::{self as serenity, ChannelId, MessageId, UserId};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{types::Json, PgConnection, PgPool, Row};
+use std::future::Future;
+
+/// An interactive game whose state is kept in the sessions table while it is being played,
+/// so that a restart can pick it back up. Every bet made in it is held in escrow until
+/// the game settles.
+#[derive(Clone)]
+pub struct Session {
+    pub id: i32,
+    pub channel: ChannelId,
+    pub message: MessageId,
+}
+
+pub enum Status {
+    Active,
+    Finished,
+    Refunded,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Finished => "finished",
+            Self::Refunded => "refunded",
+        }
+    }
+}
+
+impl Session {
+    /// Record a new game being played on a message
+    pub async fn start(
+        db: &mut PgConnection,
+        game: &Game,
+        channel: ChannelId,
+        message: MessageId,
+        state: &impl Serialize,
+    ) -> Result<Self, Error> {
+        let row = sqlx::query(
+            "INSERT INTO sessions (game, channel, message, state, status) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(game.id as i64)
+        .bind(channel.get() as i64)
+        .bind(message.get() as i64)
+        .bind(Json(state))
+        .bind(Status::Active.as_str())
+        .fetch_one(db)
+        .await?;
+
+        Ok(Self {
+            id: row.try_get(0)?,
+            channel,
+            message,
+        })
+    }
+
+    pub async fn save(&self, db: &mut PgConnection, state: &impl Serialize) -> Result<(), Error> {
+        sqlx::query("UPDATE sessions SET state = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(Json(state))
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn end(&self, db: &mut PgConnection, status: Status) -> Result<(), Error> {
+        sqlx::query("UPDATE sessions SET status = $2 WHERE id = $1")
+            .bind(self.id)
+            .bind(status.as_str())
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// How many of a user's tokens this session is holding
+    pub async fn held(&self, db: &mut PgConnection, user: UserId) -> Result<i32, Error> {
+        match sqlx::query("SELECT amount FROM escrow WHERE session = $1 AND userid = $2")
+            .bind(self.id)
+            .bind(user.get() as i64)
+            .fetch_one(db)
+            .await
+        {
+            Ok(row) => Ok(row.try_get(0)?),
+            Err(sqlx::Error::RowNotFound) => Ok(0),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    /// Hold `amount` of a user's tokens in escrow in total, taking the difference from their balance.
+    /// Returns their balance afterwards.
+    pub async fn hold(
+        &self,
+        db: &mut PgConnection,
+        user: UserId,
+        amount: i32,
+    ) -> Result<i32, Error> {
+        let held = self.held(&mut *db, user).await?;
+        let balance = super::get_balance(user, &mut *db).await? - (amount - held);
+
+        super::change_balance(user, balance, &mut *db).await?;
+        self.escrow(db, user, amount).await?;
+
+        Ok(balance)
+    }
+
+    /// Set how many of a user's tokens are held without touching their balance, for games where
+    /// held tokens change hands between players
+    pub async fn escrow(
+        &self,
+        db: &mut PgConnection,
+        user: UserId,
+        amount: i32,
+    ) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO escrow (session, userid, amount) VALUES ($1, $2, $3) ON CONFLICT (session, userid) DO UPDATE SET amount = EXCLUDED.amount",
+        )
+        .bind(self.id)
+        .bind(user.get() as i64)
+        .bind(amount)
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Take a user's tokens out of escrow so the game can pay them out, returns how many were held
+    pub async fn release(&self, db: &mut PgConnection, user: UserId) -> Result<i32, Error> {
+        let held = self.held(&mut *db, user).await?;
+
+        sqlx::query("DELETE FROM escrow WHERE session = $1 AND userid = $2")
+            .bind(self.id)
+            .bind(user.get() as i64)
+            .execute(db)
+            .await?;
+
+        Ok(held)
+    }
+
+    /// Give every held bet back to its owner and end the session
+    pub async fn refund(&self, db: &mut PgConnection) -> Result<(), Error> {
+        let rows = sqlx::query("DELETE FROM escrow WHERE session = $1 RETURNING userid, amount")
+            .bind(self.id)
+            .fetch_all(&mut *db)
+            .await?;
+
+        for row in rows {
+            let user = UserId::new(row.try_get::<i64, usize>(0)? as u64);
+            let amount: i32 = row.try_get(1)?;
+            let balance = super::get_balance(user, &mut *db).await?;
+
+            super::change_balance(user, balance + amount, &mut *db).await?;
+        }
+
+        self.end(db, Status::Refunded).await
+    }
+
+    /// Play the part of a game that comes after its bets are held. If it fails partway, like
+    /// when Discord can't be reached, every bet still held is refunded before the error is
+    /// passed on, so no tokens are left in escrow.
+    pub async fn refund_on_error<T>(
+        &self,
+        db: &PgPool,
+        game: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let result = game.await;
+
+        if result.is_err() {
+            let mut tx = db.begin().await?;
+
+            let status: String = sqlx::query("SELECT status FROM sessions WHERE id = $1")
+                .bind(self.id)
+                .fetch_one(&mut *tx)
+                .await?
+                .try_get(0)?;
+
+            // a game that already finished has paid everyone out
+            if status == Status::Active.as_str() {
+                self.refund(&mut tx).await?;
+            }
+
+            tx.commit().await?;
+        }
+
+        result
+    }
+}
+
+/// Read the saved state of a session, `None` if it no longer matches the game's types
+pub fn state<T: DeserializeOwned>(value: serde_json::Value) -> Option<T> {
+    serde_json::from_value(value).ok()
+}
+
+/// Resume every game that was still being played when the bot stopped. Games that can't be
+/// resumed have their bets refunded.
+pub async fn resume(ctx: &serenity::Context, db: &PgPool) -> Result<(), Error> {
+    let rows =
+        sqlx::query("SELECT id, game, channel, message, state FROM sessions WHERE status = $1")
+            .bind(Status::Active.as_str())
+            .fetch_all(db)
+            .await?;
+
+    for row in rows {
+        let session = Session {
+            id: row.try_get(0)?,
+            channel: ChannelId::new(row.try_get::<i64, usize>(2)? as u64),
+            message: MessageId::new(row.try_get::<i64, usize>(3)? as u64),
+        };
+
+        let game = row.try_get::<i64, usize>(1)? as u64;
+        let Json(state): Json<serde_json::Value> = row.try_get(4)?;
+
+        let resumed = if game == blackjack::GAME.id {
+            blackjack::resume(ctx, db, session, state)
+        } else if game == mines::GAME.id {
+            mines::resume(ctx, db, session, state)
+        } else {
+            Err(session)
+        };
+
+        if let Err(session) = resumed {
+            let mut tx = db.begin().await?;
+            session.refund(&mut tx).await?;
+            tx.commit().await?;
+
+            // the message might be gone by now, the refund is what matters
+            let _ = session
+                .channel
+                .edit_message(
+                    ctx,
+                    session.message,
+                    serenity::EditMessage::new()
+                        .content("This game was interrupted and every bet has been refunded.")
+                        .components(vec![]),
+                )
+                .await;
+        }
+    }
+
+    Ok(())
+}
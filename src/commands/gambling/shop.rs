@@ -1,14 +1,20 @@
-use super::Item;
+use super::{amount::TokenAmount, Item};
 use crate::common::{Context, Error};
+use crate::games;
 use crate::inventory::Inventory;
 use once_cell::sync::Lazy;
 use poise::serenity_prelude as serenity;
 use std::collections::HashMap;
 
-static ITEMS: Lazy<HashMap<&'static str, (i32, &Item)>> = Lazy::new(|| {
+pub static ITEMS: Lazy<HashMap<&'static str, (i32, &Item)>> = Lazy::new(|| {
     HashMap::from([
         ("Pile of Dirt", (10, &super::items::DIRT)),
         ("Pile of Sand", (10, &super::items::SAND)),
+        ("Wooden Crate", (25, &super::items::WOODEN_CRATE)),
+        ("Golden Crate", (150, &super::items::GOLDEN_CRATE)),
+        ("Magnifying Glass", (40, &super::items::MAGNIFYING_GLASS)),
+        ("Horseshoe", (75, &super::items::HORSESHOE)),
+        ("Safety Net", (30, &super::items::SAFETY_NET)),
     ])
 });
 
@@ -39,28 +45,77 @@ async fn autocomplete_shop<'a>(
         })
 }
 
+/// List the items for sale
 #[poise::command(slash_command, prefix_command)]
-pub async fn buy(
+pub async fn shop(
     ctx: Context<'_>,
-    count: Option<i32>,
-    #[autocomplete = "autocomplete_shop"]
+    #[autocomplete = "games::autocomplete_games"]
     #[rest]
-    item: String,
+    game: Option<String>,
 ) -> Result<(), Error> {
-    let count = count.unwrap_or(1);
+    if let Some(game) = game {
+        match games::get_id(&ctx.data().database, &game).await? {
+            Some(super::ID) => (),
+            Some(_) => {
+                ctx.reply(format!("{game} does not have a shop.")).await?;
+                return Ok(());
+            }
+            None => {
+                ctx.reply(format!("There is no game called {game}."))
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let mut items: Vec<_> = ITEMS.values().collect();
+    items.sort_by_key(|(cost, item)| (*cost, item.name));
 
-    if count < 1 {
-        ctx.reply("Ok, did you REALLY expect me to fall for that for a third time? You've gotta find a new trick.").await?;
-        return Ok(());
+    let mut output = format!("**{} shop**\n", super::GAME.name);
+
+    for (cost, item) in items {
+        output += &format!(
+            "- **{}** ({cost} tokens, {}): {}\n",
+            item.name, item.rarity, item.desc
+        );
     }
 
+    ctx.reply(output).await?;
+
+    Ok(())
+}
+
+/// Buy an item from the shop
+#[poise::command(slash_command, prefix_command)]
+pub async fn buy(
+    ctx: Context<'_>,
+    #[description = "How many to buy, like 5, max or half"] count: Option<TokenAmount>,
+    #[autocomplete = "autocomplete_shop"]
+    #[rest]
+    item: String,
+) -> Result<(), Error> {
     if let Some((price, item)) = ITEMS.get(item.as_str()) {
         let mut tx = ctx.data().database.begin().await?;
 
         let author = ctx.author();
         let balance = super::get_balance(author.id, &mut *tx).await?;
 
-        let total = *price * count;
+        // counts are worked out against how many the author can afford, so max buys as many as possible
+        let count = match count.map(|count| count.of(balance / price)) {
+            Some(Ok(count)) => count,
+            Some(Err(e)) => {
+                ctx.reply(e.to_string()).await?;
+                return Ok(());
+            }
+            None => 1,
+        };
+
+        if count < 1 {
+            ctx.reply("Ok, did you REALLY expect me to fall for that for a third time? You've gotta find a new trick.").await?;
+            return Ok(());
+        }
+
+        let total = price.saturating_mul(count);
 
         if total > balance {
             ctx.reply(format!(
@@ -80,6 +135,14 @@ pub async fn buy(
         }
 
         super::change_balance(author.id, balance - total, &mut *tx).await?;
+        super::achievements::distinct(
+            &mut tx,
+            author.id,
+            ctx.channel_id(),
+            "shop_items",
+            &item.id.to_string(),
+        )
+        .await?;
         tx.commit().await?;
 
         ctx.reply(format!("You have purchased {count}x {}.", item.name))
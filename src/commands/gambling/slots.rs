@@ -0,0 +1,662 @@
+use super::{
+    amount::TokenAmount,
+    effects::{Odds, Settlement},
+    fair,
+};
+use crate::{
+    common::{self, BigBirbError, Context, Error},
+    games::Game,
+    interactions::Route,
+};
+
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{self as serenity, GuildId};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::{Deserialize, Serialize};
+use sqlx::{types::Json, PgExecutor, Row};
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+
+/// The registry entry that slots' history and stats are kept under
+pub const GAME: Game = Game {
+    id: 443,
+    name: "Slots",
+};
+
+/// Matches any symbol in a pay line
+const ANY: &str = "*";
+
+/// The most symbol combinations a configuration can have, so its return can still be
+/// calculated exactly
+const MAX_COMBINATIONS: usize = 100_000;
+
+/// The most times a pay line can pay the bet back
+const MAX_PAYS: u32 = 10_000;
+
+/// The most symbols a configuration can have
+const MAX_SYMBOLS: usize = 20;
+
+/// The most stops a reel can have
+const MAX_STOPS: usize = 100;
+
+/// The most weight a stop can have, so that a reel's weights always add up within a u32
+const MAX_WEIGHT: u32 = 1_000_000;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Symbol {
+    name: String,
+    emoji: String,
+}
+
+/// A position on a reel strip, the reel stops on it with a chance proportional to its weight
+#[derive(Clone, Serialize, Deserialize)]
+struct Stop {
+    symbol: String,
+    weight: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Pay {
+    /// The symbol every reel has to land on, or `*` for any symbol
+    line: Vec<String>,
+    /// How many times the bet is paid back, including the bet itself
+    pays: u32,
+}
+
+impl Pay {
+    fn matches(&self, symbols: &[&str]) -> bool {
+        self.line
+            .iter()
+            .zip(symbols)
+            .all(|(want, got)| want == ANY || want == got)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Config {
+    symbols: Vec<Symbol>,
+    reels: Vec<Vec<Stop>>,
+    /// Checked in order, the first line that matches is paid
+    pays: Vec<Pay>,
+}
+
+impl Config {
+    fn validate(&self) -> Result<(), String> {
+        if self.reels.is_empty() || self.reels.len() > 5 {
+            return Err("A slot machine needs between 1 and 5 reels.".into());
+        }
+
+        if self.symbols.is_empty() || self.symbols.len() > MAX_SYMBOLS {
+            return Err(format!(
+                "A slot machine needs between 1 and {MAX_SYMBOLS} symbols."
+            ));
+        }
+
+        let known = |name: &str| self.symbols.iter().any(|symbol| symbol.name == name);
+
+        for (i, reel) in self.reels.iter().enumerate() {
+            if reel.is_empty() || reel.len() > MAX_STOPS {
+                return Err(format!(
+                    "Reel {} needs between 1 and {MAX_STOPS} stops.",
+                    i + 1
+                ));
+            }
+
+            for stop in reel {
+                if !known(&stop.symbol) {
+                    return Err(format!(
+                        "Reel {} uses the unknown symbol {}.",
+                        i + 1,
+                        stop.symbol
+                    ));
+                }
+
+                if stop.weight == 0 || stop.weight > MAX_WEIGHT {
+                    return Err(format!(
+                        "Reel {} has a stop with a weight outside of 1 to {MAX_WEIGHT}.",
+                        i + 1
+                    ));
+                }
+            }
+        }
+
+        for pay in &self.pays {
+            if pay.line.len() != self.reels.len() {
+                return Err(format!(
+                    "The pay line {} does not have a symbol for every reel.",
+                    pay.line.join(" ")
+                ));
+            }
+
+            if let Some(symbol) = pay.line.iter().find(|s| *s != ANY && !known(s)) {
+                return Err(format!("A pay line uses the unknown symbol {symbol}."));
+            }
+
+            if pay.pays > MAX_PAYS {
+                return Err(format!(
+                    "The pay line {} pays {} times the bet, the most allowed is {MAX_PAYS}.",
+                    pay.line.join(" "),
+                    pay.pays
+                ));
+            }
+        }
+
+        let combinations: usize = self.odds().iter().map(HashMap::len).product();
+
+        if combinations > MAX_COMBINATIONS {
+            return Err(format!(
+                "This machine has {combinations} symbol combinations, the most allowed is {MAX_COMBINATIONS}."
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// The chance of every symbol on each reel
+    fn odds(&self) -> Vec<HashMap<&str, f64>> {
+        self.reels
+            .iter()
+            .map(|reel| {
+                let total: u32 = reel.iter().map(|stop| stop.weight).sum();
+
+                reel.iter().fold(HashMap::new(), |mut acc, stop| {
+                    *acc.entry(stop.symbol.as_str()).or_default() +=
+                        stop.weight as f64 / total as f64;
+                    acc
+                })
+            })
+            .collect()
+    }
+
+    fn pays(&self, symbols: &[&str]) -> u32 {
+        self.pays
+            .iter()
+            .find(|pay| pay.matches(symbols))
+            .map(|pay| pay.pays)
+            .unwrap_or(0)
+    }
+
+    /// What every symbol combination pays, as a multiple of the bet, along with its chance.
+    /// Worked out exactly by going through every combination.
+    fn outcomes(&self) -> Vec<(u32, f64)> {
+        let odds = self.odds();
+        let mut combinations: Vec<(Vec<&str>, f64)> = vec![(vec![], 1.0)];
+
+        for reel in &odds {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|(symbols, chance)| {
+                    reel.iter().map(move |(symbol, p)| {
+                        let mut symbols = symbols.clone();
+                        symbols.push(*symbol);
+                        (symbols, chance * p)
+                    })
+                })
+                .collect();
+        }
+
+        combinations
+            .iter()
+            .map(|(symbols, chance)| (self.pays(symbols), *chance))
+            .collect()
+    }
+
+    /// The theoretical return to player, the share of every token bet that is paid back
+    /// on average
+    fn rtp(&self) -> f64 {
+        self.outcomes()
+            .iter()
+            .map(|(pays, chance)| chance * *pays as f64)
+            .sum()
+    }
+
+    /// The most the machine pays back on average with items, as a percent
+    fn best_return(&self) -> f64 {
+        let outcomes = self.outcomes();
+        let chance = |f: fn(u32) -> bool| -> f64 {
+            outcomes
+                .iter()
+                .filter(|(pays, _)| f(*pays))
+                .map(|(_, chance)| chance)
+                .sum()
+        };
+
+        let odds = Odds {
+            expected: self.rtp(),
+            win: chance(|pays| pays > 1),
+            loss: chance(|pays| pays == 0),
+            winnings: outcomes
+                .iter()
+                .map(|(pays, chance)| chance * pays.saturating_sub(1).min(1) as f64)
+                .sum(),
+        };
+
+        super::best_return(&GAME, |_| odds)
+    }
+
+    fn emoji(&self, name: &str) -> &str {
+        self.symbols
+            .iter()
+            .find(|symbol| symbol.name == name)
+            .map(|symbol| symbol.emoji.as_str())
+            .unwrap_or("?")
+    }
+
+    /// Draw the reels stopped at the given positions, with the symbols above and below
+    /// the pay line showing
+    fn render(&self, stops: &[usize]) -> String {
+        let mut rows = vec![vec![]; 3];
+
+        for (reel, stop) in self.reels.iter().zip(stops) {
+            for (row, offset) in rows.iter_mut().zip([reel.len() - 1, 0, 1]) {
+                row.push(self.emoji(&reel[(stop + offset) % reel.len()].symbol));
+            }
+        }
+
+        format!(
+            "\u{2003}{}\n\u{25B6}{}\u{25C0}\n\u{2003}{}",
+            rows[0].join(" "),
+            rows[1].join(" "),
+            rows[2].join(" ")
+        )
+    }
+}
+
+/// Where each reel stops, given the weight of every stop on it
+pub fn stops(reels: &[Vec<u32>], rng: &mut impl Rng) -> Vec<usize> {
+    reels
+        .iter()
+        .map(|weights| WeightedIndex::new(weights).unwrap().sample(rng))
+        .collect()
+}
+
+static DEFAULT: Lazy<Config> = Lazy::new(|| {
+    let config: Config =
+        serde_json::from_str(include_str!("slots.json")).expect("slots.json is malformed");
+
+    if let Err(e) = config.validate() {
+        panic!("slots.json is invalid: {e}");
+    }
+
+    config
+});
+
+async fn get_config<'a, E>(db: E, guild: Option<GuildId>) -> Result<Config, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let Some(guild) = guild else {
+        return Ok(DEFAULT.clone());
+    };
+
+    match sqlx::query("SELECT config FROM slot_configs WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => {
+            let Json(config): Json<Config> = row.try_get(0)?;
+            Ok(config)
+        }
+        Err(sqlx::Error::RowNotFound) => Ok(DEFAULT.clone()),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// The return to player of a server's slot machine and the most it can be with items, as percents
+pub async fn expected_return<'a, E>(db: E, guild: Option<GuildId>) -> Result<(f64, f64), Error>
+where
+    E: PgExecutor<'a>,
+{
+    let config = get_config(db, guild).await?;
+    Ok((config.rtp() * 100.0, config.best_return()))
+}
+
+/// Pull the lever on a slot machine
+#[poise::command(slash_command, prefix_command, aliases("slot"))]
+pub async fn slots(
+    ctx: Context<'_>,
+    amount: TokenAmount,
+    #[autocomplete = "super::autocomplete_inventory"]
+    #[rest]
+    item: Option<String>,
+) -> Result<(), Error> {
+    let mut tx = ctx.data().database.begin().await?;
+
+    let Some(super::Stake {
+        amount,
+        balance,
+        effects,
+    }) = super::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
+        return Ok(());
+    };
+
+    let config = get_config(&mut *tx, ctx.guild_id()).await?;
+
+    let weights: Vec<Vec<u32>> = config
+        .reels
+        .iter()
+        .map(|reel| reel.iter().map(|stop| stop.weight).collect())
+        .collect();
+
+    let (mut rng, nonce) = fair::next(
+        &mut tx,
+        ctx.author().id,
+        &fair::Draw::Slots {
+            reels: weights.clone(),
+        },
+    )
+    .await?;
+    let stops = stops(&weights, &mut rng);
+
+    // every frame is rolled up front, the reels stop one at a time from left to right,
+    // the reels still spinning are only for show
+    let frames = {
+        let mut rng = rand::thread_rng();
+
+        let frames: Vec<Vec<usize>> = (0..config.reels.len())
+            .map(|stopped| {
+                config
+                    .reels
+                    .iter()
+                    .enumerate()
+                    .map(|(i, reel)| {
+                        if i < stopped {
+                            stops[i]
+                        } else {
+                            rng.gen_range(0..reel.len())
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        frames
+    };
+
+    let symbols: Vec<&str> = config
+        .reels
+        .iter()
+        .zip(&stops)
+        .map(|(reel, stop)| reel[*stop].symbol.as_str())
+        .collect();
+
+    let pays = config.pays(&symbols) as i64;
+    let net = i32::try_from(amount as i64 * pays - amount as i64).unwrap_or(i32::MAX);
+
+    let mut settlement = Settlement::net(amount, net);
+
+    effects.settle(&mut settlement);
+
+    // the balance is written first so that tokens an achievement pays out aren't overwritten
+    let balance = balance.saturating_add(settlement.net);
+    super::change_balance(ctx.author().id, balance, &mut *tx).await?;
+    effects.finish(&mut tx).await?;
+    super::history::record(
+        &mut tx,
+        ctx.author().id,
+        ctx.channel_id(),
+        &GAME,
+        amount,
+        settlement.net,
+    )
+    .await?;
+    tx.commit().await?;
+
+    let msg = ctx.reply(config.render(&frames[0])).await?;
+
+    for frame in &frames[1..] {
+        tokio::time::sleep(Duration::from_millis(700)).await;
+        msg.edit(
+            ctx,
+            poise::CreateReply::default().content(config.render(frame)),
+        )
+        .await?;
+    }
+
+    tokio::time::sleep(Duration::from_millis(700)).await;
+
+    let result = match settlement.net {
+        net if net > 0 => format!("You've won **{net}** token(s)! You now have **{balance}**."),
+        0 => format!("You got your bet back. You have **{balance}** token(s)."),
+        net => format!(
+            "You've lost **{}** token(s), you now have **{balance}**.",
+            -net
+        ),
+    };
+
+    msg.edit(
+        ctx,
+        poise::CreateReply::default().content(format!(
+            "{}\n\n{result}\n{}",
+            config.render(&stops),
+            fair::footer(nonce)
+        )),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn describe(config: &Config) -> String {
+    let mut output = String::from("**Pay table**:\n");
+
+    for pay in &config.pays {
+        let line: Vec<&str> = pay
+            .line
+            .iter()
+            .map(|s| {
+                if s == ANY {
+                    "\u{2754}"
+                } else {
+                    config.emoji(s)
+                }
+            })
+            .collect();
+
+        output += &format!("{} pays {}x\n", line.join(" "), pay.pays);
+    }
+
+    output += &format!(
+        "\n**Theoretical return to player**: {:.2}%",
+        config.rtp() * 100.0
+    );
+
+    output
+}
+
+/// Show this server's slot machine, with its return to player
+#[poise::command(slash_command, prefix_command)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let config = get_config(&ctx.data().database, Some(guild)).await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(describe(&config))
+            .attachment(serenity::CreateAttachment::bytes(
+                serde_json::to_vec_pretty(&config)?,
+                "slots.json",
+            )),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Replace this server's slot machine with one from a JSON file like the one `show` gives
+#[poise::command(slash_command, prefix_command)]
+async fn set(ctx: Context<'_>, config: serenity::Attachment) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    let config: Config = match serde_json::from_slice(&config.download().await?) {
+        Ok(config) => config,
+        Err(e) => {
+            ctx.reply(format!("That is not a slot machine configuration: {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if let Err(e) = config.validate() {
+        ctx.reply(e).await?;
+        return Ok(());
+    }
+
+    let best = config.best_return();
+
+    if best > ctx.data().max_return {
+        ctx.reply(format!(
+            "This slot machine returns {:.2}% of every bet, and {best:.2}% with the luckiest items. The most allowed is {}%.",
+            config.rtp() * 100.0,
+            ctx.data().max_return
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut route = Route::unique("slotconfig");
+    route.restrict(vec![ctx.author().id]);
+
+    let msg = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(format!("{}\n\nSave this slot machine?", describe(&config)))
+                .components(vec![serenity::CreateActionRow::Buttons(vec![
+                    serenity::CreateButton::new(route.id("save"))
+                        .label("Save")
+                        .style(serenity::ButtonStyle::Success),
+                    serenity::CreateButton::new(route.id("cancel"))
+                        .label("Cancel")
+                        .style(serenity::ButtonStyle::Secondary),
+                ])]),
+        )
+        .await?;
+
+    let answer = route.next(Instant::now() + Duration::from_secs(60)).await;
+
+    let saved = match answer {
+        Some((mci, action)) => {
+            mci.create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                .await?;
+            action == "save"
+        }
+        None => false,
+    };
+
+    if saved {
+        sqlx::query("INSERT INTO slot_configs (guildid, config) VALUES ($1, $2) ON CONFLICT (guildid) DO UPDATE SET config = EXCLUDED.config")
+            .bind(guild.get() as i64)
+            .bind(Json(&config))
+            .execute(&ctx.data().database)
+            .await?;
+    }
+
+    msg.edit(
+        ctx,
+        poise::CreateReply::default()
+            .content(format!(
+                "{}\n\n{}",
+                describe(&config),
+                if saved {
+                    "This server's slot machine has been updated."
+                } else {
+                    "The slot machine was not changed."
+                }
+            ))
+            .components(vec![]),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Go back to the default slot machine
+#[poise::command(slash_command, prefix_command)]
+async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    sqlx::query("DELETE FROM slot_configs WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .execute(&ctx.data().database)
+        .await?;
+
+    common::no_ping_reply(&ctx, "This server now uses the default slot machine.").await?;
+
+    Ok(())
+}
+
+/// Configure the reels and pay table of this server's slot machine
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("show", "set", "reset"),
+    subcommand_required
+)]
+pub async fn slotconfig(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, DEFAULT, MAX_STOPS, MAX_WEIGHT};
+
+    fn config(pays: u32) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "symbols": [
+                { "name": "cherry", "emoji": "🍒" },
+                { "name": "lemon", "emoji": "🍋" }
+            ],
+            "reels": [
+                [{ "symbol": "cherry", "weight": 1 }, { "symbol": "lemon", "weight": 3 }],
+                [{ "symbol": "lemon", "weight": 3 }, { "symbol": "cherry", "weight": 1 }]
+            ],
+            "pays": [
+                { "line": ["cherry", "cherry"], "pays": pays },
+                { "line": ["cherry", "*"], "pays": 1 }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn rtp() {
+        // two cherries come up 1 in 16 times, a cherry then a lemon 3 in 16 times
+        assert!((config(13).rtp() - 1.0).abs() < 1e-9);
+        assert!((config(5).rtp() - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn items_raise_the_return() {
+        // the refund buff alone gives back a tenth of the bet on the 3 in 4 spins that lose
+        let config = config(13);
+        assert!(config.best_return() > config.rtp() * 100.0 + 7.5);
+    }
+
+    #[test]
+    fn first_matching_line_pays() {
+        assert_eq!(config(13).pays(&["cherry", "cherry"]), 13);
+        assert_eq!(config(13).pays(&["cherry", "lemon"]), 1);
+        assert_eq!(config(13).pays(&["lemon", "cherry"]), 0);
+    }
+
+    #[test]
+    fn validate() {
+        assert!(config(13).validate().is_ok());
+        assert!(config(10_001).validate().is_err());
+
+        for weight in [0, MAX_WEIGHT + 1, u32::MAX] {
+            let mut config = config(13);
+            config.reels[0][0].weight = weight;
+            assert!(config.validate().is_err());
+        }
+
+        let mut config = config(13);
+        config.reels[1] = vec![config.reels[1][0].clone(); MAX_STOPS + 1];
+        assert!(config.validate().is_err());
+        assert!(DEFAULT.rtp() < 1.0);
+    }
+}
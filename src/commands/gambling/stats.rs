@@ -0,0 +1,237 @@
+use crate::{
+    common::{self, Context, Error},
+    games::Game,
+};
+
+use poise::serenity_prelude::{self as serenity, ChannelId, UserId};
+use sqlx::{types::Json, PgConnection, PgExecutor, Row};
+use std::collections::BTreeMap;
+
+/// Everything a user has done in one game
+struct Stats {
+    /// The display name of the game
+    game: String,
+    bets: i32,
+    wins: i32,
+    losses: i32,
+    pushes: i32,
+    wagered: i64,
+    net: i64,
+    biggest_win: i32,
+    biggest_loss: i32,
+    /// Counters only some games keep, like naturals in blackjack
+    counters: BTreeMap<String, i32>,
+}
+
+/// Add a settled bet to a user's stats for a game. Bets of 0 tokens, like a lottery win,
+/// count toward the results but not as a bet.
+pub async fn add(
+    db: &mut PgConnection,
+    user: UserId,
+    game: &Game,
+    bet: i32,
+    net: i32,
+) -> Result<(), Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO gambling_stats (userid, game, bets, wins, losses, pushes, wagered, net, biggest_win, biggest_loss)
+        VALUES ($1, $2, ($3::INT > 0)::INT, ($4::INT > 0)::INT, ($4::INT < 0)::INT, ($3::INT > 0 AND $4::INT = 0)::INT, $3::INT, $4::INT, GREATEST($4::INT, 0), GREATEST(-$4::INT, 0))
+        ON CONFLICT (userid, game) DO UPDATE SET
+            bets = gambling_stats.bets + EXCLUDED.bets,
+            wins = gambling_stats.wins + EXCLUDED.wins,
+            losses = gambling_stats.losses + EXCLUDED.losses,
+            pushes = gambling_stats.pushes + EXCLUDED.pushes,
+            wagered = gambling_stats.wagered + EXCLUDED.wagered,
+            net = gambling_stats.net + EXCLUDED.net,
+            biggest_win = GREATEST(gambling_stats.biggest_win, EXCLUDED.biggest_win),
+            biggest_loss = GREATEST(gambling_stats.biggest_loss, EXCLUDED.biggest_loss)
+        "#,
+    )
+    .bind(user.get() as i64)
+    .bind(game.id as i64)
+    .bind(bet)
+    .bind(net)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Add to the counters a game keeps on top of the usual stats, like `("busts", 1)`.
+/// The bet they came from has to be recorded first. Every counter is also an achievement
+/// event named after the game, like `blackjack_busts`.
+pub async fn count(
+    db: &mut PgConnection,
+    user: UserId,
+    channel: ChannelId,
+    game: &Game,
+    counters: &[(&str, i32)],
+) -> Result<(), Error> {
+    for (name, n) in counters.iter().filter(|(_, n)| *n != 0) {
+        sqlx::query("UPDATE gambling_stats SET counters = counters || jsonb_build_object($3::TEXT, COALESCE((counters->>$3)::INT, 0) + $4) WHERE userid = $1 AND game = $2")
+            .bind(user.get() as i64)
+            .bind(game.id as i64)
+            .bind(*name)
+            .bind(*n)
+            .execute(&mut *db)
+            .await?;
+
+        super::achievements::add(
+            &mut *db,
+            user,
+            channel,
+            &format!("{}_{name}", game.key()),
+            *n as i64,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn get_stats<'a, E: PgExecutor<'a>>(db: E, user: UserId) -> Result<Vec<Stats>, Error> {
+    let rows = sqlx::query("SELECT games.name, bets, wins, losses, pushes, wagered, net, biggest_win, biggest_loss, counters FROM gambling_stats JOIN games ON games.id = gambling_stats.game WHERE userid = $1 ORDER BY bets DESC, games.name")
+        .bind(user.get() as i64)
+        .fetch_all(db)
+        .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(Stats {
+                game: row.try_get(0)?,
+                bets: row.try_get(1)?,
+                wins: row.try_get(2)?,
+                losses: row.try_get(3)?,
+                pushes: row.try_get(4)?,
+                wagered: row.try_get(5)?,
+                net: row.try_get(6)?,
+                biggest_win: row.try_get(7)?,
+                biggest_loss: row.try_get(8)?,
+                counters: row.try_get::<Json<_>, _>(9)?.0,
+            })
+        })
+        .collect()
+}
+
+fn title(name: &str) -> String {
+    let mut chars = name.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl Stats {
+    fn win_rate(&self) -> f64 {
+        match self.wins + self.losses {
+            0 => 0.0,
+            decided => self.wins as f64 / decided as f64 * 100.0,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut output = format!(
+            "**Bets**: {}\n**Wins**: {}, **Losses**: {}, **Pushes**: {} ({:.1}% won)\n**Wagered**: {}\n**Net profit**: {:+}\n**Biggest win**: {}\n**Biggest loss**: {}\n",
+            self.bets,
+            self.wins,
+            self.losses,
+            self.pushes,
+            self.win_rate(),
+            self.wagered,
+            self.net,
+            self.biggest_win,
+            self.biggest_loss
+        );
+
+        for (name, n) in &self.counters {
+            output += &format!("**{}**: {n}\n", title(name));
+        }
+
+        output
+    }
+}
+
+async fn autocomplete_game<'a>(
+    ctx: Context<'a>,
+    partial: &'a str,
+) -> impl Iterator<Item = serenity::AutocompleteChoice> + use<'a> {
+    let games: Vec<String> = sqlx::query("SELECT DISTINCT games.name FROM gambling_stats JOIN games ON games.id = gambling_stats.game ORDER BY games.name")
+        .fetch_all(&ctx.data().database)
+        .await
+        .map(|rows| {
+            rows.into_iter()
+                .filter_map(|row| row.try_get(0).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    games
+        .into_iter()
+        .filter(move |game| game.to_lowercase().contains(&partial.to_lowercase()))
+        .map(|game| serenity::AutocompleteChoice::new(game.clone(), game))
+}
+
+/// See how your or someone else's bets have gone, overall or in one game
+#[poise::command(slash_command, prefix_command)]
+pub async fn stats(
+    ctx: Context<'_>,
+    user: Option<serenity::User>,
+    #[autocomplete = "autocomplete_game"] game: Option<String>,
+) -> Result<(), Error> {
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let stats = get_stats(&ctx.data().database, user.id).await?;
+
+    let whose = if user.id == ctx.author().id {
+        "You haven't".to_string()
+    } else {
+        format!("{user} hasn't")
+    };
+
+    let output = match game.as_deref().map(str::trim) {
+        Some(game) => match stats
+            .iter()
+            .find(|stats| stats.game.eq_ignore_ascii_case(game))
+        {
+            Some(stats) => format!(
+                "**{}** stats for {user}\n\n{}",
+                stats.game,
+                stats.describe()
+            ),
+            None => format!("{whose} played {game} yet."),
+        },
+        None if stats.is_empty() => format!("{whose} gambled yet."),
+        None => {
+            let total = Stats {
+                game: String::new(),
+                bets: stats.iter().map(|s| s.bets).sum(),
+                wins: stats.iter().map(|s| s.wins).sum(),
+                losses: stats.iter().map(|s| s.losses).sum(),
+                pushes: stats.iter().map(|s| s.pushes).sum(),
+                wagered: stats.iter().map(|s| s.wagered).sum(),
+                net: stats.iter().map(|s| s.net).sum(),
+                biggest_win: stats.iter().map(|s| s.biggest_win).max().unwrap_or(0),
+                biggest_loss: stats.iter().map(|s| s.biggest_loss).max().unwrap_or(0),
+                counters: BTreeMap::new(),
+            };
+
+            let mut output = format!("Gambling stats for {user}\n\n{}\n", total.describe());
+
+            for stats in &stats {
+                output += &format!(
+                    "**{}**: {} bet(s), {:.1}% won, {:+} net\n",
+                    stats.game,
+                    stats.bets,
+                    stats.win_rate(),
+                    stats.net
+                );
+            }
+
+            output
+        }
+    };
+
+    common::no_ping_reply(&ctx, output).await?;
+
+    Ok(())
+}
@@ -1,116 +1,104 @@
-use super::Effect;
+use super::{
+    amount::TokenAmount,
+    effects::{Hook, Outcome, Roll, Settlement},
+    fair, history,
+};
 use crate::{
+    commands::settings,
     common::{Context, Error},
-    inventory::Inventory,
+    games::Game,
 };
 use rand::Rng;
 
+/// The registry entry that wager's history and stats are kept under
+pub const GAME: Game = Game {
+    id: 452,
+    name: "Wager",
+};
+
 /// Put forward an amount of tokens to either lose or earn
 #[poise::command(slash_command, prefix_command, aliases("w"))]
 pub async fn wager(
     ctx: Context<'_>,
-    amount: String,
+    amount: TokenAmount,
     #[autocomplete = "super::autocomplete_inventory"]
     #[rest]
     item: Option<String>,
 ) -> Result<(), Error> {
     let mut tx = ctx.data().database.begin().await?;
-    let mut balance = super::get_balance(ctx.author().id, &mut *tx).await?;
-
-    let amount = match amount.to_lowercase().as_str() {
-        "all" => balance,
-        "half" => balance / 2,
-        input => {
-            if input.ends_with('%') {
-                let percent: f64 = match input[..input.len() - 1].parse::<f64>() {
-                    Ok(x) => x,
-                    Err(_) => {
-                        ctx.reply(format!("{input} is not a valid percent."))
-                            .await?;
-                        return Ok(());
-                    }
-                } / 100f64;
-
-                (balance as f64 * percent) as i32
-            } else {
-                match input.parse() {
-                    Ok(n) => n,
-                    Err(_) => {
-                        ctx.reply("Any one of a number, all, half, or a percent are allowed as arguments.").await?;
-                        return Ok(());
-                    }
-                }
-            }
-        }
-    };
 
-    if amount < 1 {
-        ctx.reply("You cannot wager less than 1 token.").await?;
+    let Some(super::Stake {
+        amount,
+        balance,
+        effects,
+    }) = super::stake(ctx, &mut tx, &GAME, &amount, item.as_deref()).await?
+    else {
         return Ok(());
-    }
+    };
 
-    if balance < amount {
-        ctx.reply(format!(
-            "You do not have enough tokens (**{balance}**) to wager this amount."
-        ))
-        .await?;
-        return Ok(());
-    }
+    let (chance, payout) = settings::get_wager_odds(&mut *tx, ctx.guild_id()).await?;
 
-    let item = if let Some(item) = item {
-        let inventory = Inventory::new(ctx.author().id, Some(super::ID));
+    let mut roll = Roll {
+        chance: chance as f64 / 100.0,
+        bet: amount,
+    };
+    effects.before_roll(&mut roll);
 
-        match super::items::get_item_by_name(&item) {
-            Some(item) => {
-                if let Some(item) = inventory.get_item_of_type(&mut *tx, item.id).await? {
-                    inventory.remove_item(&mut *tx, item.id).await?;
-                } else {
-                    ctx.reply(format!("You do not have a(n) {} to use.", item.name))
-                        .await?;
-                    return Ok(());
-                }
+    let (mut rng, nonce) = fair::next(
+        &mut tx,
+        ctx.author().id,
+        &fair::Draw::Wager {
+            chance: roll.chance,
+        },
+    )
+    .await?;
 
-                Some(item)
-            }
-            None => {
-                ctx.reply(format!("item {item} does not exist.")).await?;
-                return Ok(());
-            }
-        }
+    // rolled as a number rather than with `gen_bool` so that it can be checked against the chance by hand
+    let mut settlement = if rng.r#gen::<f64>() < roll.chance {
+        // a big bet at a high payout doesn't always fit in an i32
+        let won = amount as i64 * payout as i64 / 100;
+        Settlement::win(amount, i32::try_from(won).unwrap_or(i32::MAX))
     } else {
-        None
+        Settlement::loss(amount)
     };
 
-    let (multiplier, chance) = item
-        .map(|item| {
-            item.effects
-                .iter()
-                .fold((1.0, 0.5), |(m, c), effect| match effect {
-                    Effect::Multiplier(m) => (*m, c),
-                    Effect::Chance(c) => (m, *c),
-                })
-        })
-        .unwrap_or((1.0, 0.5));
-
-    if rand::thread_rng().gen_bool(chance) {
-        let win = (amount as f64 * multiplier) as i32;
-        balance += win;
-        ctx.reply(format!(
-            "You just gained **{}** token(s)! You now have **{}**.",
-            win, balance
-        ))
-        .await?;
-    } else {
-        balance -= amount;
-        ctx.reply(format!(
-            "You've lost **{}** token(s), you now have **{}**.",
-            amount, balance
-        ))
-        .await?;
-    }
+    effects.settle(&mut settlement);
 
+    // the balance is written first so that tokens an achievement pays out aren't overwritten
+    let balance = balance.saturating_add(settlement.net);
     super::change_balance(ctx.author().id, balance, &mut *tx).await?;
+    effects.finish(&mut *tx).await?;
+    history::record(
+        &mut tx,
+        ctx.author().id,
+        ctx.channel_id(),
+        &GAME,
+        amount,
+        settlement.net,
+    )
+    .await?;
     tx.commit().await?;
 
+    match settlement.outcome {
+        Outcome::Win => {
+            ctx.reply(format!(
+                "You just gained **{}** token(s)! You now have **{}**.\n{}",
+                settlement.net,
+                balance,
+                fair::footer(nonce)
+            ))
+            .await?;
+        }
+        _ => {
+            ctx.reply(format!(
+                "You've lost **{}** token(s), you now have **{}**.\n{}",
+                -settlement.net,
+                balance,
+                fair::footer(nonce)
+            ))
+            .await?;
+        }
+    }
+
     Ok(())
 }
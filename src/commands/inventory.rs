@@ -0,0 +1,77 @@
+use crate::{
+    common::{self, Context, Error},
+    games,
+    inventory::Inventory,
+};
+
+use poise::serenity_prelude::{self as serenity, futures::StreamExt};
+use std::collections::BTreeMap;
+
+/// Lists the items in your or someone else's inventory
+#[poise::command(slash_command, prefix_command, aliases("inv"))]
+pub async fn inventory(
+    ctx: Context<'_>,
+    user: Option<serenity::User>,
+    #[autocomplete = "games::autocomplete_games"]
+    #[rest]
+    game: Option<String>,
+) -> Result<(), Error> {
+    let user = user.as_ref().unwrap_or(ctx.author());
+    let db = &ctx.data().database;
+
+    let game = match game {
+        Some(game) => match games::get_id(db, &game).await? {
+            Some(id) => Some(id),
+            None => {
+                ctx.reply(format!("There is no game called {game}."))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let stacks = Inventory::new(user.id, game)
+        .items(db)
+        .await
+        .fold(
+            BTreeMap::<(i64, String), usize>::new(),
+            |mut acc, item| async {
+                if let Ok(item) = item {
+                    *acc.entry((item.game, item.name)).or_default() += 1;
+                }
+                acc
+            },
+        )
+        .await;
+
+    let who = if user.id == ctx.author().id {
+        "You have".to_string()
+    } else {
+        format!("{} has", user)
+    };
+
+    if stacks.is_empty() {
+        common::no_ping_reply(&ctx, format!("{who} no items.")).await?;
+        return Ok(());
+    }
+
+    let mut output = format!("{who}:\n");
+    let mut current = None;
+
+    for ((game, name), count) in stacks {
+        if current != Some(game) {
+            let title = games::get_name(db, game as u64)
+                .await?
+                .unwrap_or(format!("Game {game}"));
+            output += &format!("**{title}**\n");
+            current = Some(game);
+        }
+
+        output += &format!("- {count}x {name}\n");
+    }
+
+    common::no_ping_reply(&ctx, output).await?;
+
+    Ok(())
+}
@@ -4,6 +4,7 @@ mod administration;
 mod dox;
 mod eval;
 mod gambling;
+mod inventory;
 mod ping;
 pub mod self_roles;
 mod settings;
@@ -11,6 +12,9 @@ mod version;
 mod yeehaw;
 
 use crate::common::{Context, Data, Error};
+use crate::games::Game;
+use poise::serenity_prelude as serenity;
+use sqlx::PgPool;
 
 /// Display a help menu
 #[poise::command(prefix_command, slash_command)]
@@ -29,6 +33,37 @@ async fn help(
     Ok(())
 }
 
+/// Every game that is registered in the games table at startup
+pub fn games() -> Vec<Game> {
+    vec![
+        gambling::GAME,
+        gambling::wager::GAME,
+        gambling::blackjack::GAME,
+        gambling::roulette::GAME,
+        gambling::slots::GAME,
+        gambling::duel::GAME,
+        gambling::poker::GAME,
+        gambling::crash::GAME,
+        gambling::race::GAME,
+        gambling::lottery::GAME,
+        gambling::dice::craps::GAME,
+        gambling::dice::highlow::GAME,
+        gambling::mines::GAME,
+    ]
+}
+
+/// Pick up or refund every game that was still being played when the bot stopped
+pub async fn resume_sessions(ctx: &serenity::Context, db: &PgPool) -> Result<(), Error> {
+    gambling::session::resume(ctx, db).await
+}
+
+/// Start the background tasks that run scheduled events
+pub fn start_schedules(ctx: &serenity::Context, db: &PgPool) {
+    gambling::race::run_schedule(ctx.clone(), db.clone());
+    gambling::lottery::run_schedule(ctx.clone(), db.clone());
+    gambling::achievements::run_schedule(ctx.clone(), db.clone());
+}
+
 pub fn commands() -> Vec<Command<Data, Error>> {
     vec![
         help(),
@@ -38,10 +73,34 @@ pub fn commands() -> Vec<Command<Data, Error>> {
         gambling::balance::balance(),
         gambling::give::give(),
         gambling::wager::wager(),
+        gambling::duel::duel(),
         gambling::daily::daily(),
         gambling::leaderboard::leaderboard(),
         gambling::shop::buy(),
+        gambling::shop::shop(),
+        inventory::inventory(),
         gambling::blackjack::blackjack(),
+        gambling::roulette::roulette(),
+        gambling::poker::poker(),
+        gambling::crash::crash(),
+        gambling::race::race(),
+        gambling::lottery::lottery(),
+        gambling::dice::craps::craps(),
+        gambling::dice::highlow::highlow(),
+        gambling::mines::mines(),
+        gambling::fair::fair(),
+        gambling::fair::verify(),
+        gambling::odds::odds(),
+        gambling::limits::limits(),
+        gambling::stats::stats(),
+        gambling::achievements::achievements(),
+        gambling::slots::slots(),
+        gambling::slots::slotconfig(),
+        gambling::loot::open(),
+        gambling::loot::droprates(),
+        gambling::craft::craft(),
+        gambling::craft::recipes(),
+        gambling::effects::buffs(),
         eval::eval(),
         self_roles::role(),
         self_roles::editrole(),
@@ -1,4 +1,7 @@
-use crate::common::{Context, Error};
+use crate::{
+    commands::gambling::achievements,
+    common::{Context, Error},
+};
 use poise::serenity_prelude::{EditRole, GuildId, Permissions, RoleId, UserId};
 use sqlx::{PgConnection, Row};
 
@@ -76,6 +79,7 @@ async fn create_role(
     role.edit(ctx, edit).await?;
     member.add_role(ctx, &role).await?;
     update_user_role(user, guild, role.id, db).await?;
+    achievements::add(db, user, ctx.channel_id(), "self_roles", 1).await?;
 
     Ok(role.id)
 }
@@ -1,7 +1,10 @@
-use crate::common::{self, BigBirbError, Context, Error};
+use crate::{
+    commands::gambling::{self, blackjack::Rules, crash, effects::Odds, wager},
+    common::{self, BigBirbError, Context, Error},
+};
 
-use poise::serenity_prelude::{GuildId, Role, RoleId};
-use sqlx::Row;
+use poise::serenity_prelude::{GuildId, Role, RoleId, UserId};
+use sqlx::{types::Json, PgExecutor, Row};
 
 async fn get_prefix(ctx: Context<'_>, guild: GuildId) -> Result<Option<String>, Error> {
     let db = &ctx.data().database;
@@ -240,10 +243,544 @@ pub async fn banrole(ctx: Context<'_>, role: Option<Role>) -> Result<(), Error>
     Ok(())
 }
 
+pub async fn get_text_only<'a, E>(db: E, user: UserId) -> Result<bool, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let text_only: Option<bool> =
+        match sqlx::query("SELECT text_only FROM user_settings WHERE userid = $1")
+            .bind(user.get() as i64)
+            .fetch_one(db)
+            .await
+        {
+            Ok(r) => r.get(0),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+    Ok(text_only.unwrap_or(false))
+}
+
+/// Show games as text instead of images, this is a setting for you and not the server
+#[poise::command(prefix_command, slash_command)]
+pub async fn textonly(ctx: Context<'_>, text_only: Option<bool>) -> Result<(), Error> {
+    let user = ctx.author().id;
+
+    match text_only {
+        Some(text_only) => {
+            let mut tx = ctx.data().database.begin().await?;
+
+            sqlx::query("INSERT INTO user_settings (userid, text_only) VALUES ($1, $2) ON CONFLICT (userid) DO UPDATE SET text_only = EXCLUDED.text_only")
+                .bind(user.get() as i64)
+                .bind(text_only)
+                .execute(&mut *tx).await?;
+
+            tx.commit().await?;
+
+            if text_only {
+                ctx.reply("Games will now be shown to you as text.").await?;
+            } else {
+                ctx.reply("Games will now be shown to you as images.")
+                    .await?;
+            }
+        }
+        None => {
+            let s = if get_text_only(&ctx.data().database, user).await? {
+                "enabled"
+            } else {
+                "disabled"
+            };
+
+            ctx.reply(format!("Text only games are {s} for you."))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_duel_cut<'a, E>(db: E, guild: GuildId) -> Result<i32, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let cut: Option<i32> = match sqlx::query("SELECT duel_cut FROM settings WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .fetch_one(db)
+        .await
+    {
+        Ok(r) => r.get(0),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    Ok(cut.unwrap_or(0))
+}
+
+/// The percent of every duel's pot the house keeps
+#[poise::command(prefix_command, slash_command)]
+pub async fn duelcut(
+    ctx: Context<'_>,
+    #[min = 0]
+    #[max = 50]
+    percent: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    match percent {
+        Some(percent) => {
+            let member = ctx.author_member().await.unwrap();
+
+            if !member.permissions(ctx).iter().any(|p| p.manage_guild()) {
+                ctx.reply("You do not have permission to change this setting.")
+                    .await?;
+                return Ok(());
+            }
+
+            if !(0..=50).contains(&percent) {
+                ctx.reply("The house cut has to be between 0% and 50%.")
+                    .await?;
+                return Ok(());
+            }
+
+            if (100 - percent) as f64 > ctx.data().max_return {
+                ctx.reply(format!(
+                    "That would pay back {}% of every duel's pot, the most allowed is {}%.",
+                    100 - percent,
+                    ctx.data().max_return
+                ))
+                .await?;
+                return Ok(());
+            }
+
+            let mut tx = ctx.data().database.begin().await?;
+
+            sqlx::query("INSERT INTO settings (guildid, duel_cut) VALUES ($1, $2) ON CONFLICT (guildid) DO UPDATE SET duel_cut = EXCLUDED.duel_cut")
+                .bind(guild.get() as i64)
+                .bind(percent)
+                .execute(&mut *tx).await?;
+
+            tx.commit().await?;
+
+            ctx.reply(format!(
+                "The house now keeps {percent}% of every duel's pot."
+            ))
+            .await?;
+        }
+        None => {
+            let percent = get_duel_cut(&ctx.data().database, guild).await?;
+            ctx.reply(format!("The house keeps {percent}% of every duel's pot."))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_crash_edge<'a, E>(db: E, guild: GuildId) -> Result<i32, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let edge: Option<i32> = match sqlx::query("SELECT crash_edge FROM settings WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .fetch_one(db)
+        .await
+    {
+        Ok(r) => r.get(0),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    Ok(edge.unwrap_or(1))
+}
+
+/// The house edge of crash rounds, as a percent
+#[poise::command(prefix_command, slash_command)]
+pub async fn crashedge(
+    ctx: Context<'_>,
+    #[min = 0]
+    #[max = 10]
+    percent: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    match percent {
+        Some(percent) => {
+            let member = ctx.author_member().await.unwrap();
+
+            if !member.permissions(ctx).iter().any(|p| p.manage_guild()) {
+                ctx.reply("You do not have permission to change this setting.")
+                    .await?;
+                return Ok(());
+            }
+
+            if !(0..=10).contains(&percent) {
+                ctx.reply("The house edge has to be between 0% and 10%.")
+                    .await?;
+                return Ok(());
+            }
+
+            let best = crash::best_return(percent);
+
+            if best > ctx.data().max_return {
+                ctx.reply(format!(
+                    "That would give crash rounds an expected return of {}%, and {best:.2}% with the luckiest items. The most allowed is {}%.",
+                    100 - percent,
+                    ctx.data().max_return
+                ))
+                .await?;
+                return Ok(());
+            }
+
+            let mut tx = ctx.data().database.begin().await?;
+
+            sqlx::query("INSERT INTO settings (guildid, crash_edge) VALUES ($1, $2) ON CONFLICT (guildid) DO UPDATE SET crash_edge = EXCLUDED.crash_edge")
+                .bind(guild.get() as i64)
+                .bind(percent)
+                .execute(&mut *tx).await?;
+
+            tx.commit().await?;
+
+            ctx.reply(format!("Crash rounds now have a {percent}% house edge."))
+                .await?;
+        }
+        None => {
+            let percent = get_crash_edge(&ctx.data().database, guild).await?;
+            ctx.reply(format!("Crash rounds have a {percent}% house edge."))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_race_cut<'a, E>(db: E, guild: GuildId) -> Result<i32, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let cut: Option<i32> = match sqlx::query("SELECT race_cut FROM settings WHERE guildid = $1")
+        .bind(guild.get() as i64)
+        .fetch_one(db)
+        .await
+    {
+        Ok(r) => r.get(0),
+        Err(sqlx::Error::RowNotFound) => None,
+        Err(e) => return Err(Box::new(e)),
+    };
+
+    Ok(cut.unwrap_or(5))
+}
+
+/// The percent of every race's betting pool the house keeps
+#[poise::command(prefix_command, slash_command)]
+pub async fn racecut(
+    ctx: Context<'_>,
+    #[min = 0]
+    #[max = 50]
+    percent: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+
+    match percent {
+        Some(percent) => {
+            let member = ctx.author_member().await.unwrap();
+
+            if !member.permissions(ctx).iter().any(|p| p.manage_guild()) {
+                ctx.reply("You do not have permission to change this setting.")
+                    .await?;
+                return Ok(());
+            }
+
+            if !(0..=50).contains(&percent) {
+                ctx.reply("The house cut has to be between 0% and 50%.")
+                    .await?;
+                return Ok(());
+            }
+
+            if (100 - percent) as f64 > ctx.data().max_return {
+                ctx.reply(format!(
+                    "That would pay back {}% of every race's pool, the most allowed is {}%.",
+                    100 - percent,
+                    ctx.data().max_return
+                ))
+                .await?;
+                return Ok(());
+            }
+
+            let mut tx = ctx.data().database.begin().await?;
+
+            sqlx::query("INSERT INTO settings (guildid, race_cut) VALUES ($1, $2) ON CONFLICT (guildid) DO UPDATE SET race_cut = EXCLUDED.race_cut")
+                .bind(guild.get() as i64)
+                .bind(percent)
+                .execute(&mut *tx).await?;
+
+            tx.commit().await?;
+
+            ctx.reply(format!(
+                "The house now keeps {percent}% of every race's pool."
+            ))
+            .await?;
+        }
+        None => {
+            let percent = get_race_cut(&ctx.data().database, guild).await?;
+            ctx.reply(format!("The house keeps {percent}% of every race's pool."))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The chance to win a wager and what it pays on top of the bet, both as percents.
+/// Games played outside of a server use the defaults.
+pub async fn get_wager_odds<'a, E>(db: E, guild: Option<GuildId>) -> Result<(i32, i32), Error>
+where
+    E: PgExecutor<'a>,
+{
+    let Some(guild) = guild else {
+        return Ok((50, 100));
+    };
+
+    let (chance, payout): (Option<i32>, Option<i32>) =
+        match sqlx::query("SELECT wager_chance, wager_payout FROM settings WHERE guildid = $1")
+            .bind(guild.get() as i64)
+            .fetch_one(db)
+            .await
+        {
+            Ok(r) => (r.get(0), r.get(1)),
+            Err(sqlx::Error::RowNotFound) => (None, None),
+            Err(e) => return Err(Box::new(e)),
+        };
+
+    Ok((chance.unwrap_or(50), payout.unwrap_or(100)))
+}
+
+/// The expected return of a wager, as a percent of the bet
+pub fn wager_return(chance: i32, payout: i32) -> f64 {
+    chance as f64 * (100 + payout) as f64 / 100.0
+}
+
+/// The expected return of a wager made with the item and buffs that add the most to it, as a
+/// percent of the bet
+pub fn best_wager_return(chance: i32, payout: i32) -> f64 {
+    let payout = payout as f64 / 100.0;
+
+    gambling::best_return(&wager::GAME, |extra| {
+        let win = (chance as f64 / 100.0 + extra).min(1.0);
+
+        Odds {
+            expected: win * (1.0 + payout),
+            win,
+            loss: 1.0 - win,
+            winnings: win * payout.min(1.0),
+        }
+    })
+}
+
+/// The chance to win a wager and what a win pays on top of the bet, as percents
+#[poise::command(prefix_command, slash_command)]
+pub async fn wager(
+    ctx: Context<'_>,
+    #[description = "The chance to win (1-99)"]
+    #[min = 1]
+    #[max = 99]
+    chance: Option<i32>,
+    #[description = "What a win pays on top of the bet, as a percent of it"]
+    #[min = 1]
+    #[max = 10000]
+    payout: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let db = &ctx.data().database;
+    let (current_chance, current_payout) = get_wager_odds(db, Some(guild)).await?;
+
+    if chance.is_none() && payout.is_none() {
+        ctx.reply(format!(
+            "Wagers have a {current_chance}% chance to win and pay {current_payout}% of the bet, an expected return of {:.2}%.",
+            wager_return(current_chance, current_payout)
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let member = ctx.author_member().await.unwrap();
+
+    if !member.permissions(ctx).iter().any(|p| p.manage_guild()) {
+        ctx.reply("You do not have permission to change this setting.")
+            .await?;
+        return Ok(());
+    }
+
+    let chance = chance.unwrap_or(current_chance);
+    let payout = payout.unwrap_or(current_payout);
+
+    if !(1..=99).contains(&chance) || !(1..=10000).contains(&payout) {
+        ctx.reply("The chance has to be between 1% and 99%, and the payout between 1% and 10000%.")
+            .await?;
+        return Ok(());
+    }
+
+    let expected = wager_return(chance, payout);
+    let best = best_wager_return(chance, payout);
+
+    if best > ctx.data().max_return {
+        ctx.reply(format!(
+            "That would give wagers an expected return of {expected:.2}%, and {best:.2}% with the luckiest items. The most allowed is {}%.",
+            ctx.data().max_return
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("INSERT INTO settings (guildid, wager_chance, wager_payout) VALUES ($1, $2, $3) ON CONFLICT (guildid) DO UPDATE SET wager_chance = EXCLUDED.wager_chance, wager_payout = EXCLUDED.wager_payout")
+        .bind(guild.get() as i64)
+        .bind(chance)
+        .bind(payout)
+        .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    ctx.reply(format!(
+        "Wagers now have a {chance}% chance to win and pay {payout}% of the bet, an expected return of {expected:.2}%."
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// The rules blackjack is played with. Games played outside of a server use the defaults.
+pub async fn get_blackjack_rules<'a, E>(db: E, guild: Option<GuildId>) -> Result<Rules, Error>
+where
+    E: PgExecutor<'a>,
+{
+    let Some(guild) = guild else {
+        return Ok(Rules::default());
+    };
+
+    let rules: Option<Json<Rules>> =
+        match sqlx::query("SELECT blackjack_rules FROM settings WHERE guildid = $1")
+            .bind(guild.get() as i64)
+            .fetch_one(db)
+            .await
+        {
+            Ok(r) => r.get(0),
+            Err(sqlx::Error::RowNotFound) => None,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+    Ok(rules.map(|Json(rules)| rules).unwrap_or_default())
+}
+
+/// The rules blackjack is played with, anything left out stays as it is
+#[poise::command(prefix_command, slash_command)]
+pub async fn blackjack(
+    ctx: Context<'_>,
+    #[description = "How many decks are in the shoe (1-8)"]
+    #[min = 1]
+    #[max = 8]
+    decks: Option<usize>,
+    #[description = "Whether the dealer hits on soft 17"] soft17: Option<bool>,
+    #[description = "The most hands a player can split into (1-4)"]
+    #[min = 1]
+    #[max = 4]
+    hands: Option<usize>,
+    #[description = "Whether players can double after splitting"] das: Option<bool>,
+    #[description = "Whether players can surrender their first two cards"] surrender: Option<bool>,
+    #[description = "What a natural pays, as a percent of the bet (100-200)"]
+    #[min = 100]
+    #[max = 200]
+    natural: Option<i32>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().ok_or(BigBirbError::GuildOnly)?;
+    let db = &ctx.data().database;
+    let mut rules = get_blackjack_rules(db, Some(guild)).await?;
+
+    if decks.is_none()
+        && soft17.is_none()
+        && hands.is_none()
+        && das.is_none()
+        && surrender.is_none()
+        && natural.is_none()
+    {
+        ctx.reply(format!(
+            "Blackjack is played with {}. The expected return is about {:.2}%.",
+            rules.describe(),
+            rules.expected_return()
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let member = ctx.author_member().await.unwrap();
+
+    if !member.permissions(ctx).iter().any(|p| p.manage_guild()) {
+        ctx.reply("You do not have permission to change this setting.")
+            .await?;
+        return Ok(());
+    }
+
+    rules.decks = decks.unwrap_or(rules.decks);
+    rules.dealer_hits_soft_17 = soft17.unwrap_or(rules.dealer_hits_soft_17);
+    rules.max_hands = hands.unwrap_or(rules.max_hands);
+    rules.double_after_split = das.unwrap_or(rules.double_after_split);
+    rules.late_surrender = surrender.unwrap_or(rules.late_surrender);
+    rules.natural_payout = natural.unwrap_or(rules.natural_payout);
+
+    if !(1..=8).contains(&rules.decks)
+        || !(1..=4).contains(&rules.max_hands)
+        || !(100..=200).contains(&rules.natural_payout)
+    {
+        ctx.reply("There can be 1 to 8 decks and 1 to 4 hands, and naturals can pay between 100% and 200%.")
+            .await?;
+        return Ok(());
+    }
+
+    let expected = rules.expected_return();
+    let best = rules.best_return();
+
+    if best > ctx.data().max_return {
+        ctx.reply(format!(
+            "Those rules would give blackjack an expected return of about {expected:.2}%, and {best:.2}% with the luckiest items. The most allowed is {}%.",
+            ctx.data().max_return
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut tx = db.begin().await?;
+
+    sqlx::query("INSERT INTO settings (guildid, blackjack_rules) VALUES ($1, $2) ON CONFLICT (guildid) DO UPDATE SET blackjack_rules = EXCLUDED.blackjack_rules")
+        .bind(guild.get() as i64)
+        .bind(Json(&rules))
+        .execute(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    ctx.reply(format!(
+        "Blackjack is now played with {}. The expected return is about {expected:.2}%.",
+        rules.describe()
+    ))
+    .await?;
+
+    Ok(())
+}
+
 #[poise::command(
     prefix_command,
     slash_command,
-    subcommands("prefix", "position", "hoist", "banrole"),
+    subcommands(
+        "prefix",
+        "position",
+        "hoist",
+        "banrole",
+        "textonly",
+        "duelcut",
+        "crashedge",
+        "racecut",
+        "wager",
+        "blackjack"
+    ),
     subcommand_required
 )]
 pub async fn setting(_ctx: Context<'_>) -> Result<(), Error> {
@@ -5,6 +5,8 @@ use std::{error, fmt};
 pub struct Data {
     pub database: Pool<Postgres>,
     pub prefix: Option<String>,
+    /// The highest expected return servers can give their games, as a percent
+    pub max_return: f64,
 }
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
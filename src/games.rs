@@ -0,0 +1,82 @@
+use crate::common::{Context, Error};
+
+use sqlx::{PgExecutor, Row};
+
+/// A game that owns its own namespace of items, inventories and stats
+pub struct Game {
+    pub id: u64,
+    pub name: &'static str,
+}
+
+impl Game {
+    /// The name in lowercase without spaces or punctuation, used in component ids and
+    /// achievement events, like `highlow` for High-Low
+    pub fn key(&self) -> String {
+        self.name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect()
+    }
+}
+
+/// Record a game in the games table, called for every game at startup
+pub async fn register<'a, E>(db: E, game: &Game) -> Result<(), Error>
+where
+    E: PgExecutor<'a>,
+{
+    sqlx::query("INSERT INTO games (id, name) VALUES ($1, $2) ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name")
+        .bind(game.id as i64)
+        .bind(game.name)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+/// Look up a game's id by its display name, ignoring case
+pub async fn get_id<'a, E>(db: E, name: &str) -> Result<Option<u64>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    match sqlx::query("SELECT id FROM games WHERE LOWER(name) = LOWER($1)")
+        .bind(name)
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => Ok(Some(row.try_get::<i64, usize>(0)? as u64)),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+/// Look up a game's display name by its id
+pub async fn get_name<'a, E>(db: E, id: u64) -> Result<Option<String>, Error>
+where
+    E: PgExecutor<'a>,
+{
+    match sqlx::query("SELECT name FROM games WHERE id = $1")
+        .bind(id as i64)
+        .fetch_one(db)
+        .await
+    {
+        Ok(row) => Ok(row.try_get(0)?),
+        Err(sqlx::Error::RowNotFound) => Ok(None),
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+pub async fn autocomplete_games<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + use<'a> {
+    let names: Vec<String> = sqlx::query("SELECT name FROM games ORDER BY name")
+        .fetch_all(&ctx.data().database)
+        .await
+        .map(|rows| rows.iter().filter_map(|row| row.get(0)).collect())
+        .unwrap_or_default();
+
+    names
+        .into_iter()
+        .filter(move |name| name.to_lowercase().contains(&partial.to_lowercase()))
+}
@@ -0,0 +1,128 @@
+use crate::common::Error;
+
+use once_cell::sync::Lazy;
+use poise::serenity_prelude::{self as serenity, ComponentInteraction, UserId};
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tokio::{sync::mpsc, time::Instant};
+
+struct Target {
+    sender: mpsc::UnboundedSender<ComponentInteraction>,
+    /// The users that can click on the route's components, anyone if empty
+    users: Vec<UserId>,
+}
+
+static ROUTES: Lazy<Mutex<HashMap<String, Target>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+static NEXT: AtomicU64 = AtomicU64::new(1);
+
+/// The components of one running game or panel. Component ids look like `<route>:<action>`,
+/// and the event handler sends every click to the route it belongs to, so games never see
+/// each other's interactions. The route stops receiving clicks once it is dropped.
+pub struct Route {
+    key: String,
+    receiver: mpsc::UnboundedReceiver<ComponentInteraction>,
+}
+
+impl Route {
+    /// A route for something with an id of its own, like a stored game session
+    pub fn new(kind: &str, id: impl Display) -> Self {
+        let key = format!("{kind}-{id}");
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        ROUTES.lock().unwrap().insert(
+            key.clone(),
+            Target {
+                sender,
+                users: vec![],
+            },
+        );
+
+        Self { key, receiver }
+    }
+
+    /// A route for something that only lives as long as the command that created it
+    pub fn unique(kind: &str) -> Self {
+        Self::new(kind, format!("t{}", NEXT.fetch_add(1, Ordering::Relaxed)))
+    }
+
+    /// Only let these users click on the route's components, others are turned away by the router
+    pub fn restrict(&self, users: Vec<UserId>) {
+        if let Some(target) = ROUTES.lock().unwrap().get_mut(&self.key) {
+            target.users = users;
+        }
+    }
+
+    /// The custom id of a component that belongs to this route
+    pub fn id(&self, action: &str) -> String {
+        format!("{}:{action}", self.key)
+    }
+
+    /// Wait for the next click until the deadline, along with the action of the clicked component
+    pub async fn next(&mut self, deadline: Instant) -> Option<(ComponentInteraction, String)> {
+        let mci = tokio::time::timeout_at(deadline, self.receiver.recv())
+            .await
+            .ok()??;
+
+        let action = action(&mci.data.custom_id).to_string();
+
+        Some((mci, action))
+    }
+}
+
+impl Drop for Route {
+    fn drop(&mut self) {
+        ROUTES.lock().unwrap().remove(&self.key);
+    }
+}
+
+/// The action part of a routed component id
+pub fn action(id: &str) -> &str {
+    id.split_once(':').map(|(_, action)| action).unwrap_or(id)
+}
+
+async fn reject(
+    ctx: &serenity::Context,
+    mci: &ComponentInteraction,
+    text: &str,
+) -> Result<(), Error> {
+    mci.create_response(
+        ctx,
+        serenity::CreateInteractionResponse::Message(
+            serenity::CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .content(text),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Send a component interaction to the route it belongs to, called from the event handler
+pub async fn dispatch(ctx: &serenity::Context, mci: &ComponentInteraction) -> Result<(), Error> {
+    let Some((key, _)) = mci.data.custom_id.split_once(':') else {
+        return Ok(());
+    };
+
+    // the lock can't be held across an await
+    let delivered = match ROUTES.lock().unwrap().get(key) {
+        Some(target) if !target.users.is_empty() && !target.users.contains(&mci.user.id) => {
+            Some(false)
+        }
+        Some(target) if target.sender.send(mci.clone()).is_ok() => Some(true),
+        _ => None,
+    };
+
+    match delivered {
+        Some(true) => Ok(()),
+        Some(false) => reject(ctx, mci, "You cannot interact with this message.").await,
+        None => reject(ctx, mci, "This is no longer running.").await,
+    }
+}
@@ -1,7 +1,8 @@
 use crate::common::Error;
 
 use poise::serenity_prelude::{futures::Stream, UserId};
-use sqlx::PgExecutor;
+use sqlx::{PgExecutor, Row};
+use std::collections::HashMap;
 
 #[derive(Clone, sqlx::FromRow, Debug, PartialEq, Eq)]
 pub struct Item {
@@ -50,11 +51,12 @@ impl Inventory {
         let x = sqlx::query_as(
             r#"
             SELECT id, name, game, item, data FROM items
-            where item = $1 AND owner = $2
+            WHERE item = $1 AND owner = $2 AND ($3::BIGINT IS NULL OR game = $3)
             "#,
         )
         .bind(item as i64)
         .bind(self.user.get() as i64)
+        .bind(self.game.map(|g| g as i64))
         .fetch_one(db)
         .await
         .ok();
@@ -69,11 +71,12 @@ impl Inventory {
         let x = sqlx::query_as(
             r#"
             SELECT id, name, game, item, data FROM items
-            where name = $1 AND user = $2
+            WHERE name = $1 AND owner = $2 AND ($3::BIGINT IS NULL OR game = $3)
             "#,
         )
         .bind(name)
         .bind(self.user.get() as i64)
+        .bind(self.game.map(|g| g as i64))
         .fetch_one(db)
         .await
         .ok();
@@ -81,6 +84,79 @@ impl Inventory {
         Ok(x)
     }
 
+    /// How many items of a given type the user owns
+    pub async fn count_item_of_type<'a, E>(&self, db: E, item: u64) -> Result<i64, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        let row = sqlx::query(
+            r#"
+            SELECT COUNT(*) FROM items
+            WHERE owner = $1 AND item = $2 AND ($3::BIGINT IS NULL OR game = $3)
+            "#,
+        )
+        .bind(self.user.get() as i64)
+        .bind(item as i64)
+        .bind(self.game.map(|g| g as i64))
+        .fetch_one(db)
+        .await?;
+
+        Ok(row.get(0))
+    }
+
+    /// How many of each item type the user owns, keyed by item type
+    pub async fn item_counts<'a, E>(&self, db: E) -> Result<HashMap<i64, i64>, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        let rows = sqlx::query(
+            r#"
+            SELECT item, COUNT(*) FROM items
+            WHERE owner = $1 AND ($2::BIGINT IS NULL OR game = $2)
+            GROUP BY item
+            "#,
+        )
+        .bind(self.user.get() as i64)
+        .bind(self.game.map(|g| g as i64))
+        .fetch_all(db)
+        .await?;
+
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// Remove `count` items of a given type. Returns false if the user did not own enough,
+    /// in which case the caller should roll back its transaction.
+    pub async fn take_items_of_type<'a, E>(
+        &self,
+        db: E,
+        item: u64,
+        count: i64,
+    ) -> Result<bool, Error>
+    where
+        E: PgExecutor<'a>,
+    {
+        let removed = sqlx::query(
+            r#"
+            DELETE FROM items
+            WHERE id IN (
+                SELECT id FROM items
+                WHERE owner = $1 AND item = $2 AND ($3::BIGINT IS NULL OR game = $3)
+                LIMIT $4
+                FOR UPDATE
+            )
+            "#,
+        )
+        .bind(self.user.get() as i64)
+        .bind(item as i64)
+        .bind(self.game.map(|g| g as i64))
+        .bind(count)
+        .execute(db)
+        .await?
+        .rows_affected();
+
+        Ok(removed == count as u64)
+    }
+
     pub async fn remove_item<'a, E>(&self, db: E, item: i64) -> Result<(), Error>
     where
         E: PgExecutor<'a>,
@@ -1,6 +1,8 @@
 mod commands;
 
 pub mod common;
+pub mod games;
+pub mod interactions;
 pub mod inventory;
 use crate::commands::self_roles;
 use crate::common::{Context, Data, Error};
@@ -20,6 +22,10 @@ struct BotArgs {
     /// Prefix for the bot. If unspecified, the bot will not have one and will also not have access to message content.
     #[arg(short, long)]
     prefix: Option<String>,
+
+    /// The highest expected return, as a percent of every bet, that servers can set their games up to pay.
+    #[arg(long, default_value_t = 100.0)]
+    max_return: f64,
 }
 
 async fn event_handler(
@@ -36,6 +42,11 @@ async fn event_handler(
                 return Ok(());
             }
         }
+        serenity::FullEvent::InteractionCreate {
+            interaction: serenity::Interaction::Component(mci),
+        } => {
+            interactions::dispatch(ctx, mci).await?;
+        }
         serenity::FullEvent::GuildMemberRemoval { guild_id, user, .. } => {
             let mut tx = data.database.begin().await?;
 
@@ -117,7 +128,7 @@ async fn main() -> Result<(), Error> {
             },
             ..Default::default()
         })
-        .setup(|ctx, _ready, framework| {
+        .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
 
@@ -154,13 +165,30 @@ async fn main() -> Result<(), Error> {
                     r#"
                     CREATE TABLE IF NOT EXISTS games (
                         id BIGSERIAL PRIMARY KEY,
-                        name CHAR[255]
+                        name TEXT NOT NULL
                     )
                     "#,
                 )
                 .execute(&database)
                 .await?;
 
+                // older databases were created with an unusable CHAR[255] name column
+                sqlx::query(
+                    r#"
+                    DO $$
+                    BEGIN
+                        IF EXISTS (
+                            SELECT 1 FROM information_schema.columns
+                            WHERE table_name = 'games' AND column_name = 'name' AND data_type = 'ARRAY'
+                        ) THEN
+                            ALTER TABLE games ALTER COLUMN name TYPE TEXT USING array_to_string(name, '');
+                        END IF;
+                    END $$
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
                 sqlx::query(
                     r#"
                     CREATE TABLE IF NOT EXISTS items (
@@ -176,6 +204,45 @@ async fn main() -> Result<(), Error> {
                 .execute(&database)
                 .await?;
 
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS crate_openings (
+                        id BIGSERIAL PRIMARY KEY,
+                        userid BIGINT NOT NULL,
+                        guildid BIGINT,
+                        crate BIGINT NOT NULL,
+                        drop_key TEXT NOT NULL,
+                        tokens INT NOT NULL,
+                        opened TIMESTAMPTZ NOT NULL DEFAULT now()
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                // drops used to be stored by their position in the crate, which can't be
+                // matched to a drop once the crate changes
+                sqlx::query("ALTER TABLE crate_openings ADD COLUMN IF NOT EXISTS drop_key TEXT NOT NULL DEFAULT ''")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query("ALTER TABLE crate_openings DROP COLUMN IF EXISTS drop_index")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS buffs (
+                        id BIGSERIAL PRIMARY KEY,
+                        userid BIGINT NOT NULL,
+                        effect JSON NOT NULL,
+                        bets INT NOT NULL
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
                 sqlx::query(
                     r#"
                     CREATE TABLE IF NOT EXISTS dailies (
@@ -202,11 +269,278 @@ async fn main() -> Result<(), Error> {
                 .execute(&database)
                 .await?;
 
+                sqlx::query("ALTER TABLE settings ADD COLUMN IF NOT EXISTS blackjack_rules JSONB")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query("ALTER TABLE settings ADD COLUMN IF NOT EXISTS duel_cut INT")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query("ALTER TABLE settings ADD COLUMN IF NOT EXISTS crash_edge INT")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query("ALTER TABLE settings ADD COLUMN IF NOT EXISTS race_cut INT")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query("ALTER TABLE settings ADD COLUMN IF NOT EXISTS wager_chance INT")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query("ALTER TABLE settings ADD COLUMN IF NOT EXISTS wager_payout INT")
+                    .execute(&database)
+                    .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS user_settings (
+                        userid BIGINT NOT NULL PRIMARY KEY,
+                        text_only BOOLEAN
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS slot_configs (
+                        guildid BIGINT NOT NULL PRIMARY KEY,
+                        config JSON NOT NULL
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS sessions (
+                        id SERIAL PRIMARY KEY,
+                        game BIGINT NOT NULL,
+                        channel BIGINT NOT NULL,
+                        message BIGINT NOT NULL,
+                        state JSON NOT NULL,
+                        status TEXT NOT NULL,
+                        started TIMESTAMPTZ NOT NULL DEFAULT now()
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS escrow (
+                        session INT NOT NULL REFERENCES sessions (id),
+                        userid BIGINT NOT NULL,
+                        amount INT NOT NULL,
+                        PRIMARY KEY (session, userid)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS race_schedules (
+                        channel BIGINT NOT NULL PRIMARY KEY,
+                        guildid BIGINT NOT NULL,
+                        kind TEXT NOT NULL,
+                        every INT NOT NULL,
+                        next TIMESTAMPTZ NOT NULL
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS lotteries (
+                        guildid BIGINT NOT NULL PRIMARY KEY,
+                        channel BIGINT NOT NULL,
+                        price INT NOT NULL,
+                        every INT NOT NULL,
+                        next TIMESTAMPTZ NOT NULL,
+                        pot INT NOT NULL DEFAULT 0
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS lottery_tickets (
+                        guildid BIGINT NOT NULL,
+                        userid BIGINT NOT NULL,
+                        count INT NOT NULL,
+                        spent INT NOT NULL DEFAULT 0,
+                        PRIMARY KEY (guildid, userid)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    "ALTER TABLE lottery_tickets ADD COLUMN IF NOT EXISTS spent INT NOT NULL DEFAULT 0",
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS fair_seeds (
+                        userid BIGINT NOT NULL PRIMARY KEY,
+                        server_seed TEXT NOT NULL,
+                        client_seed TEXT NOT NULL,
+                        nonce INT NOT NULL DEFAULT 0
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS fair_bets (
+                        userid BIGINT NOT NULL,
+                        seed_hash TEXT NOT NULL,
+                        nonce INT NOT NULL,
+                        draw TEXT NOT NULL,
+                        PRIMARY KEY (userid, seed_hash, nonce)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS fair_rounds (
+                        seed_hash TEXT NOT NULL PRIMARY KEY,
+                        draw TEXT NOT NULL
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS gambling_history (
+                        userid BIGINT NOT NULL,
+                        game BIGINT NOT NULL,
+                        bet INT NOT NULL,
+                        net INT NOT NULL,
+                        at TIMESTAMPTZ NOT NULL DEFAULT now()
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    "CREATE INDEX IF NOT EXISTS gambling_history_userid ON gambling_history (userid, at)",
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS gambling_stats (
+                        userid BIGINT NOT NULL,
+                        game BIGINT NOT NULL,
+                        bets INT NOT NULL DEFAULT 0,
+                        wins INT NOT NULL DEFAULT 0,
+                        losses INT NOT NULL DEFAULT 0,
+                        pushes INT NOT NULL DEFAULT 0,
+                        wagered BIGINT NOT NULL DEFAULT 0,
+                        net BIGINT NOT NULL DEFAULT 0,
+                        biggest_win INT NOT NULL DEFAULT 0,
+                        biggest_loss INT NOT NULL DEFAULT 0,
+                        counters JSONB NOT NULL DEFAULT '{}',
+                        PRIMARY KEY (userid, game)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS gambling_limits (
+                        userid BIGINT NOT NULL,
+                        guildid BIGINT NOT NULL,
+                        kind TEXT NOT NULL,
+                        amount INT,
+                        starts TIMESTAMPTZ NOT NULL,
+                        PRIMARY KEY (userid, guildid, kind, starts)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS achievements (
+                        userid BIGINT NOT NULL,
+                        achievement TEXT NOT NULL,
+                        channel BIGINT NOT NULL,
+                        at TIMESTAMPTZ NOT NULL DEFAULT now(),
+                        announced BOOLEAN NOT NULL DEFAULT false,
+                        PRIMARY KEY (userid, achievement)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS achievement_progress (
+                        userid BIGINT NOT NULL,
+                        event TEXT NOT NULL,
+                        progress BIGINT NOT NULL,
+                        PRIMARY KEY (userid, event)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                sqlx::query(
+                    r#"
+                    CREATE TABLE IF NOT EXISTS gambling_exclusions (
+                        userid BIGINT NOT NULL,
+                        guildid BIGINT NOT NULL,
+                        until TIMESTAMPTZ NOT NULL,
+                        PRIMARY KEY (userid, guildid)
+                    )
+                    "#,
+                )
+                .execute(&database)
+                .await?;
+
+                for game in commands::games() {
+                    games::register(&database, &game).await?;
+                }
+
+                commands::resume_sessions(ctx, &database).await?;
+                commands::start_schedules(ctx, &database);
+
                 println!("Bot is ready!");
 
                 Ok(Data {
                     database,
                     prefix: args.prefix,
+                    max_return: args.max_return,
                 })
             })
         })